@@ -0,0 +1,61 @@
+// pipelined against one round trip per command.  runs against the stand-in server, or a real
+// one when REDIS_ADDR is set:
+//
+//   REDIS_ADDR=127.0.0.1:6379 cargo bench
+use std::time::{Duration, Instant};
+
+use redis_cli::client::RedisClient;
+use redis_cli::commands::Cmd;
+use redis_cli::pipeline::Pipeline;
+use redis_cli::protocol::Protocol;
+use redis_cli::test_server;
+
+const COMMANDS: u64 = 20_000;
+const BATCHES: &[u64] = &[10, 100, 1000];
+
+fn set(i: u64) -> Cmd {
+    Cmd::new("SET").arg(format!("bench:{}", i)).arg(i)
+}
+
+fn report(what: &str, took: Duration) {
+    println!(
+        "{:<20} {} commands in {:>8.3}s, {:>9.0} per second",
+        what,
+        COMMANDS,
+        took.as_secs_f64(),
+        COMMANDS as f64 / took.as_secs_f64()
+    );
+}
+
+fn main() {
+    let addr = std::env::var("REDIS_ADDR").unwrap_or_else(|_| test_server::spawn(Protocol::Resp3));
+    let mut cli = RedisClient::new(&addr);
+    if !cli.connected() {
+        eprintln!("Could not connect to Redis at {}", addr);
+        std::process::exit(1);
+    }
+
+    let start = Instant::now();
+    for i in 0..COMMANDS {
+        cli.query(&set(i)).unwrap();
+    }
+    report("sequential", start.elapsed());
+
+    for &batch in BATCHES {
+        let start = Instant::now();
+        let mut pipe = Pipeline::new();
+        for i in 0..COMMANDS {
+            pipe.add(set(i));
+            if pipe.len() as u64 == batch || i == COMMANDS - 1 {
+                for result in pipe.execute(&mut cli).unwrap() {
+                    result.unwrap();
+                }
+                pipe.clear();
+            }
+        }
+        report(&format!("pipelined by {}", batch), start.elapsed());
+    }
+
+    let keys: Vec<String> = (0..COMMANDS).map(|i| format!("bench:{}", i)).collect();
+    cli.del(&keys).unwrap();
+}
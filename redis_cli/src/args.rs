@@ -0,0 +1,112 @@
+use crate::types::*;
+
+// split a command line into arguments the way redis-cli does.  words are separated by
+// whitespace and can be quoted:
+//
+//   "..."  takes \n \r \t \b \a \\ \" and \xHH escapes
+//   '...'  only \' is special
+//
+// a closing quote has to be followed by whitespace or the end of the line
+pub fn split_args(line: &str) -> RedisResult<Vec<Vec<u8>>> {
+    let invalid = || RedisError::InvalidArgument("Invalid argument(s)".to_string());
+    let line = line.as_bytes();
+    let mut args = vec![];
+    let mut i = 0;
+    loop {
+        while i < line.len() && line[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i == line.len() {
+            return Ok(args);
+        }
+
+        let mut arg = vec![];
+        let quote = match line[i] {
+            q @ (b'"' | b'\'') => {
+                i += 1;
+                Some(q)
+            }
+            _ => None,
+        };
+        loop {
+            let c = match line.get(i) {
+                Some(c) => *c,
+                // ran off the end inside quotes
+                None if quote.is_some() => return Err(invalid()),
+                None => break,
+            };
+            match quote {
+                None if c.is_ascii_whitespace() => break,
+                None => arg.push(c),
+                Some(q) if c == q => {
+                    if line.get(i + 1).is_some_and(|c| !c.is_ascii_whitespace()) {
+                        return Err(invalid());
+                    }
+                    i += 1;
+                    break;
+                }
+                Some(b'"') if c == b'\\' && i + 1 < line.len() => {
+                    i += 1;
+                    arg.push(match line[i] {
+                        b'n' => b'\n',
+                        b'r' => b'\r',
+                        b't' => b'\t',
+                        b'b' => 8,
+                        b'a' => 7,
+                        b'x' => match hex_byte(line.get(i + 1..i + 3)) {
+                            Some(b) => {
+                                i += 2;
+                                b
+                            }
+                            None => b'x',
+                        },
+                        other => other,
+                    });
+                }
+                Some(b'\'') if c == b'\\' && line.get(i + 1) == Some(&b'\'') => {
+                    i += 1;
+                    arg.push(b'\'');
+                }
+                Some(_) => arg.push(c),
+            }
+            i += 1;
+        }
+        args.push(arg);
+    }
+}
+
+fn hex_byte(digits: Option<&[u8]>) -> Option<u8> {
+    let digits = std::str::from_utf8(digits?).ok()?;
+    u8::from_str_radix(digits, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(line: &str) -> Vec<String> {
+        split_args(line)
+            .unwrap()
+            .into_iter()
+            .map(|a| String::from_utf8(a).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(split("  set  foo bar "), vec!["set", "foo", "bar"]);
+        assert_eq!(split(""), Vec::<String>::new());
+        assert_eq!(split(r#"set k "a b""#), vec!["set", "k", "a b"]);
+        assert_eq!(split("set k 'a \"b\" c'"), vec!["set", "k", "a \"b\" c"]);
+        assert_eq!(split(r#"set "" ''"#), vec!["set", "", ""]);
+        assert_eq!(split(r#""\n\t\\\"\x41\xzz""#), vec!["\n\t\\\"Axzz"]);
+        assert_eq!(split(r"'it\'s' 'a\nb'"), vec!["it's", "a\\nb"]);
+        assert_eq!(split_args(r#""\xff\x00""#).unwrap(), vec![vec![0xff, 0x00]]);
+        // quotes in the middle of a word are just characters
+        assert_eq!(split(r#"a"b c'd"#), vec![r#"a"b"#, "c'd"]);
+
+        assert!(split_args(r#"set "foo"#).is_err());
+        assert!(split_args("set 'foo").is_err());
+        assert!(split_args(r#"set "foo"bar"#).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+// a client for async code.  one connection, shared by every clone of the client, with any number
+// of commands in flight on it at once.  the server answers in the order commands went out, so
+// each reply goes to whoever's at the front of the queue of callers still waiting on one
+//
+// a task writes commands out, taking everything that's queued up by then in one write, and
+// another reads replies back.  the writer adds a caller to the queue before its command goes out,
+// so there's always someone waiting by the time its reply turns up
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::commands::{checked, Cmd};
+use crate::protocol::*;
+use crate::types::*;
+
+type ReplyTx = oneshot::Sender<RedisResult<WireType>>;
+
+struct Request {
+    // the command, already encoded
+    bytes: Vec<u8>,
+    reply: ReplyTx,
+}
+
+#[derive(Debug, Clone)]
+pub struct AsyncRedisClient {
+    requests: mpsc::UnboundedSender<Request>,
+    protocol: Protocol,
+    pushes: Arc<Mutex<mpsc::UnboundedReceiver<WireVec>>>,
+}
+
+impl AsyncRedisClient {
+    // connect and ask for resp3, settling for resp2 the same way RedisClient does.  the
+    // connection closes once the last clone of the client is dropped
+    pub async fn connect(addr: &str) -> RedisResult<Self> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        let (read, write) = stream.into_split();
+        let (requests, requests_rx) = mpsc::unbounded_channel();
+        let (pending, pending_rx) = mpsc::unbounded_channel();
+        let (pushes, pushes_rx) = mpsc::unbounded_channel();
+        tokio::spawn(write_loop(write, requests_rx, pending));
+        tokio::spawn(read_loop(read, pending_rx, pushes));
+
+        let mut cli = AsyncRedisClient {
+            requests,
+            protocol: Protocol::Resp2,
+            pushes: Arc::new(Mutex::new(pushes_rx)),
+        };
+        cli.protocol = match cli.call(&["HELLO", "3"]).await? {
+            WireType::Map(_) => Protocol::Resp3,
+            _ => Protocol::Resp2,
+        };
+        Ok(cli)
+    }
+
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    // the oldest push frame the server has sent, if any
+    pub fn next_push(&self) -> Option<WireVec> {
+        self.pushes.lock().unwrap().try_recv().ok()
+    }
+
+    // the raw reply, error replies included
+    pub async fn call<A: AsRef<[u8]>>(&self, args: &[A]) -> RedisResult<WireType> {
+        let reply = self.send(args)?;
+        reply.await.unwrap_or_else(|_| Err(RedisError::closed()))
+    }
+
+    // several commands queued up back to back, their replies in the same order
+    pub async fn call_many<C, A>(&self, cmds: &[C]) -> RedisResult<Vec<WireType>>
+    where
+        C: AsRef<[A]>,
+        A: AsRef<[u8]>,
+    {
+        let waiting = cmds
+            .iter()
+            .map(|args| self.send(args.as_ref()))
+            .collect::<RedisResult<Vec<_>>>()?;
+        let mut replies = Vec::with_capacity(waiting.len());
+        for reply in waiting {
+            replies.push(reply.await.unwrap_or_else(|_| Err(RedisError::closed()))?);
+        }
+        Ok(replies)
+    }
+
+    // send a command, turning an error reply into an error
+    pub async fn query(&self, cmd: &Cmd) -> RedisResult<WireType> {
+        checked(self.call(cmd.as_args()).await?)
+    }
+
+    fn send<A: AsRef<[u8]>>(
+        &self,
+        args: &[A],
+    ) -> RedisResult<oneshot::Receiver<RedisResult<WireType>>> {
+        let mut bytes = vec![];
+        encode_command(args, &mut bytes);
+        let (reply, waiting) = oneshot::channel();
+        self.requests
+            .send(Request { bytes, reply })
+            .map_err(|_| RedisError::closed())?;
+        Ok(waiting)
+    }
+}
+
+async fn write_loop(
+    mut write: OwnedWriteHalf,
+    mut requests: mpsc::UnboundedReceiver<Request>,
+    pending: mpsc::UnboundedSender<ReplyTx>,
+) {
+    let mut buf = vec![];
+    while let Some(req) = requests.recv().await {
+        let mut next = Some(req);
+        while let Some(req) = next {
+            buf.extend_from_slice(&req.bytes);
+            // the reader's gone, and the connection with it
+            if let Err(mpsc::error::SendError(reply)) = pending.send(req.reply) {
+                let _ = reply.send(Err(RedisError::closed()));
+            }
+            next = requests.try_recv().ok();
+        }
+        // the reader finds out about a broken connection too, and fails whoever's waiting
+        if write.write_all(&buf).await.is_err() {
+            return;
+        }
+        buf.clear();
+    }
+}
+
+async fn read_loop(
+    mut read: OwnedReadHalf,
+    mut pending: mpsc::UnboundedReceiver<ReplyTx>,
+    pushes: mpsc::UnboundedSender<WireVec>,
+) {
+    let mut decoder = Decoder::new();
+    let mut buf = vec![0u8; 16 * 1024];
+    let err = loop {
+        match decoder.decode() {
+            Ok(Some(WireType::Push(push))) => {
+                let _ = pushes.send(push);
+                continue;
+            }
+            Ok(Some(reply)) => match pending.try_recv() {
+                Ok(waiting) => {
+                    let _ = waiting.send(Ok(reply));
+                    continue;
+                }
+                // a reply to nothing, there's no telling which is which from here on
+                Err(_) => break RedisError::UnexpectedReply(reply),
+            },
+            Ok(None) => {}
+            Err(e) => break e,
+        }
+        match read.read(&mut buf).await {
+            Ok(0) => break RedisError::closed(),
+            Ok(n) => decoder.feed(&buf[..n]),
+            Err(e) => break e.into(),
+        }
+    };
+
+    // the first caller still waiting gets what went wrong, the rest that the connection's gone
+    let mut err = Some(err);
+    pending.close();
+    while let Some(waiting) = pending.recv().await {
+        let _ = waiting.send(Err(err.take().unwrap_or_else(RedisError::closed)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server;
+
+    fn bulk(s: &str) -> WireType {
+        WireType::BulkString(s.as_bytes().to_vec())
+    }
+
+    #[tokio::test]
+    async fn commands_and_replies() {
+        let cli = AsyncRedisClient::connect(&test_server::spawn(Protocol::Resp3))
+            .await
+            .unwrap();
+        assert_eq!(cli.protocol(), Protocol::Resp3);
+        cli.call(&["SET", "k", "v"]).await.unwrap();
+        assert_eq!(cli.call(&["GET", "k"]).await.unwrap(), bulk("v"));
+        assert_eq!(cli.call(&["GET", "nope"]).await.unwrap(), WireType::Null);
+        let err = cli.query(&Cmd::new("INCR").arg("k")).await.unwrap_err();
+        assert_eq!(err.code(), Some("ERR"));
+
+        let replies = cli
+            .call_many(&[vec!["RPUSH", "l", "a"], vec!["LRANGE", "l", "0", "-1"]])
+            .await
+            .unwrap();
+        assert_eq!(
+            replies,
+            vec![WireType::Integer(1), WireType::Array(vec![bulk("a")])]
+        );
+
+        let cli = AsyncRedisClient::connect(&test_server::spawn(Protocol::Resp2))
+            .await
+            .unwrap();
+        assert_eq!(cli.protocol(), Protocol::Resp2);
+        assert_eq!(
+            cli.call(&["GET", "nope"]).await.unwrap(),
+            WireType::NullBulkString
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn many_callers_one_connection() {
+        let cli = AsyncRedisClient::connect(&test_server::spawn(Protocol::Resp3))
+            .await
+            .unwrap();
+        let tasks: Vec<_> = (0..200)
+            .map(|i| {
+                let cli = cli.clone();
+                tokio::spawn(async move {
+                    let key = format!("k{}", i);
+                    cli.call(&["SET", &key, &i.to_string()]).await.unwrap();
+                    cli.call(&["INCR", "hits"]).await.unwrap();
+                    // each caller gets its own reply back, not whichever came in next
+                    assert_eq!(
+                        cli.call(&["GET", &key]).await.unwrap(),
+                        bulk(&i.to_string())
+                    );
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(cli.call(&["GET", "hits"]).await.unwrap(), bulk("200"));
+    }
+
+    #[tokio::test]
+    async fn push_frames() {
+        let cli = AsyncRedisClient::connect(&test_server::spawn(Protocol::Resp3))
+            .await
+            .unwrap();
+        cli.call(&["CLIENT", "TRACKING", "ON"]).await.unwrap();
+        cli.call(&["GET", "k"]).await.unwrap();
+        assert_eq!(cli.next_push(), None);
+        cli.call(&["SET", "k", "v"]).await.unwrap();
+        assert_eq!(
+            cli.next_push(),
+            Some(vec![bulk("invalidate"), WireType::Array(vec![bulk("k")])])
+        );
+    }
+
+    #[tokio::test]
+    async fn server_goes_away() {
+        let server = test_server::Server::start(Protocol::Resp3, "127.0.0.1:0", None);
+        let cli = AsyncRedisClient::connect(server.addr()).await.unwrap();
+        cli.call(&["PING"]).await.unwrap();
+        server.stop();
+        assert!(matches!(cli.call(&["PING"]).await, Err(RedisError::Io(_))));
+        assert!(cli.call(&["PING"]).await.is_err());
+    }
+}
@@ -0,0 +1,709 @@
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::hash::{BuildHasher, Hasher};
+use std::io::prelude::*;
+use std::io::ErrorKind;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::args::split_args;
+use crate::protocol::*;
+use crate::types::*;
+
+// how to connect, and what to do when the connection goes away
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    pub connect_timeout: Option<Duration>,
+    // a reply that takes longer than this fails the command and drops the connection, it can't
+    // be told apart from the next one if it does turn up
+    pub read_timeout: Option<Duration>,
+    pub write_timeout: Option<Duration>,
+    // tries at getting the connection back once it's lost, 0 to leave it lost
+    pub reconnect_attempts: u32,
+    // the wait before the second try, doubling for each one after up to max_backoff
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+    // PING a connection that's sat idle this long before trusting it with a command
+    pub health_check_interval: Option<Duration>,
+    // AUTH with these on every connect.  a user of None is redis's default user
+    pub username: Option<String>,
+    pub password: Option<String>,
+    // the database to start out on.  a SELECT later on moves the client for good, reconnects
+    // included
+    pub db: i64,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        ConnectOptions {
+            connect_timeout: None,
+            read_timeout: None,
+            write_timeout: None,
+            reconnect_attempts: 3,
+            min_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            health_check_interval: None,
+            username: None,
+            password: None,
+            db: 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RedisClient {
+    stream: Option<TcpStream>,
+    endpoint: String,
+    options: ConnectOptions,
+    prompt: String,
+    // replies can straddle reads, whatever's left over from one stays here for the next
+    decoder: Decoder,
+    protocol: Protocol,
+    // push frames that turned up while waiting on a reply, oldest first
+    pushes: VecDeque<WireVec>,
+    // the database SELECT last switched to, and the AUTH that last worked.  both get sent again
+    // on a new connection so it picks up where the old one left off
+    db: i64,
+    auth: Option<Vec<Vec<u8>>>,
+    // inside a MULTI that hasn't been EXECed or DISCARDed yet.  the queued commands are on the
+    // connection, a new one won't have them
+    multi: bool,
+    last_used: Instant,
+}
+
+impl RedisClient {
+    pub fn new(addr: &str) -> Self {
+        Self::with_options(addr, ConnectOptions::default())
+    }
+
+    // a single try at connecting, failing that the client starts out not connected and tries
+    // again on the first command
+    pub fn with_options(addr: &str, options: ConnectOptions) -> Self {
+        let auth = options.password.as_ref().map(|pass| {
+            let mut auth = vec![b"AUTH".to_vec()];
+            auth.extend(options.username.as_ref().map(|u| u.as_bytes().to_vec()));
+            auth.push(pass.as_bytes().to_vec());
+            auth
+        });
+        let db = options.db;
+        let mut cli = RedisClient {
+            stream: None,
+            endpoint: String::from(addr),
+            options,
+            prompt: String::new(),
+            decoder: Decoder::new(),
+            protocol: Protocol::Resp2,
+            pushes: VecDeque::new(),
+            db,
+            auth,
+            multi: false,
+            last_used: Instant::now(),
+        };
+        let _ = cli.connect();
+        cli.update_prompt();
+        cli
+    }
+
+    fn connect(&mut self) -> RedisResult<()> {
+        self.stream = None;
+        let stream = match self.options.connect_timeout {
+            Some(timeout) => {
+                let mut err =
+                    std::io::Error::new(ErrorKind::NotFound, "no addresses to connect to");
+                let mut stream = None;
+                for addr in self.endpoint.to_socket_addrs()? {
+                    match TcpStream::connect_timeout(&addr, timeout) {
+                        Ok(s) => {
+                            stream = Some(s);
+                            break;
+                        }
+                        Err(e) => err = e,
+                    }
+                }
+                stream.ok_or(err)?
+            }
+            None => TcpStream::connect(&self.endpoint)?,
+        };
+        stream.set_read_timeout(self.options.read_timeout)?;
+        stream.set_write_timeout(self.options.write_timeout)?;
+        stream.set_nodelay(true)?;
+        self.stream = Some(stream);
+        self.decoder = Decoder::new();
+        self.multi = false;
+        self.last_used = Instant::now();
+
+        if let Err(e) = self.handshake() {
+            self.stream = None;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    // AUTH comes first, a server with a password won't answer anything else.  then ask for
+    // resp3: servers older than 6 don't know HELLO and ones that can't do 3 say NOPROTO, either
+    // way it's an error reply and the connection carries on in resp2
+    fn handshake(&mut self) -> RedisResult<()> {
+        if let Some(auth) = self.auth.clone() {
+            if let WireType::Error(e) = self.call_one(&auth)? {
+                return Err(RedisError::server(&e));
+            }
+        }
+        self.protocol = match self.call_one(&["HELLO", "3"])? {
+            WireType::Map(_) => Protocol::Resp3,
+            _ => Protocol::Resp2,
+        };
+        if self.db != 0 {
+            let db = self.db.to_string();
+            if let WireType::Error(e) = self.call_one(&["SELECT", &db])? {
+                return Err(RedisError::server(&e));
+            }
+        }
+        Ok(())
+    }
+
+    // keep trying to connect, backing off between tries
+    fn reconnect(&mut self) -> RedisResult<()> {
+        let mut err = RedisError::NotConnected;
+        for attempt in 0..self.options.reconnect_attempts {
+            if attempt > 0 {
+                thread::sleep(self.backoff(attempt - 1));
+            }
+            match self.connect() {
+                Ok(()) => return Ok(()),
+                Err(e) => err = e,
+            }
+        }
+        Err(err)
+    }
+
+    // min_backoff doubled n times, capped at max_backoff.  then anywhere from half of that to
+    // all of it, so clients that lost the same server don't all come back at the same moment
+    fn backoff(&self, n: u32) -> Duration {
+        let wait = self
+            .options
+            .min_backoff
+            .saturating_mul(1 << n.min(31))
+            .min(self.options.max_backoff);
+        let jitter = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        wait / 2 + (wait / 2).mul_f64(jitter)
+    }
+
+    pub fn connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    // what redis-cli would show: the endpoint, and the database if it isn't 0
+    pub fn prompt(&self) -> &str {
+        &self.prompt
+    }
+
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    // the oldest push frame the server has sent, if any
+    pub fn next_push(&mut self) -> Option<WireVec> {
+        self.pushes.pop_front()
+    }
+
+    // PING the server, connecting again first if need be.  how long it took to answer if it did
+    pub fn health_check(&mut self) -> RedisResult<Duration> {
+        let start = Instant::now();
+        match self.call(&["PING"])? {
+            WireType::SimpleString(_) => Ok(start.elapsed()),
+            WireType::Error(e) => Err(RedisError::server(&e)),
+            reply => Err(RedisError::UnexpectedReply(reply)),
+        }
+    }
+
+    // a command line as typed at redis-cli, quoting and all
+    pub fn send_command(&mut self, cmd: &str) -> RedisResult<WireType> {
+        let args = split_args(cmd)?;
+        self.call(&args)
+    }
+
+    pub fn call<A: AsRef<[u8]>>(&mut self, args: &[A]) -> RedisResult<WireType> {
+        let mut replies = self.call_many(&[args])?;
+        Ok(replies.remove(0))
+    }
+
+    // several commands at once: all of them go out in a single write, then their replies are
+    // read back in the same order
+    pub fn call_many<C, A>(&mut self, cmds: &[C]) -> RedisResult<Vec<WireType>>
+    where
+        C: AsRef<[A]>,
+        A: AsRef<[u8]>,
+    {
+        let result = self.call_reconnecting(cmds);
+        self.update_prompt();
+        result
+    }
+
+    // a connection that's gone away gets one more go once it's back, like redis-cli does.  one
+    // the server hung up while it sat idle is noticed before anything goes out on it.  past
+    // that, a batch only goes again if none of it made it out: a write that failed part way may
+    // have delivered some of it, and once it's all gone out the server may have run every
+    // command and died before answering, so INCR and friends would run twice.  neither does a
+    // transaction, which a new connection can't pick up where the old one left off
+    fn call_reconnecting<C, A>(&mut self, cmds: &[C]) -> RedisResult<Vec<WireType>>
+    where
+        C: AsRef<[A]>,
+        A: AsRef<[u8]>,
+    {
+        // a PING that fails drops the connection, same as any other command
+        if self.stream.is_some()
+            && self
+                .options
+                .health_check_interval
+                .is_some_and(|every| self.last_used.elapsed() >= every)
+        {
+            let _ = self.call_impl(&[["PING"]]);
+        }
+        if self.stream.as_ref().is_some_and(hung_up) {
+            self.stream = None;
+        }
+        if self.stream.is_none() {
+            self.reconnect()?;
+        }
+        let transaction = self.multi
+            || cmds
+                .iter()
+                .any(|args| is_cmd(args.as_ref(), b"multi") || is_cmd(args.as_ref(), b"exec"));
+        match self.call_impl(cmds) {
+            Err((RedisError::Io(e), true))
+                if !matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock)
+                    && !transaction
+                    && self.options.reconnect_attempts > 0 =>
+            {
+                self.reconnect()?;
+                self.call_impl(cmds).map_err(|(e, _)| e)
+            }
+            result => result.map_err(|(e, _)| e),
+        }
+    }
+
+    fn call_one<A: AsRef<[u8]>>(&mut self, args: &[A]) -> RedisResult<WireType> {
+        Ok(self.call_impl(&[args]).map_err(|(e, _)| e)?.remove(0))
+    }
+
+    // any error here leaves the connection in an unknown state, part way through a reply or
+    // with replies still to come.  it's dropped rather than risk mixing those up.  an error
+    // comes with whether it's safe to send the batch again: true if none of it was sent
+    fn call_impl<C, A>(&mut self, cmds: &[C]) -> Result<Vec<WireType>, (RedisError, bool)>
+    where
+        C: AsRef<[A]>,
+        A: AsRef<[u8]>,
+    {
+        let result = self.send_and_read(cmds);
+        match &result {
+            Ok(_) => self.last_used = Instant::now(),
+            Err(_) => self.stream = None,
+        }
+        result
+    }
+
+    fn send_and_read<C, A>(&mut self, cmds: &[C]) -> Result<Vec<WireType>, (RedisError, bool)>
+    where
+        C: AsRef<[A]>,
+        A: AsRef<[u8]>,
+    {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => return Err((RedisError::NotConnected, true)),
+        };
+        let mut buf = vec![];
+        for args in cmds {
+            encode_command(args.as_ref(), &mut buf);
+        }
+        // write_all can't say how much went out before it failed
+        let mut written = 0;
+        while written < buf.len() {
+            match stream.write(&buf[written..]) {
+                Ok(0) => {
+                    let e = std::io::Error::from(ErrorKind::WriteZero);
+                    return Err((e.into(), written == 0));
+                }
+                Ok(n) => written += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err((e.into(), written == 0)),
+            }
+        }
+
+        let mut replies = Vec::with_capacity(cmds.len());
+        for args in cmds {
+            let reply = self.read_reply().map_err(|e| (e, false))?;
+            self.track_session(args.as_ref(), &reply);
+            replies.push(reply);
+        }
+        Ok(replies)
+    }
+
+    // keep track of the database for the prompt, like redis-cli does, and of both SELECT and
+    // AUTH for reconnecting.  QUEUED inside a MULTI doesn't count, that hasn't happened yet
+    fn track_session<A: AsRef<[u8]>>(&mut self, args: &[A], reply: &WireType) {
+        if is_cmd(args, b"exec") || is_cmd(args, b"discard") {
+            self.multi = false;
+            return;
+        }
+        if !matches!(reply, WireType::SimpleString(s) if s == "OK") {
+            return;
+        }
+        if is_cmd(args, b"multi") {
+            self.multi = true;
+        } else if is_cmd(args, b"auth") {
+            self.auth = Some(args.iter().map(|a| a.as_ref().to_vec()).collect());
+        } else if let [_, db] = args {
+            if is_cmd(args, b"select") {
+                if let Some(db) = std::str::from_utf8(db.as_ref())
+                    .ok()
+                    .and_then(|db| db.parse().ok())
+                {
+                    self.db = db;
+                }
+            }
+        }
+    }
+
+    // the next reply, setting aside any push frames that come first
+    fn read_reply(&mut self) -> RedisResult<WireType> {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => return Err(RedisError::NotConnected),
+        };
+        let mut buf = [0u8; 16 * 1024];
+        loop {
+            match self.decoder.decode()? {
+                Some(WireType::Push(push)) => {
+                    self.pushes.push_back(push);
+                    continue;
+                }
+                Some(reply) => return Ok(reply),
+                None => {}
+            }
+            let n = stream.read(&mut buf)?;
+            if n == 0 {
+                return Err(RedisError::closed());
+            }
+            self.decoder.feed(&buf[..n]);
+        }
+    }
+
+    fn update_prompt(&mut self) {
+        self.prompt = match (&self.stream, self.db) {
+            (None, _) => String::from("not connected> "),
+            (Some(_), 0) => format!("{}> ", self.endpoint),
+            (Some(_), db) => format!("{}[{}]> ", self.endpoint, db),
+        };
+    }
+}
+
+fn is_cmd<A: AsRef<[u8]>>(args: &[A], name: &[u8]) -> bool {
+    args.first()
+        .is_some_and(|cmd| cmd.as_ref().eq_ignore_ascii_case(name))
+}
+
+// whether the server has closed a connection that's sitting idle.  nothing's owed on one, so
+// the end of the stream or an error says it's gone.  a push frame waiting there doesn't
+fn hung_up(stream: &TcpStream) -> bool {
+    if stream.set_nonblocking(true).is_err() {
+        return true;
+    }
+    let gone = match stream.peek(&mut [0u8; 1]) {
+        Ok(n) => n == 0,
+        Err(e) => e.kind() != ErrorKind::WouldBlock,
+    };
+    stream.set_nonblocking(false).is_err() || gone
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn bulk(s: &[u8]) -> WireType {
+        WireType::BulkString(s.to_vec())
+    }
+
+    #[test]
+    fn negotiates_resp3() {
+        let mut cli = RedisClient::new(&test_server::spawn(Protocol::Resp3));
+        assert_eq!(cli.protocol(), Protocol::Resp3);
+        assert_eq!(cli.send_command("GET nope").unwrap(), WireType::Null);
+        assert_eq!(
+            cli.send_command("CONFIG GET maxmemory").unwrap(),
+            WireType::Map(vec![(bulk(b"maxmemory"), bulk(b"0"))])
+        );
+    }
+
+    #[test]
+    fn falls_back_to_resp2() {
+        let mut cli = RedisClient::new(&test_server::spawn(Protocol::Resp2));
+        assert_eq!(cli.protocol(), Protocol::Resp2);
+        assert_eq!(
+            cli.send_command("GET nope").unwrap(),
+            WireType::NullBulkString
+        );
+        assert_eq!(
+            cli.send_command("CONFIG GET maxmemory").unwrap(),
+            WireType::Array(vec![bulk(b"maxmemory"), bulk(b"0")])
+        );
+    }
+
+    #[test]
+    fn quoted_arguments_and_select() {
+        let mut cli = RedisClient::new(&test_server::spawn(Protocol::Resp3));
+        let addr = cli.endpoint.clone();
+        assert_eq!(cli.prompt(), format!("{}> ", addr));
+
+        cli.send_command(r#"SET "a key" 'a "value"'"#).unwrap();
+        assert_eq!(cli.call(&["GET", "a key"]).unwrap(), bulk(b"a \"value\""));
+        assert!(cli.send_command(r#"GET "a key"#).is_err());
+
+        cli.send_command("SELECT 2").unwrap();
+        assert_eq!(cli.prompt(), format!("{}[2]> ", addr));
+        // a failed SELECT leaves things as they were
+        cli.send_command("SELECT 99").unwrap();
+        assert_eq!(cli.prompt(), format!("{}[2]> ", addr));
+    }
+
+    #[test]
+    fn sets_push_frames_aside() {
+        let mut cli = RedisClient::new(&test_server::spawn(Protocol::Resp3));
+        cli.send_command("CLIENT TRACKING ON").unwrap();
+        cli.send_command("GET k").unwrap();
+        assert_eq!(cli.next_push(), None);
+
+        // the invalidation comes in ahead of SET's reply, which still gets back to the caller
+        assert_eq!(
+            cli.send_command("SET k v").unwrap(),
+            WireType::SimpleString("OK".to_string())
+        );
+        assert_eq!(
+            cli.next_push(),
+            Some(vec![bulk(b"invalidate"), WireType::Array(vec![bulk(b"k")])])
+        );
+        assert_eq!(cli.send_command("GET k").unwrap(), bulk(b"v"));
+    }
+
+    fn quick() -> ConnectOptions {
+        ConnectOptions {
+            min_backoff: Duration::from_millis(20),
+            max_backoff: Duration::from_millis(40),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reconnects_after_restart() {
+        let server = test_server::Server::start(Protocol::Resp3, "127.0.0.1:0", None);
+        let addr = server.addr().to_string();
+        let mut cli = RedisClient::with_options(&addr, quick());
+        cli.send_command("SELECT 2").unwrap();
+        cli.send_command("SET k before").unwrap();
+
+        server.stop();
+        let server = test_server::Server::start(Protocol::Resp3, &addr, None);
+        // the write goes to a dead connection, then again to a new one that's back on db 2
+        assert_eq!(
+            cli.send_command("SET k after").unwrap(),
+            WireType::SimpleString("OK".to_string())
+        );
+        assert_eq!(cli.prompt(), format!("{}[2]> ", addr));
+        assert_eq!(cli.protocol(), Protocol::Resp3);
+
+        let mut other = RedisClient::new(&addr);
+        assert_eq!(other.send_command("GET k").unwrap(), WireType::Null);
+        other.send_command("SELECT 2").unwrap();
+        assert_eq!(other.send_command("GET k").unwrap(), bulk(b"after"));
+
+        // same for a database picked up front
+        let mut picked = RedisClient::with_options(&addr, ConnectOptions { db: 2, ..quick() });
+        assert_eq!(picked.prompt(), format!("{}[2]> ", addr));
+        server.stop();
+        let server = test_server::Server::start(Protocol::Resp3, &addr, None);
+        picked.send_command("SET k again").unwrap();
+        other.send_command("SELECT 2").unwrap();
+        assert_eq!(other.send_command("GET k").unwrap(), bulk(b"again"));
+        assert!(
+            !RedisClient::with_options(&addr, ConnectOptions { db: 99, ..quick() }).connected()
+        );
+        server.stop();
+    }
+
+    #[test]
+    fn backs_off_while_down() {
+        let server = test_server::Server::start(Protocol::Resp3, "127.0.0.1:0", None);
+        let addr = server.addr().to_string();
+        let mut cli = RedisClient::with_options(&addr, quick());
+        server.stop();
+
+        // three tries, with two waits of at least half of 20ms and 40ms
+        let start = Instant::now();
+        assert!(cli.send_command("PING").is_err());
+        assert!(start.elapsed() >= Duration::from_millis(30));
+        assert!(!cli.connected());
+        assert_eq!(cli.prompt(), "not connected> ");
+
+        for _ in 0..20 {
+            let wait = cli.backoff(0);
+            assert!(wait >= Duration::from_millis(10) && wait <= Duration::from_millis(20));
+            // doubled past max_backoff, so capped at it
+            let wait = cli.backoff(35);
+            assert!(wait >= Duration::from_millis(20) && wait <= Duration::from_millis(40));
+        }
+
+        let server = test_server::Server::start(Protocol::Resp3, &addr, None);
+        assert!(cli.health_check().is_ok());
+        assert!(cli.connected());
+        assert_eq!(cli.prompt(), format!("{}> ", addr));
+        server.stop();
+
+        // no reconnecting means no coming back
+        let server = test_server::Server::start(Protocol::Resp3, &addr, None);
+        let mut cli = RedisClient::with_options(
+            &addr,
+            ConnectOptions {
+                reconnect_attempts: 0,
+                ..quick()
+            },
+        );
+        server.stop();
+        let server = test_server::Server::start(Protocol::Resp3, &addr, None);
+        assert!(cli.send_command("PING").is_err());
+        assert!(cli.send_command("PING").is_err());
+        server.stop();
+    }
+
+    #[test]
+    fn auth_on_reconnect() {
+        let server = test_server::Server::start(Protocol::Resp3, "127.0.0.1:0", Some("sekrit"));
+        let addr = server.addr().to_string();
+
+        let mut cli = RedisClient::with_options(
+            &addr,
+            ConnectOptions {
+                password: Some("sekrit".to_string()),
+                ..quick()
+            },
+        );
+        assert_eq!(cli.protocol(), Protocol::Resp3);
+        cli.send_command("SET k v").unwrap();
+
+        // an AUTH typed in is remembered the same way
+        let mut typed = RedisClient::with_options(&addr, quick());
+        assert_eq!(typed.protocol(), Protocol::Resp2);
+        match typed.send_command("GET k").unwrap() {
+            WireType::Error(e) => assert!(e.starts_with("NOAUTH")),
+            reply => panic!("{:?}", reply),
+        }
+        typed.send_command("AUTH default sekrit").unwrap();
+
+        server.stop();
+        let server = test_server::Server::start(Protocol::Resp3, &addr, Some("sekrit"));
+        assert_eq!(cli.send_command("GET k").unwrap(), WireType::Null);
+        assert_eq!(typed.send_command("GET k").unwrap(), WireType::Null);
+        assert_eq!(typed.protocol(), Protocol::Resp3);
+
+        let wrong = RedisClient::with_options(
+            &addr,
+            ConnectOptions {
+                password: Some("nope".to_string()),
+                ..quick()
+            },
+        );
+        assert!(!wrong.connected());
+        server.stop();
+    }
+
+    #[test]
+    fn read_timeout() {
+        // a server that takes connections and never says anything
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let start = Instant::now();
+        let cli = RedisClient::with_options(
+            &addr,
+            ConnectOptions {
+                read_timeout: Some(Duration::from_millis(50)),
+                connect_timeout: Some(Duration::from_millis(50)),
+                ..quick()
+            },
+        );
+        assert!(!cli.connected());
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    // a server that hangs up part way through the first batch on the first connection, after
+    // answering `answered` of its commands.  later connections get everything answered.  the
+    // count is of every command it's been sent, across all of them
+    fn flaky(batch: usize, answered: usize) -> (String, Arc<AtomicUsize>) {
+        fn read_cmds(conn: &mut TcpStream, n: usize) {
+            let mut data = vec![];
+            let mut buf = [0u8; 1024];
+            while data
+                .split(|&b| b == b'\n')
+                .filter(|l| l.starts_with(b"*"))
+                .count()
+                < n
+            {
+                match conn.read(&mut buf).unwrap() {
+                    0 => return,
+                    got => data.extend_from_slice(&buf[..got]),
+                }
+            }
+        }
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let sent = Arc::new(AtomicUsize::new(0));
+        let count = sent.clone();
+        thread::spawn(move || {
+            for (n, conn) in listener.incoming().enumerate() {
+                let mut conn = conn.unwrap();
+                // refuse HELLO, resp2 is all this one speaks
+                read_cmds(&mut conn, 1);
+                conn.write_all(b"-ERR unknown command 'HELLO'\r\n").unwrap();
+                read_cmds(&mut conn, batch);
+                count.fetch_add(batch, Ordering::SeqCst);
+                let replies = if n == 0 { answered } else { batch };
+                for i in 0..replies {
+                    write!(conn, ":{}\r\n", i + 1).unwrap();
+                }
+            }
+        });
+        (addr, sent)
+    }
+
+    #[test]
+    fn no_resend_once_sent() {
+        let incrs = [["INCR", "n"], ["INCR", "n"], ["INCR", "n"]];
+
+        // the first INCR has run, sending them all again would run it twice
+        let (addr, sent) = flaky(3, 1);
+        let mut cli = RedisClient::with_options(&addr, quick());
+        assert!(matches!(cli.call_many(&incrs), Err(RedisError::Io(_))));
+        assert_eq!(sent.load(Ordering::SeqCst), 3);
+        assert!(!cli.connected());
+
+        // no replies at all doesn't mean they didn't run
+        let (addr, sent) = flaky(3, 0);
+        let mut cli = RedisClient::with_options(&addr, quick());
+        assert!(matches!(cli.call_many(&incrs), Err(RedisError::Io(_))));
+        assert_eq!(sent.load(Ordering::SeqCst), 3);
+        assert!(!cli.connected());
+
+        // the next call gets a new connection
+        assert_eq!(
+            cli.call_many(&incrs).unwrap(),
+            vec![
+                WireType::Integer(1),
+                WireType::Integer(2),
+                WireType::Integer(3)
+            ]
+        );
+        assert_eq!(sent.load(Ordering::SeqCst), 6);
+    }
+}
@@ -0,0 +1,629 @@
+// typed wrappers around the commands people use most.  each one builds its command, sends it
+// and turns the reply into a plain rust value.  error replies come back as RedisError::Server,
+// and a reply of the wrong shape as RedisError::UnexpectedReply.
+//
+// they work the same over resp2 and resp3: a resp3 map or a resp2 flat array come back as the
+// same pairs, a double or the string a resp2 server sends come back as the same f64
+use crate::client::RedisClient;
+use crate::protocol::*;
+use crate::types::*;
+
+// anything that can go in a command as an argument
+pub trait ToArg {
+    fn to_arg(&self) -> Vec<u8>;
+}
+
+impl ToArg for [u8] {
+    fn to_arg(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+impl<const N: usize> ToArg for [u8; N] {
+    fn to_arg(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+impl ToArg for Vec<u8> {
+    fn to_arg(&self) -> Vec<u8> {
+        self.clone()
+    }
+}
+
+impl ToArg for str {
+    fn to_arg(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl ToArg for String {
+    fn to_arg(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl ToArg for i64 {
+    fn to_arg(&self) -> Vec<u8> {
+        self.to_string().into_bytes()
+    }
+}
+
+impl ToArg for u64 {
+    fn to_arg(&self) -> Vec<u8> {
+        self.to_string().into_bytes()
+    }
+}
+
+impl ToArg for f64 {
+    fn to_arg(&self) -> Vec<u8> {
+        double_text(*self).into_bytes()
+    }
+}
+
+impl<T: ToArg + ?Sized> ToArg for &T {
+    fn to_arg(&self) -> Vec<u8> {
+        (**self).to_arg()
+    }
+}
+
+// a command and its arguments, put together a piece at a time
+//
+//   Cmd::new("SET").arg("counter").arg(10).arg("NX")
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cmd {
+    args: Vec<Vec<u8>>,
+}
+
+impl Cmd {
+    pub fn new(name: &str) -> Self {
+        Cmd {
+            args: vec![name.as_bytes().to_vec()],
+        }
+    }
+
+    pub fn arg(mut self, arg: impl ToArg) -> Self {
+        self.args.push(arg.to_arg());
+        self
+    }
+
+    pub fn args<A: ToArg>(mut self, args: &[A]) -> Self {
+        self.args.extend(args.iter().map(ToArg::to_arg));
+        self
+    }
+
+    pub fn as_args(&self) -> &[Vec<u8>] {
+        &self.args
+    }
+}
+
+impl AsRef<[Vec<u8>]> for Cmd {
+    fn as_ref(&self) -> &[Vec<u8>] {
+        &self.args
+    }
+}
+
+// the options SET takes past its key and value
+#[derive(Debug, Clone, Default)]
+pub struct SetOptions {
+    // EX, expire after this many seconds
+    pub ex: Option<u64>,
+    // PX, the same in milliseconds
+    pub px: Option<u64>,
+    // NX, only set it if the key isn't there yet
+    pub nx: bool,
+    // XX, only set it if it is
+    pub xx: bool,
+}
+
+// an error reply as an error, anything else as it came.  RESP3 attributes are dropped, nothing
+// here has a use for them
+pub(crate) fn checked(reply: WireType) -> RedisResult<WireType> {
+    match reply {
+        WireType::Attribute { reply, .. } => checked(*reply),
+        WireType::Error(e) | WireType::BulkError(e) => Err(RedisError::server(&e)),
+        reply => Ok(reply),
+    }
+}
+
+fn unexpected<T>(reply: WireType) -> RedisResult<T> {
+    Err(RedisError::UnexpectedReply(reply))
+}
+
+fn done(reply: WireType) -> RedisResult<()> {
+    match reply {
+        WireType::SimpleString(_) => Ok(()),
+        reply => unexpected(reply),
+    }
+}
+
+fn int(reply: WireType) -> RedisResult<i64> {
+    match reply {
+        WireType::Integer(n) => Ok(n),
+        reply => unexpected(reply),
+    }
+}
+
+fn boolean(reply: WireType) -> RedisResult<bool> {
+    match reply {
+        WireType::Integer(n) => Ok(n != 0),
+        WireType::Boolean(b) => Ok(b),
+        reply => unexpected(reply),
+    }
+}
+
+fn bytes(reply: WireType) -> RedisResult<Option<Vec<u8>>> {
+    match reply {
+        WireType::BulkString(s) | WireType::Verbatim { text: s, .. } => Ok(Some(s)),
+        WireType::SimpleString(s) => Ok(Some(s.into_bytes())),
+        WireType::Null | WireType::NullBulkString => Ok(None),
+        reply => unexpected(reply),
+    }
+}
+
+fn some_bytes(reply: WireType) -> RedisResult<Vec<u8>> {
+    match bytes(reply)? {
+        Some(s) => Ok(s),
+        None => unexpected(WireType::Null),
+    }
+}
+
+fn float(reply: WireType) -> RedisResult<Option<f64>> {
+    match reply {
+        WireType::Double(d) => Ok(Some(d)),
+        // resp2 sends scores as strings
+        WireType::BulkString(s) => {
+            match std::str::from_utf8(&s).ok().and_then(|s| s.parse().ok()) {
+                Some(d) => Ok(Some(d)),
+                None => unexpected(WireType::BulkString(s)),
+            }
+        }
+        WireType::Null | WireType::NullBulkString => Ok(None),
+        reply => unexpected(reply),
+    }
+}
+
+fn some_float(reply: WireType) -> RedisResult<f64> {
+    match float(reply)? {
+        Some(d) => Ok(d),
+        None => unexpected(WireType::Null),
+    }
+}
+
+fn elems(reply: WireType) -> RedisResult<WireVec> {
+    match reply {
+        WireType::Array(v) | WireType::Set(v) => Ok(v),
+        WireType::NullArray | WireType::Null => Ok(vec![]),
+        reply => unexpected(reply),
+    }
+}
+
+fn list(reply: WireType) -> RedisResult<Vec<Vec<u8>>> {
+    elems(reply)?.into_iter().map(some_bytes).collect()
+}
+
+fn pairs(reply: WireType) -> RedisResult<Vec<(Vec<u8>, Vec<u8>)>> {
+    match reply {
+        WireType::Map(m) => m
+            .into_iter()
+            .map(|(k, v)| Ok((some_bytes(k)?, some_bytes(v)?)))
+            .collect(),
+        WireType::Array(v) if v.len() % 2 == 0 => {
+            let mut v = v.into_iter();
+            let mut out = vec![];
+            while let (Some(k), Some(val)) = (v.next(), v.next()) {
+                out.push((some_bytes(k)?, some_bytes(val)?));
+            }
+            Ok(out)
+        }
+        reply => unexpected(reply),
+    }
+}
+
+// members with their scores.  resp3 sends [member, score] pairs, resp2 one flat array
+fn scored(reply: WireType) -> RedisResult<Vec<(Vec<u8>, f64)>> {
+    let v = elems(reply)?;
+    let mut out = vec![];
+    let mut v = v.into_iter();
+    while let Some(item) = v.next() {
+        let (member, score) = match item {
+            WireType::Array(pair) if pair.len() == 2 => {
+                let mut pair = pair.into_iter();
+                (pair.next().unwrap(), pair.next().unwrap())
+            }
+            member => match v.next() {
+                Some(score) => (member, score),
+                None => return unexpected(member),
+            },
+        };
+        out.push((some_bytes(member)?, some_float(score)?));
+    }
+    Ok(out)
+}
+
+impl RedisClient {
+    // send a command, turning an error reply into an error
+    pub fn query(&mut self, cmd: &Cmd) -> RedisResult<WireType> {
+        checked(self.call(cmd.as_args())?)
+    }
+
+    // strings
+
+    pub fn get(&mut self, key: impl ToArg) -> RedisResult<Option<Vec<u8>>> {
+        bytes(self.query(&Cmd::new("GET").arg(key))?)
+    }
+
+    pub fn set(&mut self, key: impl ToArg, val: impl ToArg) -> RedisResult<()> {
+        done(self.query(&Cmd::new("SET").arg(key).arg(val))?)
+    }
+
+    // false if NX or XX kept it from being set
+    pub fn set_with(
+        &mut self,
+        key: impl ToArg,
+        val: impl ToArg,
+        opts: &SetOptions,
+    ) -> RedisResult<bool> {
+        let mut cmd = Cmd::new("SET").arg(key).arg(val);
+        if let Some(secs) = opts.ex {
+            cmd = cmd.arg("EX").arg(secs);
+        }
+        if let Some(ms) = opts.px {
+            cmd = cmd.arg("PX").arg(ms);
+        }
+        if opts.nx {
+            cmd = cmd.arg("NX");
+        }
+        if opts.xx {
+            cmd = cmd.arg("XX");
+        }
+        match self.query(&cmd)? {
+            WireType::Null | WireType::NullBulkString => Ok(false),
+            reply => done(reply).map(|_| true),
+        }
+    }
+
+    pub fn incr(&mut self, key: impl ToArg) -> RedisResult<i64> {
+        int(self.query(&Cmd::new("INCR").arg(key))?)
+    }
+
+    pub fn incr_by(&mut self, key: impl ToArg, by: i64) -> RedisResult<i64> {
+        int(self.query(&Cmd::new("INCRBY").arg(key).arg(by))?)
+    }
+
+    pub fn decr(&mut self, key: impl ToArg) -> RedisResult<i64> {
+        int(self.query(&Cmd::new("DECR").arg(key))?)
+    }
+
+    // keys
+
+    // how many of them were there to delete
+    pub fn del<K: ToArg>(&mut self, keys: &[K]) -> RedisResult<i64> {
+        int(self.query(&Cmd::new("DEL").args(keys))?)
+    }
+
+    pub fn exists(&mut self, key: impl ToArg) -> RedisResult<bool> {
+        boolean(self.query(&Cmd::new("EXISTS").arg(key))?)
+    }
+
+    // false if there's no such key
+    pub fn expire(&mut self, key: impl ToArg, secs: u64) -> RedisResult<bool> {
+        boolean(self.query(&Cmd::new("EXPIRE").arg(key).arg(secs))?)
+    }
+
+    // seconds left before the key expires.  None if it never will, or isn't there at all
+    pub fn ttl(&mut self, key: impl ToArg) -> RedisResult<Option<i64>> {
+        match int(self.query(&Cmd::new("TTL").arg(key))?)? {
+            n if n < 0 => Ok(None),
+            n => Ok(Some(n)),
+        }
+    }
+
+    // lists
+
+    // the list's length after the push
+    pub fn lpush<V: ToArg>(&mut self, key: impl ToArg, vals: &[V]) -> RedisResult<i64> {
+        int(self.query(&Cmd::new("LPUSH").arg(key).args(vals))?)
+    }
+
+    pub fn rpush<V: ToArg>(&mut self, key: impl ToArg, vals: &[V]) -> RedisResult<i64> {
+        int(self.query(&Cmd::new("RPUSH").arg(key).args(vals))?)
+    }
+
+    pub fn lpop(&mut self, key: impl ToArg) -> RedisResult<Option<Vec<u8>>> {
+        bytes(self.query(&Cmd::new("LPOP").arg(key))?)
+    }
+
+    pub fn rpop(&mut self, key: impl ToArg) -> RedisResult<Option<Vec<u8>>> {
+        bytes(self.query(&Cmd::new("RPOP").arg(key))?)
+    }
+
+    // start and stop are inclusive, negative ones count back from the end
+    pub fn lrange(&mut self, key: impl ToArg, start: i64, stop: i64) -> RedisResult<Vec<Vec<u8>>> {
+        list(self.query(&Cmd::new("LRANGE").arg(key).arg(start).arg(stop))?)
+    }
+
+    pub fn llen(&mut self, key: impl ToArg) -> RedisResult<i64> {
+        int(self.query(&Cmd::new("LLEN").arg(key))?)
+    }
+
+    // hashes
+
+    // true if the field is new
+    pub fn hset(
+        &mut self,
+        key: impl ToArg,
+        field: impl ToArg,
+        val: impl ToArg,
+    ) -> RedisResult<bool> {
+        boolean(self.query(&Cmd::new("HSET").arg(key).arg(field).arg(val))?)
+    }
+
+    pub fn hget(&mut self, key: impl ToArg, field: impl ToArg) -> RedisResult<Option<Vec<u8>>> {
+        bytes(self.query(&Cmd::new("HGET").arg(key).arg(field))?)
+    }
+
+    pub fn hdel<F: ToArg>(&mut self, key: impl ToArg, fields: &[F]) -> RedisResult<i64> {
+        int(self.query(&Cmd::new("HDEL").arg(key).args(fields))?)
+    }
+
+    pub fn hgetall(&mut self, key: impl ToArg) -> RedisResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        pairs(self.query(&Cmd::new("HGETALL").arg(key))?)
+    }
+
+    pub fn hlen(&mut self, key: impl ToArg) -> RedisResult<i64> {
+        int(self.query(&Cmd::new("HLEN").arg(key))?)
+    }
+
+    // sets
+
+    // how many of them weren't in the set already
+    pub fn sadd<M: ToArg>(&mut self, key: impl ToArg, members: &[M]) -> RedisResult<i64> {
+        int(self.query(&Cmd::new("SADD").arg(key).args(members))?)
+    }
+
+    pub fn srem<M: ToArg>(&mut self, key: impl ToArg, members: &[M]) -> RedisResult<i64> {
+        int(self.query(&Cmd::new("SREM").arg(key).args(members))?)
+    }
+
+    pub fn smembers(&mut self, key: impl ToArg) -> RedisResult<Vec<Vec<u8>>> {
+        list(self.query(&Cmd::new("SMEMBERS").arg(key))?)
+    }
+
+    pub fn sismember(&mut self, key: impl ToArg, member: impl ToArg) -> RedisResult<bool> {
+        boolean(self.query(&Cmd::new("SISMEMBER").arg(key).arg(member))?)
+    }
+
+    pub fn scard(&mut self, key: impl ToArg) -> RedisResult<i64> {
+        int(self.query(&Cmd::new("SCARD").arg(key))?)
+    }
+
+    // sorted sets
+
+    // true if the member is new, false if it was there and only its score changed
+    pub fn zadd(&mut self, key: impl ToArg, score: f64, member: impl ToArg) -> RedisResult<bool> {
+        boolean(self.query(&Cmd::new("ZADD").arg(key).arg(score).arg(member))?)
+    }
+
+    // the member's new score
+    pub fn zincr_by(&mut self, key: impl ToArg, by: f64, member: impl ToArg) -> RedisResult<f64> {
+        some_float(self.query(&Cmd::new("ZINCRBY").arg(key).arg(by).arg(member))?)
+    }
+
+    pub fn zrem<M: ToArg>(&mut self, key: impl ToArg, members: &[M]) -> RedisResult<i64> {
+        int(self.query(&Cmd::new("ZREM").arg(key).args(members))?)
+    }
+
+    pub fn zscore(&mut self, key: impl ToArg, member: impl ToArg) -> RedisResult<Option<f64>> {
+        float(self.query(&Cmd::new("ZSCORE").arg(key).arg(member))?)
+    }
+
+    // members by rank, lowest score first
+    pub fn zrange(&mut self, key: impl ToArg, start: i64, stop: i64) -> RedisResult<Vec<Vec<u8>>> {
+        list(self.query(&Cmd::new("ZRANGE").arg(key).arg(start).arg(stop))?)
+    }
+
+    pub fn zrange_with_scores(
+        &mut self,
+        key: impl ToArg,
+        start: i64,
+        stop: i64,
+    ) -> RedisResult<Vec<(Vec<u8>, f64)>> {
+        let cmd = Cmd::new("ZRANGE")
+            .arg(key)
+            .arg(start)
+            .arg(stop)
+            .arg("WITHSCORES");
+        scored(self.query(&cmd)?)
+    }
+
+    pub fn zcard(&mut self, key: impl ToArg) -> RedisResult<i64> {
+        int(self.query(&Cmd::new("ZCARD").arg(key))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::Pipeline;
+    use crate::test_server;
+
+    fn clients() -> Vec<RedisClient> {
+        vec![
+            RedisClient::new(&test_server::spawn(Protocol::Resp3)),
+            RedisClient::new(&test_server::spawn(Protocol::Resp2)),
+        ]
+    }
+
+    fn b(s: &str) -> Vec<u8> {
+        s.as_bytes().to_vec()
+    }
+
+    #[test]
+    fn strings_and_keys() {
+        for mut cli in clients() {
+            assert_eq!(cli.get("k").unwrap(), None);
+            cli.set("k", "v").unwrap();
+            assert_eq!(cli.get("k").unwrap(), Some(b("v")));
+
+            let nx = SetOptions {
+                nx: true,
+                ..Default::default()
+            };
+            assert!(!cli.set_with("k", "w", &nx).unwrap());
+            assert!(cli.set_with("k2", "w", &nx).unwrap());
+            let ex = SetOptions {
+                ex: Some(100),
+                xx: true,
+                ..Default::default()
+            };
+            assert!(cli.set_with("k2", "x", &ex).unwrap());
+            assert_eq!(cli.get("k2").unwrap(), Some(b("x")));
+            assert_eq!(cli.ttl("k2").unwrap(), Some(100));
+            assert_eq!(cli.ttl("k").unwrap(), None);
+            assert_eq!(cli.ttl("nope").unwrap(), None);
+
+            assert_eq!(cli.incr("n").unwrap(), 1);
+            assert_eq!(cli.incr_by("n", 41).unwrap(), 42);
+            assert_eq!(cli.decr("n").unwrap(), 41);
+
+            assert!(cli.exists("n").unwrap());
+            assert!(cli.expire("n", 10).unwrap());
+            assert!(!cli.expire("nope", 10).unwrap());
+            assert_eq!(cli.del(&["n", "k", "nope"]).unwrap(), 2);
+            assert!(!cli.exists("n").unwrap());
+
+            // binary keys and values go through untouched
+            cli.set(&[0u8, 255, 13, 10][..], &[1u8, 2, 3][..]).unwrap();
+            assert_eq!(cli.get([0u8, 255, 13, 10]).unwrap(), Some(vec![1, 2, 3]));
+        }
+    }
+
+    #[test]
+    fn collections() {
+        for mut cli in clients() {
+            assert_eq!(cli.rpush("l", &["b", "c"]).unwrap(), 2);
+            assert_eq!(cli.lpush("l", &["a"]).unwrap(), 3);
+            assert_eq!(
+                cli.lrange("l", 0, -1).unwrap(),
+                vec![b("a"), b("b"), b("c")]
+            );
+            assert_eq!(cli.lrange("l", -2, 10).unwrap(), vec![b("b"), b("c")]);
+            assert_eq!(cli.lpop("l").unwrap(), Some(b("a")));
+            assert_eq!(cli.rpop("l").unwrap(), Some(b("c")));
+            assert_eq!(cli.llen("l").unwrap(), 1);
+            assert_eq!(cli.rpop("l").unwrap(), Some(b("b")));
+            assert_eq!(cli.rpop("l").unwrap(), None);
+
+            assert!(cli.hset("h", "f", "1").unwrap());
+            assert!(!cli.hset("h", "f", "2").unwrap());
+            cli.hset("h", "g", "3").unwrap();
+            assert_eq!(cli.hget("h", "f").unwrap(), Some(b("2")));
+            assert_eq!(cli.hget("h", "x").unwrap(), None);
+            assert_eq!(
+                cli.hgetall("h").unwrap(),
+                vec![(b("f"), b("2")), (b("g"), b("3"))]
+            );
+            assert_eq!(cli.hdel("h", &["f", "x"]).unwrap(), 1);
+            assert_eq!(cli.hlen("h").unwrap(), 1);
+            assert_eq!(cli.hgetall("nope").unwrap(), vec![]);
+
+            assert_eq!(cli.sadd("s", &["x", "y", "x"]).unwrap(), 2);
+            assert!(cli.sismember("s", "x").unwrap());
+            assert!(!cli.sismember("s", "z").unwrap());
+            assert_eq!(cli.smembers("s").unwrap(), vec![b("x"), b("y")]);
+            assert_eq!(cli.srem("s", &["x", "z"]).unwrap(), 1);
+            assert_eq!(cli.scard("s").unwrap(), 1);
+
+            assert!(cli.zadd("z", 2.5, "b").unwrap());
+            assert!(cli.zadd("z", 1.0, "a").unwrap());
+            assert!(!cli.zadd("z", 3.0, "b").unwrap());
+            assert_eq!(cli.zincr_by("z", 0.5, "a").unwrap(), 1.5);
+            assert_eq!(cli.zscore("z", "b").unwrap(), Some(3.0));
+            assert_eq!(cli.zscore("z", "c").unwrap(), None);
+            assert_eq!(cli.zrange("z", 0, -1).unwrap(), vec![b("a"), b("b")]);
+            assert_eq!(
+                cli.zrange_with_scores("z", 0, -1).unwrap(),
+                vec![(b("a"), 1.5), (b("b"), 3.0)]
+            );
+            assert_eq!(cli.zrem("z", &["a"]).unwrap(), 1);
+            assert_eq!(cli.zcard("z").unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn error_replies() {
+        for mut cli in clients() {
+            cli.rpush("l", &["x"]).unwrap();
+            match cli.get("l") {
+                Err(RedisError::Server { code, message }) => {
+                    assert_eq!(code, "WRONGTYPE");
+                    assert!(message.starts_with("Operation against a key"));
+                }
+                other => panic!("{:?}", other),
+            }
+            cli.set("k", "abc").unwrap();
+            let err = cli.incr("k").unwrap_err();
+            assert_eq!(err.code(), Some("ERR"));
+            assert_eq!(
+                err.to_string(),
+                "ERR value is not an integer or out of range"
+            );
+
+            // the connection carries on fine after an error
+            assert_eq!(cli.llen("l").unwrap(), 1);
+
+            // a reply that doesn't fit what the method returns
+            let reply = cli.query(&Cmd::new("PING")).unwrap();
+            assert!(matches!(
+                int(reply),
+                Err(RedisError::UnexpectedReply(WireType::SimpleString(_)))
+            ));
+        }
+    }
+
+    #[test]
+    fn attributed_replies() {
+        let mut cli = RedisClient::new(&test_server::spawn(Protocol::Resp3));
+        cli.query(&Cmd::new("DEBUG").arg("ATTRIBUTES").arg("ON"))
+            .unwrap();
+        assert!(matches!(
+            cli.call(&["GET", "k"]).unwrap(),
+            WireType::Attribute { .. }
+        ));
+
+        cli.set("k", "1").unwrap();
+        assert_eq!(cli.incr("k").unwrap(), 2);
+        assert_eq!(cli.get("k").unwrap(), Some(b("2")));
+        cli.set("k", "abc").unwrap();
+        assert_eq!(cli.incr("k").unwrap_err().code(), Some("ERR"));
+
+        for mut pipe in [Pipeline::new(), Pipeline::atomic()] {
+            pipe.add(Cmd::new("SET").arg("k").arg(5i64))
+                .add(Cmd::new("INCR").arg("k"));
+            let replies = pipe.execute(&mut cli).unwrap();
+            assert_eq!(replies[1].as_ref().unwrap(), &WireType::Integer(6));
+
+            // and the errors, EXECABORT included
+            pipe.add(Cmd::new("NOSUCHCOMMAND"));
+            let replies = pipe.execute(&mut cli).unwrap();
+            assert_eq!(replies[2].as_ref().unwrap_err().code(), Some("ERR"));
+        }
+        let mut pipe = Pipeline::atomic();
+        pipe.add(Cmd::new("INCR").arg("k"))
+            .add(Cmd::new("NOSUCHCOMMAND"));
+        let replies = pipe.execute(&mut cli).unwrap();
+        assert_eq!(replies[0].as_ref().unwrap_err().code(), Some("EXECABORT"));
+    }
+
+    #[test]
+    fn cmd_builder() {
+        let cmd = Cmd::new("ZADD").arg("k").arg(1.5).arg(b"m").arg(-3i64);
+        assert_eq!(
+            cmd.as_args(),
+            &[b("ZADD"), b("k"), b("1.5"), b("m"), b("-3")]
+        );
+        let cmd = Cmd::new("DEL").args(&["a", "b"]).args(&[b("c")]);
+        assert_eq!(cmd.as_args(), &[b("DEL"), b("a"), b("b"), b("c")]);
+    }
+}
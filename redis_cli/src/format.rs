@@ -0,0 +1,201 @@
+use crate::protocol::*;
+use std::fmt::Write;
+
+// replies laid out the way redis-cli shows them at a terminal:
+//
+//   (integer) 1
+//   "a bulk string"
+//   (nil)
+//   1) 1) "nested"
+//      2) "array"
+//   2) "tail"
+pub fn tty(reply: &WireType) -> String {
+    let mut out = String::new();
+    tty_into(&mut out, reply, "");
+    out
+}
+
+// roughly what redis-cli --raw prints: strings as they are with nothing around them, one array
+// element per line
+pub fn raw(reply: &WireType) -> Vec<u8> {
+    let mut out = vec![];
+    raw_into(&mut out, reply);
+    out.push(b'\n');
+    out
+}
+
+// a bulk string in double quotes, with anything unprintable escaped
+pub fn repr(s: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for &c in s {
+        match c {
+            b'\\' => out.push_str("\\\\"),
+            b'"' => out.push_str("\\\""),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            7 => out.push_str("\\a"),
+            8 => out.push_str("\\b"),
+            b' '..=b'~' => out.push(c as char),
+            _ => write!(out, "\\x{:02x}", c).unwrap(),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn tty_into(out: &mut String, reply: &WireType, prefix: &str) {
+    match reply {
+        WireType::SimpleString(s) => out.push_str(s),
+        WireType::BulkString(s) => out.push_str(&repr(s)),
+        WireType::Verbatim { text, .. } => out.push_str(&String::from_utf8_lossy(text)),
+        WireType::Error(e) | WireType::BulkError(e) => write!(out, "(error) {}", e).unwrap(),
+        WireType::Integer(i) => write!(out, "(integer) {}", i).unwrap(),
+        WireType::Double(d) => write!(out, "(double) {}", double_text(*d)).unwrap(),
+        WireType::BigNumber(n) => write!(out, "(big number) {}", n).unwrap(),
+        WireType::Boolean(b) => write!(out, "({})", b).unwrap(),
+        WireType::Null | WireType::NullBulkString | WireType::NullArray => out.push_str("(nil)"),
+        WireType::Attribute { reply, .. } => return tty_into(out, reply, prefix),
+        WireType::Array(v) => return entries(out, v, None, ')', "(empty array)", prefix),
+        WireType::Set(v) => return entries(out, v, None, '~', "(empty set)", prefix),
+        WireType::Push(v) => return entries(out, v, None, ')', "(empty push)", prefix),
+        WireType::Map(m) => {
+            let (keys, vals): (WireVec, WireVec) = m.iter().cloned().unzip();
+            return entries(out, &keys, Some(&vals), '#', "(empty hash)", prefix);
+        }
+    }
+    out.push('\n');
+}
+
+// numbered items, nested ones indented past their parent's number.  maps put the value after
+// the key with a =>
+fn entries(
+    out: &mut String,
+    items: &[WireType],
+    vals: Option<&[WireType]>,
+    sep: char,
+    empty: &str,
+    prefix: &str,
+) {
+    if items.is_empty() {
+        out.push_str(empty);
+        out.push('\n');
+        return;
+    }
+    let width = items.len().to_string().len();
+    let nested = format!("{}{}", prefix, " ".repeat(width + 2));
+    for (i, item) in items.iter().enumerate() {
+        // the first one goes on the line the parent already started
+        if i > 0 {
+            out.push_str(prefix);
+        }
+        write!(out, "{:>width$}{} ", i + 1, sep, width = width).unwrap();
+        tty_into(out, item, &nested);
+        if let Some(vals) = vals {
+            out.pop();
+            out.push_str(" => ");
+            tty_into(out, &vals[i], &nested);
+        }
+    }
+}
+
+fn raw_into(out: &mut Vec<u8>, reply: &WireType) {
+    match reply {
+        WireType::SimpleString(s) | WireType::Error(s) | WireType::BulkError(s) => {
+            out.extend_from_slice(s.as_bytes())
+        }
+        WireType::BulkString(s) | WireType::Verbatim { text: s, .. } => out.extend_from_slice(s),
+        WireType::Integer(i) => out.extend_from_slice(i.to_string().as_bytes()),
+        WireType::Double(d) => out.extend_from_slice(double_text(*d).as_bytes()),
+        WireType::BigNumber(n) => out.extend_from_slice(n.as_bytes()),
+        WireType::Boolean(b) => out.extend_from_slice(format!("({})", b).as_bytes()),
+        WireType::Null | WireType::NullBulkString | WireType::NullArray => {}
+        WireType::Attribute { reply, .. } => raw_into(out, reply),
+        WireType::Array(v) | WireType::Set(v) | WireType::Push(v) => {
+            for (i, item) in v.iter().enumerate() {
+                if i > 0 {
+                    out.push(b'\n');
+                }
+                raw_into(out, item);
+            }
+        }
+        WireType::Map(m) => {
+            for (i, (k, v)) in m.iter().enumerate() {
+                if i > 0 {
+                    out.push(b'\n');
+                }
+                raw_into(out, k);
+                out.push(b'\n');
+                raw_into(out, v);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bulk(s: &[u8]) -> WireType {
+        WireType::BulkString(s.to_vec())
+    }
+
+    #[test]
+    fn like_redis_cli() {
+        assert_eq!(tty(&WireType::Integer(1)), "(integer) 1\n");
+        assert_eq!(tty(&WireType::SimpleString("OK".into())), "OK\n");
+        assert_eq!(tty(&WireType::NullBulkString), "(nil)\n");
+        assert_eq!(
+            tty(&WireType::Error("ERR nope".into())),
+            "(error) ERR nope\n"
+        );
+        assert_eq!(
+            tty(&bulk(b"a \"b\"\n\x01\xff")),
+            "\"a \\\"b\\\"\\n\\x01\\xff\"\n"
+        );
+        assert_eq!(tty(&WireType::Array(vec![])), "(empty array)\n");
+        assert_eq!(tty(&WireType::Double(1.5)), "(double) 1.5\n");
+        assert_eq!(tty(&WireType::Boolean(false)), "(false)\n");
+
+        let nested = WireType::Array(vec![
+            WireType::Array(vec![bulk(b"a"), WireType::Integer(2)]),
+            WireType::Null,
+            WireType::Set(vec![bulk(b"s")]),
+        ]);
+        assert_eq!(
+            tty(&nested),
+            "1) 1) \"a\"\n   2) (integer) 2\n2) (nil)\n3) 1~ \"s\"\n"
+        );
+
+        // numbers line up once there are ten or more
+        let many = WireType::Array((0..10).map(WireType::Integer).collect());
+        let out = tty(&many);
+        assert!(out.starts_with(" 1) (integer) 0\n 2) (integer) 1\n"));
+        assert!(out.ends_with("10) (integer) 9\n"));
+
+        let map = WireType::Map(vec![
+            (bulk(b"k"), bulk(b"v")),
+            (bulk(b"l"), WireType::Array(vec![bulk(b"x"), bulk(b"y")])),
+        ]);
+        assert_eq!(
+            tty(&map),
+            // redis-cli indents a value by the key's number only, so do we
+            "1# \"k\" => \"v\"\n2# \"l\" => 1) \"x\"\n   2) \"y\"\n"
+        );
+    }
+
+    #[test]
+    fn raw_output() {
+        assert_eq!(raw(&bulk(b"a b\n")), b"a b\n\n".to_vec());
+        assert_eq!(raw(&WireType::NullBulkString), b"\n".to_vec());
+        assert_eq!(raw(&WireType::Integer(-3)), b"-3\n".to_vec());
+        assert_eq!(
+            raw(&WireType::Array(vec![bulk(b"x"), WireType::Integer(1)])),
+            b"x\n1\n".to_vec()
+        );
+        assert_eq!(
+            raw(&WireType::Map(vec![(bulk(b"k"), bulk(b"v"))])),
+            b"k\nv\n".to_vec()
+        );
+    }
+}
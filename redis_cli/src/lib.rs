@@ -0,0 +1,11 @@
+pub mod args;
+pub mod async_client;
+pub mod client;
+pub mod commands;
+pub mod format;
+pub mod pipeline;
+pub mod protocol;
+// a fake redis-server for tests and benches to point the client at
+#[cfg(any(test, feature = "test-server"))]
+pub mod test_server;
+pub mod types;
@@ -1,101 +1,181 @@
-use std::io::prelude::*;
-use std::net::TcpStream;
-use std::{thread, time};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::{env, process};
 
-mod protocol;
-mod types;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
 
-use protocol::*;
-use types::RedisError;
-use types::RedisResult;
+use redis_cli::args::split_args;
+use redis_cli::client::{ConnectOptions, RedisClient};
+use redis_cli::format;
+use redis_cli::protocol::*;
 
-#[derive(Debug)]
-pub struct RedisClient {
-    stream: Option<TcpStream>,
-    endpoint: String,
-    prompt: String,
+const USAGE: &str =
+    "usage: redis_cli [-h host] [-p port] [-n db] [--raw | --no-raw] [cmd [arg ...]]";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(1);
 }
 
-impl RedisClient {
-    pub fn new(addr: &str) -> Self {
-        // let timeout = time::Duration::from_millis(1000);
-        let result = TcpStream::connect(addr);
-        let cli_stream: Option<TcpStream>;
-        let prompt: String;
-        match result {
-            Ok(stream) => {
-                cli_stream = Some(stream);
-                prompt = format!("{}>", addr);
-            }
-            Err(_) => {
-                cli_stream = None;
-                prompt = String::from("not connected>");
-            }
+fn print_reply(out: &mut impl Write, reply: &WireType, raw: bool) {
+    let _ = if raw {
+        out.write_all(&format::raw(reply))
+    } else {
+        out.write_all(format::tty(reply).as_bytes())
+    };
+}
+
+// send one command and print whatever comes back, false if it failed
+fn execute(cli: &mut RedisClient, args: &[Vec<u8>], raw: bool, out: &mut impl Write) -> bool {
+    let reply = cli.call(args);
+    // anything pushed at us came in before the reply did
+    while let Some(push) = cli.next_push() {
+        print_reply(out, &WireType::Push(push), raw);
+    }
+    match reply {
+        Ok(reply) => {
+            print_reply(out, &reply, raw);
+            !matches!(reply, WireType::Error(_) | WireType::BulkError(_))
         }
-        RedisClient {
-            stream: cli_stream,
-            prompt: prompt,
-            endpoint: String::from(addr),
+        Err(e) => {
+            let _ = writeln!(out, "Error: {}", e);
+            false
         }
     }
+}
 
-    pub fn send_command(&mut self, cmd: &str) -> RedisResult<Vec<u8>> {
-        match self.send_command_impl(cmd) {
-            Ok(r) => {
-                self.on_connect();
-                return Ok(r);
-            }
-            Err(r) => {
-                self.on_disconnect();
-                return Err(r);
-            }
+// one line from the prompt, false once it's time to go
+fn command_line(cli: &mut RedisClient, line: &str, raw: bool, out: &mut impl Write) -> bool {
+    let args = match split_args(line) {
+        Ok(args) => args,
+        Err(e) => {
+            let _ = writeln!(out, "{}", e);
+            return true;
+        }
+    };
+    match args.first() {
+        None => {}
+        Some(cmd) if cmd.eq_ignore_ascii_case(b"quit") || cmd.eq_ignore_ascii_case(b"exit") => {
+            return false
+        }
+        Some(_) => {
+            execute(cli, &args, raw, out);
         }
     }
+    true
+}
 
-    fn send_command_impl(&mut self, cmd: &str) -> RedisResult<Vec<u8>> {
-        match &mut self.stream {
-            Some(stream) => {
-                let mut v: WireVec = vec![];
-                for tok in cmd.split_whitespace() {
-                    v.push(WireType::BulkString(tok.to_string()));
-                }
-                stream.write_all(v.serialize().as_slice())?;
+fn history_file() -> Option<PathBuf> {
+    match env::var_os("REDISCLI_HISTFILE") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => Some(PathBuf::from(env::var_os("HOME")?).join(".rediscli_history")),
+    }
+}
 
-                let bufsize = 1024;
-                let mut buf: Vec<u8> = vec![0; bufsize];
-                stream.read(buf.as_mut_slice())?;
-                return Ok(buf);
-            }
-            None => {
-                return Err(RedisError::new("not connected"));
+fn repl(cli: &mut RedisClient, raw: bool) {
+    // commands piped in get run one a line, without any prompt or editing
+    if !io::stdin().is_terminal() {
+        for line in io::stdin().lock().lines() {
+            match line {
+                Ok(line) if command_line(cli, &line, raw, &mut io::stdout().lock()) => {}
+                _ => break,
             }
         }
+        return;
     }
 
-    fn on_connect(&mut self) {
-        self.prompt = format!("{}>", self.endpoint);
+    let mut rl = match DefaultEditor::new() {
+        Ok(rl) => rl,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    let history = history_file();
+    if let Some(history) = &history {
+        let _ = rl.load_history(history);
     }
-
-    fn on_disconnect(&mut self) {
-        self.prompt = String::from("not connected>");
+    loop {
+        let prompt = cli.prompt().to_string();
+        match rl.readline(&prompt) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    let _ = rl.add_history_entry(line.as_str());
+                }
+                if !command_line(cli, &line, raw, &mut io::stdout().lock()) {
+                    break;
+                }
+            }
+            // ctrl-c and ctrl-d both leave, same as redis-cli
+            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("{}", e);
+                break;
+            }
+        }
+    }
+    if let Some(history) = &history {
+        let _ = rl.save_history(history);
     }
 }
 
 pub fn main() {
-    // ridiculous library driver code
-    let mut counter: u32 = 0;
-    let mut cli = RedisClient::new("127.0.0.1:6379");
-    loop {
-        let cmd = format!("set foo {}", counter);
-        match cli.send_command(&cmd) {
-            Ok(response) => {
-                println!("{}", String::from_utf8(response).unwrap());
+    let mut host = String::from("127.0.0.1");
+    let mut port: u16 = 6379;
+    let mut db: i64 = 0;
+    // like redis-cli, raw unless it's a person reading
+    let mut raw = !io::stdout().is_terminal();
+
+    let mut command = vec![];
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" => host = args.next().unwrap_or_else(|| usage()),
+            "-p" => {
+                port = args
+                    .next()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(|| usage())
             }
-            Err(err) => {
-                println!("{:?}", err);
+            "-n" => {
+                db = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or_else(|| usage())
             }
+            "--raw" => raw = true,
+            "--no-raw" => raw = false,
+            "--help" => usage(),
+            _ if arg.starts_with('-') => usage(),
+            // everything from the first word on is the command to run
+            _ => {
+                command.push(arg.into_bytes());
+                command.extend(args.by_ref().map(String::into_bytes));
+            }
+        }
+    }
+
+    let addr = format!("{}:{}", host, port);
+    let mut cli = RedisClient::with_options(
+        &addr,
+        ConnectOptions {
+            db,
+            ..Default::default()
+        },
+    );
+    if !cli.connected() {
+        eprintln!("Could not connect to Redis at {}", addr);
+        if !command.is_empty() {
+            process::exit(1);
         }
-        counter += 1;
-        thread::sleep(time::Duration::from_millis(500));
     }
+
+    if command.is_empty() {
+        repl(&mut cli, raw);
+        return;
+    }
+    let ok = execute(&mut cli, &command, raw, &mut io::stdout().lock());
+    let _ = io::stdout().flush();
+    process::exit(if ok { 0 } else { 1 });
 }
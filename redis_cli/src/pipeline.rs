@@ -0,0 +1,198 @@
+// commands queued up and sent together, so a batch costs one round trip instead of one per
+// command.  each command gets its own result back, an error reply from one doesn't stop the rest
+//
+//   let mut pipe = Pipeline::new();
+//   pipe.add(Cmd::new("INCR").arg("hits")).add(Cmd::new("GET").arg("name"));
+//   let results = pipe.execute(&mut cli)?;
+use crate::client::RedisClient;
+use crate::commands::{checked, Cmd};
+use crate::protocol::*;
+use crate::types::*;
+
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    cmds: Vec<Cmd>,
+    // wrap the batch in MULTI/EXEC
+    atomic: bool,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // a pipeline that runs as a transaction.  its commands run back to back with nothing from
+    // other clients in between, and none of them run if one can't be queued
+    pub fn atomic() -> Self {
+        Pipeline {
+            cmds: vec![],
+            atomic: true,
+        }
+    }
+
+    pub fn add(&mut self, cmd: Cmd) -> &mut Self {
+        self.cmds.push(cmd);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.cmds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cmds.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.cmds.clear();
+    }
+
+    // send everything and collect the replies, one per command in the order they were added.
+    // the outer error is for the batch as a whole: the connection failing, or an atomic
+    // pipeline being refused or aborted
+    pub fn execute(&self, cli: &mut RedisClient) -> RedisResult<Vec<RedisResult<WireType>>> {
+        if self.cmds.is_empty() {
+            return Ok(vec![]);
+        }
+        if !self.atomic {
+            let replies = cli.call_many(&self.cmds)?;
+            return Ok(replies.into_iter().map(checked).collect());
+        }
+
+        // MULTI goes first on its own.  if the server refuses it, say because the client is
+        // already inside a MULTI of its own, the commands would be queued into that one or run
+        // outside any, so nothing else goes out
+        cli.query(&Cmd::new("MULTI"))?;
+        let mut cmds = Vec::with_capacity(self.cmds.len() + 1);
+        cmds.extend(self.cmds.iter().cloned());
+        cmds.push(Cmd::new("EXEC"));
+        // call_many reads back exactly one reply per command
+        let mut replies = cli.call_many(&cmds)?.into_iter();
+        let exec = match replies.next_back().unwrap() {
+            WireType::Attribute { reply, .. } => *reply,
+            exec => exec,
+        };
+        // QUEUED, or why the command couldn't be
+        let queued: Vec<_> = replies.map(checked).collect();
+
+        match exec {
+            // commands that errored rather than queueing aren't in EXEC's results
+            WireType::Array(results) => {
+                let mut results = results.into_iter();
+                Ok(queued
+                    .into_iter()
+                    .map(|q| {
+                        q?;
+                        checked(results.next().ok_or_else(|| {
+                            RedisError::protocol("fewer results from EXEC than commands queued")
+                        })?)
+                    })
+                    .collect())
+            }
+            WireType::Null | WireType::NullArray => Err(RedisError::Aborted),
+            // EXECABORT, blame the commands that failed to queue and fail the rest along
+            // with them
+            WireType::Error(e) => Ok(queued
+                .into_iter()
+                .map(|q| match q {
+                    Err(q) => Err(q),
+                    Ok(_) => Err(RedisError::server(&e)),
+                })
+                .collect()),
+            reply => Err(RedisError::UnexpectedReply(reply)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server;
+
+    fn bulk(s: &str) -> WireType {
+        WireType::BulkString(s.as_bytes().to_vec())
+    }
+
+    fn ok() -> WireType {
+        WireType::SimpleString("OK".to_string())
+    }
+
+    #[test]
+    fn replies_in_order() {
+        for proto in [Protocol::Resp3, Protocol::Resp2] {
+            let mut cli = RedisClient::new(&test_server::spawn(proto));
+            let mut pipe = Pipeline::new();
+            pipe.add(Cmd::new("SET").arg("k").arg("v"))
+                .add(Cmd::new("INCR").arg("k"))
+                .add(Cmd::new("FROB"))
+                .add(Cmd::new("RPUSH").arg("l").args(&["a", "b"]))
+                .add(Cmd::new("GET").arg("k"));
+            assert_eq!(pipe.len(), 5);
+
+            let results = pipe.execute(&mut cli).unwrap();
+            assert_eq!(results.len(), 5);
+            assert_eq!(results[0].as_ref().unwrap(), &ok());
+            assert_eq!(
+                results[1].as_ref().unwrap_err().to_string(),
+                "ERR value is not an integer or out of range"
+            );
+            assert_eq!(
+                results[2].as_ref().unwrap_err().to_string(),
+                "ERR unknown command 'frob'"
+            );
+            assert_eq!(results[3].as_ref().unwrap(), &WireType::Integer(2));
+            assert_eq!(results[4].as_ref().unwrap(), &bulk("v"));
+
+            // the pipeline can go again, and the connection is still in step
+            assert_eq!(
+                pipe.execute(&mut cli).unwrap()[3].as_ref().unwrap(),
+                &WireType::Integer(4)
+            );
+            assert_eq!(cli.llen("l").unwrap(), 4);
+            assert!(Pipeline::new().execute(&mut cli).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn atomic() {
+        let mut cli = RedisClient::new(&test_server::spawn(Protocol::Resp3));
+        let mut pipe = Pipeline::atomic();
+        pipe.add(Cmd::new("INCR").arg("n"))
+            .add(Cmd::new("LPUSH").arg("n").arg("x"))
+            .add(Cmd::new("INCRBY").arg("n").arg(10i64));
+        let results = pipe.execute(&mut cli).unwrap();
+        assert_eq!(results[0].as_ref().unwrap(), &WireType::Integer(1));
+        // an error at run time doesn't undo the others, same as redis
+        assert_eq!(results[1].as_ref().unwrap_err().code(), Some("WRONGTYPE"));
+        assert_eq!(results[2].as_ref().unwrap(), &WireType::Integer(11));
+
+        // one that can't even be queued sinks the lot
+        pipe.clear();
+        pipe.add(Cmd::new("INCR").arg("n")).add(Cmd::new("FROB"));
+        let results = pipe.execute(&mut cli).unwrap();
+        assert_eq!(results[0].as_ref().unwrap_err().code(), Some("EXECABORT"));
+        assert_eq!(
+            results[1].as_ref().unwrap_err().to_string(),
+            "ERR unknown command 'frob'"
+        );
+        assert_eq!(cli.get("n").unwrap(), Some(b"11".to_vec()));
+
+        // a MULTI inside is refused, but doesn't stop the transaction
+        let mut pipe = Pipeline::atomic();
+        pipe.add(Cmd::new("MULTI")).add(Cmd::new("INCR").arg("n"));
+        let results = pipe.execute(&mut cli).unwrap();
+        assert_eq!(
+            results[0].as_ref().unwrap_err().to_string(),
+            "ERR MULTI calls can not be nested"
+        );
+        assert_eq!(results[1].as_ref().unwrap(), &WireType::Integer(12));
+
+        // inside a MULTI of the caller's own, it's the pipeline's MULTI that gets refused, and
+        // nothing of it ends up in the caller's transaction
+        cli.call(&["MULTI"]).unwrap();
+        let err = pipe.execute(&mut cli).unwrap_err();
+        assert_eq!(err.to_string(), "ERR MULTI calls can not be nested");
+        assert_eq!(cli.call(&["EXEC"]).unwrap(), WireType::Array(vec![]));
+        assert_eq!(cli.get("n").unwrap(), Some(b"12".to_vec()));
+    }
+}
@@ -1,164 +1,711 @@
 use crate::types::*;
 
 pub type WireVec = Vec<WireType>;
+pub type WireMap = Vec<(WireType, WireType)>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum WireType {
     SimpleString(String),
-    BulkString(String),
+    // binary safe, values can hold anything including \r\n
+    BulkString(Vec<u8>),
     Error(String),
-    Integer(i32),
+    Integer(i64),
     Array(WireVec),
+    // $-1 and *-1, what a missing key or a timed out BLPOP come back as
+    NullBulkString,
+    NullArray,
+
+    // everything from here down is RESP3 only, a server sends these once HELLO 3 has gone through
+    Null,
+    Boolean(bool),
+    Double(f64),
+    // digits too big for an i64, left as text
+    BigNumber(String),
+    // an error that needed a length prefix
+    BulkError(String),
+    // text plus a three letter hint at what it is, "txt" or "mkd"
+    Verbatim {
+        format: String,
+        text: Vec<u8>,
+    },
+    // kept in the order the server sent them
+    Map(WireMap),
+    Set(WireVec),
+    // extra information about a reply, tagged on in front of it
+    Attribute {
+        attrs: WireMap,
+        reply: Box<WireType>,
+    },
+    // out of band, not a reply to anything the client sent.  pubsub messages, tracking
+    // invalidations and the like
+    Push(WireVec),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    Resp2,
+    Resp3,
 }
 
 pub trait Wire {
-    fn serialize(&self) -> Vec<u8>;
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = vec![];
+        self.serialize_into(&mut out);
+        out
+    }
+
+    fn serialize_into(&self, out: &mut Vec<u8>);
 }
 
 impl Wire for WireType {
-    fn serialize(&self) -> Vec<u8> {
-        let mut out = String::new();
+    fn serialize_into(&self, out: &mut Vec<u8>) {
         match self {
             WireType::SimpleString(s) => {
-                out.push_str(&format!("+{}\r\n", s));
+                out.extend_from_slice(format!("+{}\r\n", s).as_bytes());
             }
             WireType::Error(s) => {
-                out.push_str(&format!("-{}\r\n", s));
+                out.extend_from_slice(format!("-{}\r\n", s).as_bytes());
             }
             WireType::Integer(i) => {
-                out.push_str(&format!(":{}\r\n", i));
+                out.extend_from_slice(format!(":{}\r\n", i).as_bytes());
             }
-            WireType::BulkString(s) => {
-                out.push_str(&format!("${}\r\n{}\r\n", &s.len(), &s));
+            WireType::BulkString(s) => blob(out, b'$', s),
+            WireType::Array(v) => v.serialize_into(out),
+            WireType::NullBulkString => out.extend_from_slice(b"$-1\r\n"),
+            WireType::NullArray => out.extend_from_slice(b"*-1\r\n"),
+            WireType::Null => out.extend_from_slice(b"_\r\n"),
+            WireType::Boolean(b) => {
+                out.extend_from_slice(if *b { b"#t\r\n" } else { b"#f\r\n" });
             }
-            WireType::Array(v) => {
-                out.push_str(&format!("*{}\r\n", v.len()));
-                for elem in v.iter() {
-                    out.push_str(std::str::from_utf8(&elem.serialize()).unwrap());
-                }
+            WireType::Double(d) => {
+                out.extend_from_slice(format!(",{}\r\n", double_text(*d)).as_bytes());
+            }
+            WireType::BigNumber(n) => {
+                out.extend_from_slice(format!("({}\r\n", n).as_bytes());
             }
+            WireType::BulkError(s) => blob(out, b'!', s.as_bytes()),
+            WireType::Verbatim { format, text } => {
+                let mut body = format!("{}:", format).into_bytes();
+                body.extend_from_slice(text);
+                blob(out, b'=', &body);
+            }
+            WireType::Map(m) => pairs(out, b'%', m),
+            WireType::Set(v) => elems(out, b'~', v),
+            WireType::Attribute { attrs, reply } => {
+                pairs(out, b'|', attrs);
+                reply.serialize_into(out);
+            }
+            WireType::Push(v) => elems(out, b'>', v),
         }
-        out.into_bytes()
     }
 }
 
 impl Wire for WireVec {
-    fn serialize(&self) -> Vec<u8> {
-        let mut out = String::new();
-        out.push_str(&format!("*{}\r\n", self.len()));
-        for elem in self.iter() {
-            out.push_str(std::str::from_utf8(&elem.serialize()).unwrap());
-        }
-        out.into_bytes()
+    fn serialize_into(&self, out: &mut Vec<u8>) {
+        elems(out, b'*', self);
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    #[test]
-    fn wire_types() {
-        let val = WireType::Integer(99);
-        let wire_val = val.serialize();
-        assert!(matches!(deserialize(&wire_val), val));
-
-        let val = WireType::Error("ERR: bad stuff".to_string());
-        let wire_val = val.serialize();
-        assert!(matches!(deserialize(&wire_val), Ok(val)));
-
-        let val = WireType::SimpleString("ERR: bad stuff".to_string());
-        let wire_val = val.serialize();
-        assert!(matches!(deserialize(&wire_val), Ok(val)));
-
-        let val = WireType::BulkString("foobar foobaz frobniz".to_string());
-        let wire_val = val.serialize();
-        assert!(matches!(deserialize(&wire_val), Ok(val)));
-
-        let mut arr = WireType::Array(vec![]);
-        // val.push(WireType::Integer(1));
-        // val.push(WireType::Integer(2));
-        // val.push(WireType::SimpleString("Hello".to_string()));
-        // val.push(WireType::Error("Goodbye".to_string()));
-        // let wire_val = val.serialize();
-        // let tmp = wire_val.clone();
-        // println!("{}", String::from_utf8(tmp).unwrap());
-        // if let Err(e) = deserialize(&wire_val) {
-        //     println!("{:?}", e);
-        // }
-        // assert!(matches!(deserialize(&wire_val), Ok(val)));
+// rust spells NaN its own way, resp wants nan
+pub fn double_text(d: f64) -> String {
+    if d.is_nan() {
+        "nan".to_string()
+    } else {
+        d.to_string()
     }
 }
 
-pub fn deserialize(bytes: &[u8]) -> RedisResult<WireType> {
-    match bytes {
-        [] => {
-            return Err(RedisError::new("empty"));
-        }
-        [b':', rest @ ..] => {
-            let s = std::str::from_utf8(rest)?;
-            let end = s.rfind("\r\n").unwrap_or(rest.len());
-            let i = s[0..end].parse::<i32>()?;
-            return Ok(WireType::Integer(i));
-        }
-        [b'-', rest @ ..] => {
-            let s = String::from_utf8(rest.to_vec())?;
-            let end = s.rfind("\r\n").unwrap_or(rest.len());
-            return Ok(WireType::Error(s[0..end].to_string()));
-        }
-        [b'+', rest @ ..] => {
-            let s = String::from_utf8(rest.to_vec())?;
-            let end = s.rfind("\r\n").unwrap_or(rest.len());
-            return Ok(WireType::SimpleString(s[0..end].to_string()));
+// a command the way clients send one, an array of bulk strings
+pub fn encode_command<A: AsRef<[u8]>>(args: &[A], out: &mut Vec<u8>) {
+    out.extend_from_slice(format!("*{}\r\n", args.len()).as_bytes());
+    for arg in args {
+        blob(out, b'$', arg.as_ref());
+    }
+}
+
+fn blob(out: &mut Vec<u8>, kind: u8, body: &[u8]) {
+    out.push(kind);
+    out.extend_from_slice(format!("{}\r\n", body.len()).as_bytes());
+    out.extend_from_slice(body);
+    out.extend_from_slice(b"\r\n");
+}
+
+fn elems(out: &mut Vec<u8>, kind: u8, v: &[WireType]) {
+    out.push(kind);
+    out.extend_from_slice(format!("{}\r\n", v.len()).as_bytes());
+    for elem in v {
+        elem.serialize_into(out);
+    }
+}
+
+fn pairs(out: &mut Vec<u8>, kind: u8, m: &[(WireType, WireType)]) {
+    out.push(kind);
+    out.extend_from_slice(format!("{}\r\n", m.len()).as_bytes());
+    for (k, v) in m {
+        k.serialize_into(out);
+        v.serialize_into(out);
+    }
+}
+
+// same cap the server puts on a single bulk string, anything bigger is garbage on the wire
+const MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+
+// don't trust a header's element count for preallocating
+const MAX_PREALLOC: usize = 1024;
+
+// aggregates nested any deeper than this are garbage too.  decoding doesn't recurse but dropping,
+// printing and comparing a reply all do, so this keeps a hostile one off the stack
+const MAX_DEPTH: usize = 512;
+
+#[derive(Debug, Clone, Copy)]
+enum Aggregate {
+    Array,
+    Set,
+    Push,
+    Map,
+    // the pairs, then the reply they're about
+    Attribute,
+}
+
+impl Aggregate {
+    // how many values follow a header saying len
+    fn want(self, len: usize) -> usize {
+        match self {
+            Aggregate::Map => len.saturating_mul(2),
+            Aggregate::Attribute => len.saturating_mul(2).saturating_add(1),
+            _ => len,
         }
-        [b'$', rest @ ..] => {
-            let s = format!(
-                "${}\r\n{}",
-                rest.len(),
-                String::from_utf8(rest.to_vec()).unwrap()
-            );
-            return Ok(WireType::BulkString(s));
+    }
+
+    fn finish(self, mut items: WireVec) -> WireType {
+        match self {
+            Aggregate::Array => WireType::Array(items),
+            Aggregate::Set => WireType::Set(items),
+            Aggregate::Push => WireType::Push(items),
+            Aggregate::Map => WireType::Map(pair_up(items)),
+            Aggregate::Attribute => {
+                let reply = Box::new(items.pop().unwrap());
+                WireType::Attribute {
+                    attrs: pair_up(items),
+                    reply,
+                }
+            }
         }
-        [b'*', rest @ ..] => {
-            let rest = std::str::from_utf8(rest)?;
-            let mut num_elements: u32;
-            let mut element_size: u32;
-            // simple finite state automata
-            // there has to be a simpler / more idiomatic way to do this?
-            enum ParseState {
-                Init,
-                Size,
-                Payload,
-                Done,
+    }
+}
+
+fn pair_up(items: WireVec) -> WireMap {
+    let mut out = Vec::with_capacity(items.len() / 2);
+    let mut items = items.into_iter();
+    while let (Some(k), Some(v)) = (items.next(), items.next()) {
+        out.push((k, v));
+    }
+    out
+}
+
+// an aggregate that has had its header read but is still waiting on elements
+#[derive(Debug)]
+struct Frame {
+    kind: Aggregate,
+    want: usize,
+    items: WireVec,
+}
+
+// one piece of the stream: either a whole value, or the header of an aggregate whose elements
+// follow
+enum Token {
+    Value(WireType),
+    Aggregate(Aggregate, usize),
+}
+
+// turns a byte stream into replies.  bytes go in with feed() in whatever chunks they arrive in,
+// decode() hands back each reply once all of it is there.  partly read aggregates are kept on a
+// stack
+// rather than reparsed, so a big reply costs the same however it gets split up.
+//
+// after a protocol error the stream is out of sync for good, the connection should be dropped
+#[derive(Debug, Default)]
+pub struct Decoder {
+    buf: Vec<u8>,
+    // everything before pos has been decoded already
+    pos: usize,
+    stack: Vec<Frame>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    // bytes fed in but not yet part of a returned reply
+    pub fn buffered(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+
+    // the next complete reply, or None if more bytes are needed first
+    pub fn decode(&mut self) -> RedisResult<Option<WireType>> {
+        loop {
+            let (token, used) = match next_token(&self.buf[self.pos..])? {
+                Some(next) => next,
+                None => {
+                    self.compact();
+                    return Ok(None);
+                }
             };
-            let mut state = ParseState::Init;
+            self.pos += used;
+
+            let mut val = match token {
+                Token::Value(val) => val,
+                Token::Aggregate(kind, len) => match kind.want(len) {
+                    0 => kind.finish(vec![]),
+                    want => {
+                        if self.stack.len() >= MAX_DEPTH {
+                            return Err(RedisError::protocol("nesting too deep"));
+                        }
+                        self.stack.push(Frame {
+                            kind,
+                            want,
+                            items: Vec::with_capacity(want.min(MAX_PREALLOC)),
+                        });
+                        continue;
+                    }
+                },
+            };
+
+            // hand the value up to whatever aggregate is waiting on it, finishing off any that
+            // fills along the way
             loop {
-                println!("rest: '{}'", rest);
-                match state {
-                    ParseState::Init => {
-                        let (head, rest) = rest.split_at(1);
-                        num_elements = head.parse()?;
-                        state = ParseState::Size;
+                match self.stack.last_mut() {
+                    None => {
+                        self.compact();
+                        return Ok(Some(val));
                     }
-                    ParseState::Size => {
-                        let (head, rest) = rest.split_at(1);
-                        let q = head.parse::<char>()?;
-                        if q != '?' {
-                            return Err(RedisError::new("missing size prefix"));
+                    Some(frame) => {
+                        frame.items.push(val);
+                        if frame.items.len() < frame.want {
+                            break;
                         }
-                        state = ParseState::Payload;
-                    }
-                    ParseState::Payload => {
-                        element_size = rest.parse()?;
-                        state = ParseState::Size;
                     }
-                    ParseState::Done => break,
                 }
+                let frame = self.stack.pop().unwrap();
+                val = frame.kind.finish(frame.items);
+            }
+        }
+    }
+
+    // drop the decoded bytes from the front once they make up most of the buffer
+    fn compact(&mut self) {
+        if self.pos > 0 && self.pos >= self.buf.len() / 2 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+    }
+}
+
+// a whole reply at once, erroring if it's cut short
+pub fn deserialize(bytes: &[u8]) -> RedisResult<WireType> {
+    let mut decoder = Decoder::new();
+    decoder.feed(bytes);
+    match decoder.decode()? {
+        Some(val) => Ok(val),
+        None => Err(RedisError::protocol("incomplete reply")),
+    }
+}
+
+// the line after the type byte, up to but not including its \r\n, and how many bytes that took
+// with the type byte and \r\n counted.  None if the \r\n hasn't arrived
+fn line(buf: &[u8]) -> Option<(&[u8], usize)> {
+    let end = buf.windows(2).position(|w| w == b"\r\n")?;
+    Some((&buf[1..end], end + 2))
+}
+
+fn text(line: &[u8]) -> RedisResult<String> {
+    Ok(std::str::from_utf8(line)?.to_string())
+}
+
+fn number(line: &[u8]) -> RedisResult<i64> {
+    std::str::from_utf8(line)?
+        .parse::<i64>()
+        .map_err(|_| RedisError::protocol("invalid number"))
+}
+
+// a length that's allowed to be -1 for null
+fn length(line: &[u8]) -> RedisResult<Option<usize>> {
+    match number(line)? {
+        -1 => Ok(None),
+        n if n < 0 => Err(RedisError::protocol("negative length")),
+        n => Ok(Some(n as usize)),
+    }
+}
+
+// the body of a length prefixed value, and where it ends.  None if it hasn't all arrived
+fn body(buf: &[u8], used: usize, len: usize) -> RedisResult<Option<(&[u8], usize)>> {
+    if len > MAX_BULK_LEN {
+        return Err(RedisError::protocol("bulk string too long"));
+    }
+    let body = &buf[used..];
+    if body.len() < len + 2 {
+        return Ok(None);
+    }
+    if &body[len..len + 2] != b"\r\n" {
+        return Err(RedisError::protocol("bulk string missing its \\r\\n"));
+    }
+    Ok(Some((&body[..len], used + len + 2)))
+}
+
+fn double(line: &[u8]) -> RedisResult<f64> {
+    let line = std::str::from_utf8(line)?;
+    // rust would also take things like "infinity" and "1e", resp is pickier than that
+    let ok = match line {
+        "inf" | "-inf" | "nan" => true,
+        _ => line
+            .bytes()
+            .all(|b| b.is_ascii_digit() || b"+-.eE".contains(&b)),
+    };
+    match line.parse::<f64>() {
+        Ok(d) if ok => Ok(d),
+        _ => Err(RedisError::protocol("invalid double")),
+    }
+}
+
+fn big_number(line: &[u8]) -> RedisResult<String> {
+    let digits = line.strip_prefix(b"-").unwrap_or(line);
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return Err(RedisError::protocol("invalid big number"));
+    }
+    text(line)
+}
+
+fn next_token(buf: &[u8]) -> RedisResult<Option<(Token, usize)>> {
+    let kind = match buf.first() {
+        Some(kind) => *kind,
+        None => return Ok(None),
+    };
+    let (line, used) = match line(buf) {
+        Some(line) => line,
+        None => return Ok(None),
+    };
+
+    let aggregate = match kind {
+        b'*' => Some(Aggregate::Array),
+        b'~' => Some(Aggregate::Set),
+        b'>' => Some(Aggregate::Push),
+        b'%' => Some(Aggregate::Map),
+        b'|' => Some(Aggregate::Attribute),
+        _ => None,
+    };
+    if let Some(aggregate) = aggregate {
+        return Ok(Some(match length(line)? {
+            // only arrays have a null form, the rest have _ for that
+            None if kind == b'*' => (Token::Value(WireType::NullArray), used),
+            None => return Err(RedisError::protocol("negative length")),
+            Some(len) => (Token::Aggregate(aggregate, len), used),
+        }));
+    }
+
+    if let b'$' | b'!' | b'=' = kind {
+        let len = match length(line)? {
+            None if kind == b'$' => {
+                return Ok(Some((Token::Value(WireType::NullBulkString), used)))
+            }
+            None => return Err(RedisError::protocol("negative length")),
+            Some(len) => len,
+        };
+        let (body, used) = match body(buf, used, len)? {
+            Some(body) => body,
+            None => return Ok(None),
+        };
+        let val = match kind {
+            b'$' => WireType::BulkString(body.to_vec()),
+            b'!' => WireType::BulkError(text(body)?),
+            _ => match body.get(3) {
+                Some(b':') => WireType::Verbatim {
+                    format: text(&body[..3])?,
+                    text: body[4..].to_vec(),
+                },
+                _ => return Err(RedisError::protocol("verbatim string without a format")),
+            },
+        };
+        return Ok(Some((Token::Value(val), used)));
+    }
+
+    let val = match kind {
+        b'+' => WireType::SimpleString(text(line)?),
+        b'-' => WireType::Error(text(line)?),
+        b':' => WireType::Integer(number(line)?),
+        b'_' if line.is_empty() => WireType::Null,
+        b'#' => match line {
+            b"t" => WireType::Boolean(true),
+            b"f" => WireType::Boolean(false),
+            _ => return Err(RedisError::protocol("invalid boolean")),
+        },
+        b',' => WireType::Double(double(line)?),
+        b'(' => WireType::BigNumber(big_number(line)?),
+        other => {
+            return Err(RedisError::protocol(&format!(
+                "unexpected type byte {:?}",
+                other as char
+            )))
+        }
+    };
+    Ok(Some((Token::Value(val), used)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bulk(s: &[u8]) -> WireType {
+        WireType::BulkString(s.to_vec())
+    }
+
+    // a spread of replies, scalars and nested arrays, with the awkward cases mixed in
+    fn samples() -> Vec<WireType> {
+        let scalars = vec![
+            WireType::SimpleString("OK".to_string()),
+            WireType::SimpleString(String::new()),
+            WireType::Error("ERR unknown command 'foo'".to_string()),
+            WireType::Integer(0),
+            WireType::Integer(-1),
+            WireType::Integer(i64::MAX),
+            WireType::Integer(i64::MIN),
+            bulk(b""),
+            bulk(b"foobar foobaz frobniz"),
+            bulk(b"line\r\nbreaks\r\n"),
+            bulk(&[0, 255, 13, 10, 36, 42]),
+            bulk(&vec![b'x'; 10000]),
+            WireType::NullBulkString,
+            WireType::NullArray,
+            WireType::Null,
+            WireType::Boolean(true),
+            WireType::Boolean(false),
+            WireType::Double(1.5),
+            WireType::Double(-0.001),
+            WireType::Double(1e300),
+            WireType::Double(f64::INFINITY),
+            WireType::Double(f64::NEG_INFINITY),
+            WireType::BigNumber("3492890328409238509324850943850943825024385".to_string()),
+            WireType::BigNumber("-1".to_string()),
+            WireType::BulkError("SYNTAX invalid\r\nsyntax".to_string()),
+            WireType::Verbatim {
+                format: "txt".to_string(),
+                text: b"Some string".to_vec(),
+            },
+            WireType::Verbatim {
+                format: "mkd".to_string(),
+                text: vec![],
+            },
+        ];
+        let mut out = scalars.clone();
+        out.push(WireType::Array(vec![]));
+        out.push(WireType::Array(scalars.clone()));
+        out.push(WireType::Array(vec![
+            WireType::Array(vec![]),
+            WireType::Array(vec![WireType::Array(vec![WireType::Integer(1)])]),
+            WireType::NullArray,
+            WireType::Array(scalars.clone()),
+            bulk(b"tail"),
+        ]));
+        out.push(WireType::Map(vec![]));
+        out.push(WireType::Map(vec![
+            (bulk(b"first"), WireType::Integer(1)),
+            (
+                WireType::Array(vec![WireType::Null]),
+                WireType::Set(scalars.clone()),
+            ),
+            (WireType::Double(2.5), WireType::Map(vec![])),
+        ]));
+        out.push(WireType::Set(vec![]));
+        out.push(WireType::Push(vec![
+            bulk(b"message"),
+            bulk(b"chan"),
+            WireType::Set(vec![WireType::Boolean(false)]),
+        ]));
+        out.push(WireType::Attribute {
+            attrs: vec![],
+            reply: Box::new(WireType::Integer(7)),
+        });
+        out.push(WireType::Array(vec![
+            WireType::Attribute {
+                attrs: vec![(
+                    bulk(b"key-popularity"),
+                    WireType::Map(vec![(bulk(b"a"), WireType::Double(0.1923))]),
+                )],
+                reply: Box::new(WireType::Array(vec![WireType::Integer(2039123)])),
+            },
+            WireType::Integer(9543892),
+        ]));
+        out
+    }
+
+    #[test]
+    fn round_trips_at_every_split() {
+        for val in samples() {
+            let wire = val.serialize();
+            assert_eq!(deserialize(&wire).unwrap(), val);
+
+            // any prefix is incomplete, the rest completes it
+            for split in 0..wire.len() {
+                let mut dec = Decoder::new();
+                dec.feed(&wire[..split]);
+                assert_eq!(dec.decode().unwrap(), None, "{:?} split at {}", val, split);
+                dec.feed(&wire[split..]);
+                assert_eq!(dec.decode().unwrap(), Some(val.clone()));
+                assert_eq!(dec.buffered(), 0);
             }
-            return Err(RedisError::new("invalid"));
         }
+    }
 
-        _ => {
-            return Err(RedisError::new("unhandled"));
+    #[test]
+    fn streams_byte_at_a_time() {
+        let vals = samples();
+        let mut wire = vec![];
+        for val in &vals {
+            val.serialize_into(&mut wire);
         }
+
+        let mut dec = Decoder::new();
+        let mut got = vec![];
+        for b in wire {
+            dec.feed(&[b]);
+            while let Some(val) = dec.decode().unwrap() {
+                got.push(val);
+            }
+        }
+        assert_eq!(got, vals);
+    }
+
+    #[test]
+    fn replies_back_to_back() {
+        let mut dec = Decoder::new();
+        dec.feed(b"+OK\r\n:42\r\n$3\r\nfoo\r\n$-1\r\n*2\r\n:1\r\n");
+        assert_eq!(
+            dec.decode().unwrap(),
+            Some(WireType::SimpleString("OK".into()))
+        );
+        assert_eq!(dec.decode().unwrap(), Some(WireType::Integer(42)));
+        assert_eq!(dec.decode().unwrap(), Some(bulk(b"foo")));
+        assert_eq!(dec.decode().unwrap(), Some(WireType::NullBulkString));
+        assert_eq!(dec.decode().unwrap(), None);
+        dec.feed(b":2\r\n");
+        assert_eq!(
+            dec.decode().unwrap(),
+            Some(WireType::Array(vec![
+                WireType::Integer(1),
+                WireType::Integer(2)
+            ]))
+        );
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        let bad: &[&[u8]] = &[
+            b"?\r\n",
+            b":12x\r\n",
+            b":99999999999999999999\r\n",
+            b"$-2\r\n",
+            b"$3\r\nfoobar\r\n",
+            b"*-5\r\n",
+            b"+\xff\r\n",
+            b"_x\r\n",
+            b"#x\r\n",
+            b",1.5x\r\n",
+            b",infinity\r\n",
+            b"(12a\r\n",
+            b"(\r\n",
+            b"=3\r\ntxt\r\n",
+            b"%-1\r\n",
+            b"~-1\r\n",
+        ];
+        for wire in bad {
+            assert!(deserialize(wire).is_err(), "{:?}", wire);
+        }
+        let deep = [b"*1\r\n".repeat(100_000), b":1\r\n".to_vec()].concat();
+        assert!(deserialize(&deep).is_err());
+        assert!(deserialize(b"$3\r\nfo").is_err());
+    }
+
+    #[test]
+    fn resp3_spec_examples() {
+        let cases: Vec<(&[u8], WireType)> = vec![
+            (b"_\r\n", WireType::Null),
+            (b",1.23\r\n", WireType::Double(1.23)),
+            (b",10\r\n", WireType::Double(10.0)),
+            (b",-inf\r\n", WireType::Double(f64::NEG_INFINITY)),
+            (b"#t\r\n", WireType::Boolean(true)),
+            (
+                b"!21\r\nSYNTAX invalid syntax\r\n",
+                WireType::BulkError("SYNTAX invalid syntax".to_string()),
+            ),
+            (
+                b"=15\r\ntxt:Some string\r\n",
+                WireType::Verbatim {
+                    format: "txt".to_string(),
+                    text: b"Some string".to_vec(),
+                },
+            ),
+            (
+                b"%2\r\n+first\r\n:1\r\n+second\r\n:2\r\n",
+                WireType::Map(vec![
+                    (WireType::SimpleString("first".into()), WireType::Integer(1)),
+                    (
+                        WireType::SimpleString("second".into()),
+                        WireType::Integer(2),
+                    ),
+                ]),
+            ),
+            (
+                b"~3\r\n+orange\r\n+apple\r\n#t\r\n",
+                WireType::Set(vec![
+                    WireType::SimpleString("orange".into()),
+                    WireType::SimpleString("apple".into()),
+                    WireType::Boolean(true),
+                ]),
+            ),
+            (
+                b"|1\r\n+key-popularity\r\n%1\r\n$1\r\na\r\n,0.1923\r\n*1\r\n:2039123\r\n",
+                WireType::Attribute {
+                    attrs: vec![(
+                        WireType::SimpleString("key-popularity".into()),
+                        WireType::Map(vec![(bulk(b"a"), WireType::Double(0.1923))]),
+                    )],
+                    reply: Box::new(WireType::Array(vec![WireType::Integer(2039123)])),
+                },
+            ),
+            (
+                b">2\r\n+pubsub\r\n+message\r\n",
+                WireType::Push(vec![
+                    WireType::SimpleString("pubsub".into()),
+                    WireType::SimpleString("message".into()),
+                ]),
+            ),
+        ];
+        for (wire, val) in cases {
+            assert_eq!(deserialize(wire).unwrap(), val);
+        }
+
+        // NaN never equals itself, so it can't go through the round trips
+        let nan = WireType::Double(f64::NAN);
+        assert_eq!(nan.serialize(), b",nan\r\n".to_vec());
+        match deserialize(b",nan\r\n").unwrap() {
+            WireType::Double(d) => assert!(d.is_nan()),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn commands_serialize_as_bulk_arrays() {
+        let cmd: WireVec = vec![bulk(b"SET"), bulk(b"k"), bulk(b"a b")];
+        assert_eq!(
+            cmd.serialize(),
+            b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$3\r\na b\r\n".to_vec()
+        );
+        let mut out = vec![];
+        encode_command(&["SET", "k", "a b"], &mut out);
+        assert_eq!(out, cmd.serialize());
     }
 }
@@ -0,0 +1,794 @@
+// a stand-in for redis-server, just enough of one for the client's tests.  a thread per
+// connection, with the keys shared between them
+use crate::protocol::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::io::prelude::*;
+use std::net::Shutdown;
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+enum Value {
+    Str(Vec<u8>),
+    List(VecDeque<Vec<u8>>),
+    Hash(BTreeMap<Vec<u8>, Vec<u8>>),
+    Set(BTreeSet<Vec<u8>>),
+    ZSet(BTreeMap<Vec<u8>, f64>),
+}
+
+impl Value {
+    // redis drops a key once its collection has nothing left in it
+    fn is_empty(&self) -> bool {
+        match self {
+            Value::Str(_) => false,
+            Value::List(l) => l.is_empty(),
+            Value::Hash(h) => h.is_empty(),
+            Value::Set(s) => s.is_empty(),
+            Value::ZSet(z) => z.is_empty(),
+        }
+    }
+}
+
+struct Entry {
+    value: Value,
+    expires: Option<Instant>,
+}
+
+// keyed by database number and key
+type Key = (i64, Vec<u8>);
+type Store = HashMap<Key, Entry>;
+type Db = Arc<Mutex<Store>>;
+
+// a reply, or the text of an error reply
+type Reply = Result<WireType, String>;
+
+const WRONGTYPE: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
+
+const COMMANDS: &[&str] = &[
+    "PING",
+    "AUTH",
+    "HELLO",
+    "CONFIG",
+    "CLIENT",
+    "SELECT",
+    "GET",
+    "SET",
+    "INCR",
+    "INCRBY",
+    "DECR",
+    "DEL",
+    "EXISTS",
+    "EXPIRE",
+    "TTL",
+    "LPUSH",
+    "RPUSH",
+    "LPOP",
+    "RPOP",
+    "LRANGE",
+    "LLEN",
+    "HSET",
+    "HGET",
+    "HDEL",
+    "HGETALL",
+    "HLEN",
+    "SADD",
+    "SREM",
+    "SMEMBERS",
+    "SISMEMBER",
+    "SCARD",
+    "ZADD",
+    "ZINCRBY",
+    "ZREM",
+    "ZSCORE",
+    "ZRANGE",
+    "ZCARD",
+];
+
+// what a connection has asked for so far
+struct Conn {
+    // the newest protocol the server will agree to
+    max: Protocol,
+    protocol: Protocol,
+    db: i64,
+    tracking: bool,
+    // keys read since tracking went on, a write to one gets an invalidation pushed
+    tracked: HashSet<Vec<u8>>,
+    // commands waiting on EXEC, between it and MULTI
+    queued: Option<Vec<(String, Vec<Vec<u8>>)>>,
+    // a command that couldn't be queued, EXEC refuses to run the rest
+    dirty: bool,
+    // requirepass, nothing but AUTH goes until it's been given
+    password: Option<Vec<u8>>,
+    authed: bool,
+    // every RESP3 reply, errors included, goes out with an attribute in front
+    attributes: bool,
+}
+
+// listen on a free port, returning the address to connect to.  a server with max Resp2 behaves
+// like one from before HELLO existed
+pub fn spawn(max: Protocol) -> String {
+    Server::start(max, "127.0.0.1:0", None).addr().to_string()
+}
+
+// a server that can be taken down, connections and all, and brought back up on the same port.
+// it comes back empty, like a redis-server without persistence
+pub struct Server {
+    addr: String,
+    stopped: Arc<AtomicBool>,
+    conns: Arc<Mutex<Vec<TcpStream>>>,
+    accept: JoinHandle<()>,
+}
+
+impl Server {
+    pub fn start(max: Protocol, addr: &str, password: Option<&str>) -> Server {
+        let listener = TcpListener::bind(addr).unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let conns = Arc::new(Mutex::new(Vec::<TcpStream>::new()));
+        let password = password.map(|p| p.as_bytes().to_vec());
+        let db = Db::default();
+        let accept = {
+            let stopped = stopped.clone();
+            let conns = conns.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(_) => break,
+                    };
+                    if let Ok(clone) = stream.try_clone() {
+                        conns.lock().unwrap().push(clone);
+                    }
+                    let db = db.clone();
+                    let password = password.clone();
+                    thread::spawn(move || serve(stream, db, max, password));
+                }
+            })
+        };
+        Server {
+            addr,
+            stopped,
+            conns,
+            accept,
+        }
+    }
+
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    // stop listening and drop every connection.  the port is free again once this returns
+    pub fn stop(self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // wake the accept loop up so it sees it's been stopped
+        let _ = TcpStream::connect(&self.addr);
+        let _ = self.accept.join();
+        for conn in self.conns.lock().unwrap().drain(..) {
+            let _ = conn.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+fn serve(mut stream: TcpStream, db: Db, max: Protocol, password: Option<Vec<u8>>) {
+    // as redis-server does, otherwise pipelined replies split over several writes sit waiting
+    // on delayed acks
+    let _ = stream.set_nodelay(true);
+    let mut conn = Conn {
+        max,
+        protocol: Protocol::Resp2,
+        db: 0,
+        tracking: false,
+        tracked: HashSet::new(),
+        queued: None,
+        dirty: false,
+        authed: password.is_none(),
+        password,
+        attributes: false,
+    };
+    let mut decoder = Decoder::new();
+    let mut buf = [0u8; 16 * 1024];
+    loop {
+        let mut out = vec![];
+        loop {
+            match decoder.decode() {
+                Ok(Some(req)) => handle(&mut conn, &db, req, &mut out),
+                Ok(None) => break,
+                Err(_) => return,
+            }
+        }
+        // everything that came in one read goes back in one write
+        if !out.is_empty() && stream.write_all(&out).is_err() {
+            return;
+        }
+        match stream.read(&mut buf) {
+            Ok(0) | Err(_) => return,
+            Ok(n) => decoder.feed(&buf[..n]),
+        }
+    }
+}
+
+fn bulk(s: &[u8]) -> WireType {
+    WireType::BulkString(s.to_vec())
+}
+
+fn ok() -> WireType {
+    WireType::SimpleString("OK".to_string())
+}
+
+fn int(n: usize) -> WireType {
+    WireType::Integer(n as i64)
+}
+
+// a map in resp3, flattened out into an array for resp2 like the real server does
+fn map(conn: &Conn, pairs: WireMap) -> WireType {
+    match conn.protocol {
+        Protocol::Resp3 => WireType::Map(pairs),
+        Protocol::Resp2 => {
+            WireType::Array(pairs.into_iter().flat_map(|(k, v)| vec![k, v]).collect())
+        }
+    }
+}
+
+fn null(conn: &Conn) -> WireType {
+    match conn.protocol {
+        Protocol::Resp3 => WireType::Null,
+        Protocol::Resp2 => WireType::NullBulkString,
+    }
+}
+
+fn double(conn: &Conn, d: f64) -> WireType {
+    match conn.protocol {
+        Protocol::Resp3 => WireType::Double(d),
+        Protocol::Resp2 => bulk(double_text(d).as_bytes()),
+    }
+}
+
+fn int_arg(arg: &[u8]) -> Result<i64, String> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|arg| arg.parse().ok())
+        .ok_or_else(|| "ERR value is not an integer or out of range".to_string())
+}
+
+fn float_arg(arg: &[u8]) -> Result<f64, String> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|arg| arg.parse().ok())
+        .ok_or_else(|| "ERR value is not a valid float".to_string())
+}
+
+// start and stop the way LRANGE and ZRANGE take them, negative counting from the end
+fn range(len: usize, start: i64, stop: i64) -> std::ops::Range<usize> {
+    let len = len as i64;
+    let start = if start < 0 { len + start } else { start }.max(0);
+    let stop = if stop < 0 { len + stop } else { stop }.min(len - 1);
+    if start > stop {
+        0..0
+    } else {
+        start as usize..stop as usize + 1
+    }
+}
+
+// the value at a key, unless it's expired
+fn live<'a>(store: &'a mut Store, key: &Key) -> Option<&'a mut Value> {
+    if store
+        .get(key)
+        .and_then(|e| e.expires)
+        .is_some_and(|at| at <= Instant::now())
+    {
+        store.remove(key);
+    }
+    store.get_mut(key).map(|e| &mut e.value)
+}
+
+// the value at a key, starting off as empty if there isn't one
+fn create<'a>(store: &'a mut Store, key: &Key, empty: Value) -> &'a mut Value {
+    live(store, key);
+    &mut store
+        .entry(key.clone())
+        .or_insert(Entry {
+            value: empty,
+            expires: None,
+        })
+        .value
+}
+
+// the insides of a value of the given kind, or a WRONGTYPE error reply
+macro_rules! expect {
+    ($value:expr, $kind:path) => {
+        match $value {
+            $kind(v) => v,
+            _ => return Err(WRONGTYPE.to_string()),
+        }
+    };
+}
+
+fn handle(conn: &mut Conn, db: &Db, req: WireType, out: &mut Vec<u8>) {
+    let mut args: Vec<Vec<u8>> = match req {
+        WireType::Array(args) => args
+            .into_iter()
+            .map(|arg| match arg {
+                WireType::BulkString(arg) => arg,
+                _ => vec![],
+            })
+            .collect(),
+        _ => return,
+    };
+    if args.is_empty() {
+        return;
+    }
+    let name = String::from_utf8_lossy(&args.remove(0)).to_uppercase();
+
+    let mut store = db.lock().unwrap();
+    let reply = match (name.as_str(), conn.queued.is_some()) {
+        (name, _) if !conn.authed && name != "AUTH" => {
+            Err("NOAUTH Authentication required.".to_string())
+        }
+        ("MULTI", true) => Err("ERR MULTI calls can not be nested".to_string()),
+        ("MULTI", false) => {
+            conn.queued = Some(vec![]);
+            conn.dirty = false;
+            Ok(ok())
+        }
+        ("EXEC", false) => Err("ERR EXEC without MULTI".to_string()),
+        ("DISCARD", false) => Err("ERR DISCARD without MULTI".to_string()),
+        ("DISCARD", true) => {
+            conn.queued = None;
+            Ok(ok())
+        }
+        // the store stays locked the whole way through, nothing else gets in between
+        ("EXEC", true) => {
+            let queued = conn.queued.take().unwrap_or_default();
+            if conn.dirty {
+                Err("EXECABORT Transaction discarded because of previous errors.".to_string())
+            } else {
+                Ok(WireType::Array(
+                    queued
+                        .into_iter()
+                        .map(
+                            |(name, args)| match run(conn, &mut store, &name, &args, out) {
+                                Ok(reply) => reply,
+                                Err(e) => WireType::Error(e),
+                            },
+                        )
+                        .collect(),
+                ))
+            }
+        }
+        (_, true) if COMMANDS.contains(&name.as_str()) => {
+            conn.queued.get_or_insert_with(Vec::new).push((name, args));
+            Ok(WireType::SimpleString("QUEUED".to_string()))
+        }
+        (_, true) => {
+            conn.dirty = true;
+            Err(unknown(&name))
+        }
+        _ => run(conn, &mut store, &name, &args, out),
+    };
+    let reply = reply.unwrap_or_else(WireType::Error);
+    if conn.attributes && conn.protocol == Protocol::Resp3 {
+        let attrs = vec![(bulk(b"key-popularity"), WireType::Array(vec![]))];
+        WireType::Attribute {
+            attrs,
+            reply: Box::new(reply),
+        }
+        .serialize_into(out)
+    } else {
+        reply.serialize_into(out)
+    }
+}
+
+fn run(
+    conn: &mut Conn,
+    store: &mut Store,
+    name: &str,
+    args: &[Vec<u8>],
+    out: &mut Vec<u8>,
+) -> Reply {
+    let args: Vec<&[u8]> = args.iter().map(|a| a.as_slice()).collect();
+    let reply = command(conn, store, name, &args, out);
+    // a command's first argument is usually its key, drop that if the command emptied it out
+    if let Some(key) = args.first() {
+        let key = (conn.db, key.to_vec());
+        if store.get(&key).is_some_and(|e| e.value.is_empty()) {
+            store.remove(&key);
+        }
+    }
+    reply
+}
+
+fn unknown(name: &str) -> String {
+    format!("ERR unknown command '{}'", name.to_lowercase())
+}
+
+fn command(
+    conn: &mut Conn,
+    store: &mut Store,
+    name: &str,
+    args: &[&[u8]],
+    out: &mut Vec<u8>,
+) -> Reply {
+    let db = conn.db;
+    let key = |i: usize| (db, args[i].to_vec());
+
+    Ok(match (name, args) {
+        ("PING", []) => WireType::SimpleString("PONG".to_string()),
+        ("AUTH", [_, _]) | ("AUTH", [_]) => {
+            let (user, pass) = match args {
+                [user, pass] => (*user, *pass),
+                _ => (&b"default"[..], args[0]),
+            };
+            match &conn.password {
+                None => return Err("ERR AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?".into()),
+                Some(p) if user == b"default" && p.as_slice() == pass => {
+                    conn.authed = true;
+                    ok()
+                }
+                Some(_) => return Err("WRONGPASS invalid username-password pair or user is disabled.".into()),
+            }
+        }
+        ("HELLO", [ver]) if conn.max == Protocol::Resp3 => {
+            conn.protocol = match *ver {
+                b"2" => Protocol::Resp2,
+                b"3" => Protocol::Resp3,
+                _ => return Err("NOPROTO sorry, this protocol version is not supported".into()),
+            };
+            let proto = match conn.protocol {
+                Protocol::Resp2 => 2,
+                Protocol::Resp3 => 3,
+            };
+            map(
+                conn,
+                vec![
+                    (bulk(b"server"), bulk(b"redis")),
+                    (bulk(b"version"), bulk(b"7.2.0")),
+                    (bulk(b"proto"), WireType::Integer(proto)),
+                    (bulk(b"mode"), bulk(b"standalone")),
+                ],
+            )
+        }
+        ("CONFIG", [get, param]) if get.eq_ignore_ascii_case(b"GET") => {
+            map(conn, vec![(bulk(param), bulk(b"0"))])
+        }
+        ("CLIENT", [tracking, on])
+            if tracking.eq_ignore_ascii_case(b"TRACKING") && on.eq_ignore_ascii_case(b"ON") =>
+        {
+            conn.tracking = true;
+            ok()
+        }
+        // not one of redis's.  real servers rarely send attributes, this makes them common
+        // enough to test against
+        ("DEBUG", [attributes, on])
+            if attributes.eq_ignore_ascii_case(b"ATTRIBUTES") && on.eq_ignore_ascii_case(b"ON") =>
+        {
+            conn.attributes = true;
+            ok()
+        }
+        ("SELECT", [db]) => match int_arg(db) {
+            Ok(db @ 0..=15) => {
+                conn.db = db;
+                ok()
+            }
+            _ => return Err("ERR DB index is out of range".into()),
+        },
+
+        // strings
+        ("GET", [k]) => {
+            if conn.tracking {
+                conn.tracked.insert(k.to_vec());
+            }
+            match live(store, &key(0)) {
+                Some(Value::Str(v)) => bulk(v),
+                Some(_) => return Err(WRONGTYPE.into()),
+                None => null(conn),
+            }
+        }
+        ("SET", [k, v, opts @ ..]) => {
+            let (mut nx, mut xx, mut ttl) = (false, false, None);
+            let mut opts = opts.iter();
+            while let Some(opt) = opts.next() {
+                match opt.to_ascii_uppercase().as_slice() {
+                    b"NX" => nx = true,
+                    b"XX" => xx = true,
+                    unit @ (b"EX" | b"PX") => {
+                        let n = int_arg(opts.next().ok_or("ERR syntax error")?)?;
+                        if n <= 0 {
+                            return Err("ERR invalid expire time in 'set' command".into());
+                        }
+                        ttl = Some(match unit {
+                            b"EX" => Duration::from_secs(n as u64),
+                            _ => Duration::from_millis(n as u64),
+                        });
+                    }
+                    _ => return Err("ERR syntax error".into()),
+                }
+            }
+            let exists = live(store, &key(0)).is_some();
+            if (nx && exists) || (xx && !exists) {
+                return Ok(null(conn));
+            }
+            store.insert(
+                key(0),
+                Entry {
+                    value: Value::Str(v.to_vec()),
+                    expires: ttl.map(|ttl| Instant::now() + ttl),
+                },
+            );
+            if conn.protocol == Protocol::Resp3 && conn.tracked.remove(*k) {
+                WireType::Push(vec![bulk(b"invalidate"), WireType::Array(vec![bulk(k)])])
+                    .serialize_into(out);
+            }
+            ok()
+        }
+        ("INCR", [_]) | ("DECR", [_]) | ("INCRBY", [_, _]) => {
+            let by = match name {
+                "INCR" => 1,
+                "DECR" => -1,
+                _ => int_arg(args[1])?,
+            };
+            let v = expect!(
+                create(store, &key(0), Value::Str(b"0".to_vec())),
+                Value::Str
+            );
+            let n = int_arg(v)?
+                .checked_add(by)
+                .ok_or("ERR increment or decrement would overflow")?;
+            *v = n.to_string().into_bytes();
+            WireType::Integer(n)
+        }
+
+        // keys
+        ("DEL", [_, ..]) | ("EXISTS", [_, ..]) => {
+            let mut n = 0;
+            for k in args {
+                let k = (conn.db, k.to_vec());
+                if live(store, &k).is_some() {
+                    n += 1;
+                    if name == "DEL" {
+                        store.remove(&k);
+                    }
+                }
+            }
+            int(n)
+        }
+        ("EXPIRE", [_, secs]) => {
+            let secs = int_arg(secs)?;
+            match live(store, &key(0)) {
+                None => int(0),
+                Some(_) if secs <= 0 => {
+                    store.remove(&key(0));
+                    int(1)
+                }
+                Some(_) => {
+                    store.get_mut(&key(0)).unwrap().expires =
+                        Some(Instant::now() + Duration::from_secs(secs as u64));
+                    int(1)
+                }
+            }
+        }
+        ("TTL", [_]) => match live(store, &key(0)) {
+            None => WireType::Integer(-2),
+            Some(_) => match store[&key(0)].expires {
+                None => WireType::Integer(-1),
+                Some(at) => {
+                    let left = at.saturating_duration_since(Instant::now());
+                    WireType::Integer(((left.as_millis() + 500) / 1000) as i64)
+                }
+            },
+        },
+
+        // lists
+        ("LPUSH", [_, _, ..]) | ("RPUSH", [_, _, ..]) => {
+            let list = expect!(
+                create(store, &key(0), Value::List(VecDeque::new())),
+                Value::List
+            );
+            for v in &args[1..] {
+                match name {
+                    "LPUSH" => list.push_front(v.to_vec()),
+                    _ => list.push_back(v.to_vec()),
+                }
+            }
+            int(list.len())
+        }
+        ("LPOP", [_]) | ("RPOP", [_]) => match live(store, &key(0)) {
+            None => null(conn),
+            Some(value) => {
+                let list = expect!(value, Value::List);
+                let v = match name {
+                    "LPOP" => list.pop_front(),
+                    _ => list.pop_back(),
+                };
+                bulk(&v.unwrap())
+            }
+        },
+        ("LRANGE", [_, start, stop]) => {
+            let (start, stop) = (int_arg(start)?, int_arg(stop)?);
+            match live(store, &key(0)) {
+                None => WireType::Array(vec![]),
+                Some(value) => {
+                    let list = expect!(value, Value::List);
+                    let r = range(list.len(), start, stop);
+                    WireType::Array(list.range(r).map(|v| bulk(v)).collect())
+                }
+            }
+        }
+        ("LLEN", [_]) => match live(store, &key(0)) {
+            None => int(0),
+            Some(value) => int(expect!(value, Value::List).len()),
+        },
+
+        // hashes
+        ("HSET", [_, fields @ ..]) if !fields.is_empty() && fields.len() % 2 == 0 => {
+            let hash = expect!(
+                create(store, &key(0), Value::Hash(BTreeMap::new())),
+                Value::Hash
+            );
+            let mut added = 0;
+            for pair in fields.chunks(2) {
+                if hash.insert(pair[0].to_vec(), pair[1].to_vec()).is_none() {
+                    added += 1;
+                }
+            }
+            int(added)
+        }
+        ("HGET", [_, field]) => match live(store, &key(0)) {
+            None => null(conn),
+            Some(value) => match expect!(value, Value::Hash).get(*field) {
+                Some(v) => bulk(v),
+                None => null(conn),
+            },
+        },
+        ("HDEL", [_, _, ..]) => match live(store, &key(0)) {
+            None => int(0),
+            Some(value) => {
+                let hash = expect!(value, Value::Hash);
+                int(args[1..]
+                    .iter()
+                    .filter(|f| hash.remove(**f).is_some())
+                    .count())
+            }
+        },
+        ("HGETALL", [_]) => {
+            let pairs = match live(store, &key(0)) {
+                None => vec![],
+                Some(value) => expect!(value, Value::Hash)
+                    .iter()
+                    .map(|(f, v)| (bulk(f), bulk(v)))
+                    .collect(),
+            };
+            map(conn, pairs)
+        }
+        ("HLEN", [_]) => match live(store, &key(0)) {
+            None => int(0),
+            Some(value) => int(expect!(value, Value::Hash).len()),
+        },
+
+        // sets
+        ("SADD", [_, _, ..]) => {
+            let set = expect!(
+                create(store, &key(0), Value::Set(BTreeSet::new())),
+                Value::Set
+            );
+            int(args[1..].iter().filter(|m| set.insert(m.to_vec())).count())
+        }
+        ("SREM", [_, _, ..]) => match live(store, &key(0)) {
+            None => int(0),
+            Some(value) => {
+                let set = expect!(value, Value::Set);
+                int(args[1..].iter().filter(|m| set.remove(**m)).count())
+            }
+        },
+        ("SMEMBERS", [_]) => {
+            let members = match live(store, &key(0)) {
+                None => vec![],
+                Some(value) => expect!(value, Value::Set).iter().map(|m| bulk(m)).collect(),
+            };
+            match conn.protocol {
+                Protocol::Resp3 => WireType::Set(members),
+                Protocol::Resp2 => WireType::Array(members),
+            }
+        }
+        ("SISMEMBER", [_, member]) => match live(store, &key(0)) {
+            None => int(0),
+            Some(value) => int(expect!(value, Value::Set).contains(*member) as usize),
+        },
+        ("SCARD", [_]) => match live(store, &key(0)) {
+            None => int(0),
+            Some(value) => int(expect!(value, Value::Set).len()),
+        },
+
+        // sorted sets
+        ("ZADD", [_, pairs @ ..]) if !pairs.is_empty() && pairs.len() % 2 == 0 => {
+            let scores = pairs
+                .chunks(2)
+                .map(|pair| float_arg(pair[0]))
+                .collect::<Result<Vec<_>, _>>()?;
+            let zset = expect!(
+                create(store, &key(0), Value::ZSet(BTreeMap::new())),
+                Value::ZSet
+            );
+            let mut added = 0;
+            for (score, pair) in scores.into_iter().zip(pairs.chunks(2)) {
+                if zset.insert(pair[1].to_vec(), score).is_none() {
+                    added += 1;
+                }
+            }
+            int(added)
+        }
+        ("ZINCRBY", [_, by, member]) => {
+            let by = float_arg(by)?;
+            let zset = expect!(
+                create(store, &key(0), Value::ZSet(BTreeMap::new())),
+                Value::ZSet
+            );
+            let score = zset.entry(member.to_vec()).or_insert(0.0);
+            *score += by;
+            let score = *score;
+            double(conn, score)
+        }
+        ("ZREM", [_, _, ..]) => match live(store, &key(0)) {
+            None => int(0),
+            Some(value) => {
+                let zset = expect!(value, Value::ZSet);
+                int(args[1..]
+                    .iter()
+                    .filter(|m| zset.remove(**m).is_some())
+                    .count())
+            }
+        },
+        ("ZSCORE", [_, member]) => match live(store, &key(0)) {
+            None => null(conn),
+            Some(value) => match expect!(value, Value::ZSet).get(*member) {
+                Some(score) => double(conn, *score),
+                None => null(conn),
+            },
+        },
+        ("ZRANGE", [_, start, stop, with @ ..]) if with.len() <= 1 => {
+            let scores = match with {
+                [] => false,
+                [w] if w.eq_ignore_ascii_case(b"WITHSCORES") => true,
+                _ => return Err("ERR syntax error".into()),
+            };
+            let (start, stop) = (int_arg(start)?, int_arg(stop)?);
+            let mut members: Vec<(Vec<u8>, f64)> = match live(store, &key(0)) {
+                None => vec![],
+                Some(value) => expect!(value, Value::ZSet)
+                    .iter()
+                    .map(|(m, s)| (m.clone(), *s))
+                    .collect(),
+            };
+            members.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+            let members = &members[range(members.len(), start, stop)];
+            WireType::Array(match (scores, conn.protocol) {
+                (false, _) => members.iter().map(|(m, _)| bulk(m)).collect(),
+                // resp3 pairs each member up with its score, resp2 runs them all together
+                (true, Protocol::Resp3) => members
+                    .iter()
+                    .map(|(m, s)| WireType::Array(vec![bulk(m), WireType::Double(*s)]))
+                    .collect(),
+                (true, Protocol::Resp2) => members
+                    .iter()
+                    .flat_map(|(m, s)| vec![bulk(m), double(conn, *s)])
+                    .collect(),
+            })
+        }
+        ("ZCARD", [_]) => match live(store, &key(0)) {
+            None => int(0),
+            Some(value) => int(expect!(value, Value::ZSet).len()),
+        },
+
+        _ if COMMANDS.contains(&name) => {
+            return Err(format!(
+                "ERR wrong number of arguments for '{}' command",
+                name.to_lowercase()
+            ))
+        }
+        _ => return Err(unknown(name)),
+    })
+}
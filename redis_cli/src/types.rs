@@ -1,43 +1,104 @@
-#[allow(dead_code)]
+use crate::protocol::WireType;
+use std::fmt;
+
 #[derive(Debug)]
-pub struct RedisError {
-    repr: String,
+pub enum RedisError {
+    // an error reply.  code is its first word, ERR or WRONGTYPE or the like, message the rest
+    Server { code: String, message: String },
+    Io(std::io::Error),
+    // bytes from the server that aren't resp
+    Protocol(String),
+    // well formed, but not what the command answers with
+    UnexpectedReply(WireType),
+    NotConnected,
+    // EXEC came back null, a WATCHed key changed and the transaction never ran
+    Aborted,
+    // a command that couldn't be sent as given, unbalanced quotes and the like
+    InvalidArgument(String),
 }
+
 impl RedisError {
-    pub fn new(repr: &str) -> Self {
-        RedisError {
-            repr: String::from(repr),
+    // the text of an error reply, split up into its parts
+    pub fn server(text: &str) -> Self {
+        let (code, message) = text.split_once(' ').unwrap_or((text, ""));
+        RedisError::Server {
+            code: code.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn protocol(msg: &str) -> Self {
+        RedisError::Protocol(msg.to_string())
+    }
+
+    // the server hung up
+    pub fn closed() -> Self {
+        RedisError::Io(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "connection closed",
+        ))
+    }
+
+    // the server's error code, None for errors that weren't replies
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            RedisError::Server { code, .. } => Some(code),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RedisError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RedisError::Server { code, message } if message.is_empty() => write!(f, "{}", code),
+            RedisError::Server { code, message } => write!(f, "{} {}", code, message),
+            RedisError::Io(e) => write!(f, "{}", e),
+            RedisError::Protocol(msg) => write!(f, "protocol error: {}", msg),
+            RedisError::UnexpectedReply(reply) => write!(f, "unexpected reply {:?}", reply),
+            RedisError::NotConnected => write!(f, "not connected"),
+            RedisError::Aborted => write!(f, "transaction aborted"),
+            RedisError::InvalidArgument(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for RedisError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RedisError::Io(e) => Some(e),
+            _ => None,
         }
     }
 }
 
 impl From<std::io::Error> for RedisError {
-    fn from(_: std::io::Error) -> Self {
-        RedisError::new("io_error")
+    fn from(e: std::io::Error) -> Self {
+        RedisError::Io(e)
     }
 }
 
 impl From<std::num::ParseIntError> for RedisError {
     fn from(_: std::num::ParseIntError) -> Self {
-        RedisError::new("parse_error")
+        RedisError::protocol("invalid number")
     }
 }
 
 impl From<std::char::ParseCharError> for RedisError {
     fn from(_: std::char::ParseCharError) -> Self {
-        RedisError::new("str_from_utf8_error")
+        RedisError::protocol("invalid utf-8")
     }
 }
 
 impl From<std::string::FromUtf8Error> for RedisError {
     fn from(_: std::string::FromUtf8Error) -> Self {
-        RedisError::new("string_from_utf8_error")
+        RedisError::protocol("invalid utf-8")
     }
 }
 
 impl From<std::str::Utf8Error> for RedisError {
     fn from(_: std::str::Utf8Error) -> Self {
-        RedisError::new("str_from_utf8_error")
+        RedisError::protocol("invalid utf-8")
     }
 }
 
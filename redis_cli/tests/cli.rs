@@ -0,0 +1,91 @@
+// the binary driven the way a shell would, pointed at a stand-in server
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use redis_cli::protocol::Protocol;
+use redis_cli::test_server;
+
+const REDIS_CLI: &str = env!("CARGO_BIN_EXE_redis_cli");
+
+fn port() -> String {
+    let addr = test_server::spawn(Protocol::Resp3);
+    addr.rsplit(':').next().unwrap().to_string()
+}
+
+fn redis_cli(port: &str, args: &[&str]) -> Output {
+    Command::new(REDIS_CLI)
+        .args(["-p", port])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(out: &Output) -> &str {
+    std::str::from_utf8(&out.stdout).unwrap()
+}
+
+#[test]
+fn one_shot_commands() {
+    let port = port();
+    let out = redis_cli(&port, &["set", "k", "a b"]);
+    assert_eq!(stdout(&out), "OK\n");
+    assert_eq!(out.status.code(), Some(0));
+
+    // output that isn't going to a terminal is raw unless asked otherwise
+    assert_eq!(stdout(&redis_cli(&port, &["get", "k"])), "a b\n");
+    assert_eq!(
+        stdout(&redis_cli(&port, &["--no-raw", "get", "k"])),
+        "\"a b\"\n"
+    );
+    assert_eq!(
+        stdout(&redis_cli(&port, &["--no-raw", "get", "nope"])),
+        "(nil)\n"
+    );
+
+    let out = redis_cli(&port, &["frob"]);
+    assert_eq!(stdout(&out), "ERR unknown command 'frob'\n");
+    assert_eq!(out.status.code(), Some(1));
+
+    // -n picks the database
+    redis_cli(&port, &["-n", "2", "set", "k", "two"]);
+    assert_eq!(stdout(&redis_cli(&port, &["-n", "2", "get", "k"])), "two\n");
+    assert_eq!(stdout(&redis_cli(&port, &["get", "k"])), "a b\n");
+    assert_eq!(
+        redis_cli(&port, &["-n", "99", "get", "k"]).status.code(),
+        Some(1)
+    );
+}
+
+#[test]
+fn commands_from_stdin() {
+    let port = port();
+    let mut child = Command::new(REDIS_CLI)
+        .args(["-p", &port, "--no-raw"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"set k \"x y\"\n\nget k\nget \"k\nincr\nquit\nget k\n")
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert_eq!(
+        stdout(&out),
+        "OK\n\"x y\"\nInvalid argument(s)\n(error) ERR wrong number of arguments for 'incr' command\n"
+    );
+}
+
+#[test]
+fn not_connected() {
+    // grab a free port, then let it go so nothing's listening there
+    let port = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port().to_string()
+    };
+    let out = redis_cli(&port, &["ping"]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stderr).starts_with("Could not connect to Redis at"));
+}
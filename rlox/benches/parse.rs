@@ -0,0 +1,105 @@
+// scan and parse a big generated script and report how long each took and how much the allocator
+// was asked for along the way.  run with `cargo bench --bench parse`
+use rlox::parser::*;
+use rlox::scanner::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+struct Counting;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const FUNCS: usize = 10000;
+const RUNS: u32 = 5;
+
+// a few thousand functions with nested blocks, loops and some arithmetic in each
+fn generate() -> String {
+    let mut src = String::new();
+    for i in 0..FUNCS {
+        src.push_str(&format!(
+            r#"
+fun f{i}(a, b) {{
+  var total = 0;
+  for (var j = 0; j < a; j = j + 1) {{
+    if (j > b and j < a or j == {i}) {{
+      total = total + (j * 2 - b) / 3;
+    }} else {{
+      total = total - 1;
+    }}
+  }}
+  while (total > 100) {{
+    total = total - {i};
+  }}
+  return total + "suffix {i}";
+}}
+print f{i}({i}, {i} + 1);
+"#,
+            i = i
+        ));
+    }
+    src
+}
+
+#[derive(Default)]
+struct Stats {
+    elapsed: Duration,
+    allocs: usize,
+    bytes: usize,
+}
+
+impl Stats {
+    fn measure<T>(&mut self, f: impl FnOnce() -> T) -> T {
+        let a = ALLOCS.load(Ordering::Relaxed);
+        let b = BYTES.load(Ordering::Relaxed);
+        let start = Instant::now();
+        let res = f();
+        self.elapsed += start.elapsed();
+        self.allocs += ALLOCS.load(Ordering::Relaxed) - a;
+        self.bytes += BYTES.load(Ordering::Relaxed) - b;
+        res
+    }
+
+    fn report(&self, what: &str) {
+        println!(
+            "{}: {:?}/iter, {} allocs/iter, {} KiB allocated/iter",
+            what,
+            self.elapsed / RUNS,
+            self.allocs / RUNS as usize,
+            self.bytes / RUNS as usize / 1024
+        );
+    }
+}
+
+fn main() {
+    let src = generate();
+    let mut scan = Stats::default();
+    let mut parse = Stats::default();
+    let mut ntoks = 0;
+    for _ in 0..RUNS {
+        let toks = scan.measure(|| Scanner::new(&src).scan_tokens());
+        let parsed = parse.measure(|| Parser::new(&toks).parse());
+        assert!(parsed.is_ok());
+        ntoks = toks.len();
+    }
+
+    println!("input: {} bytes, {} tokens", src.len(), ntoks);
+    scan.report("scan");
+    parse.report("parse");
+}
@@ -0,0 +1,109 @@
+// run a few scripts that shuffle strings and functions around a lot, reporting how long each took
+// and how much the allocator was asked for.  every variable read and argument pass copies a value,
+// so this is mostly measuring what that copy costs.  run with `cargo bench --bench values`
+use rlox::interpreter::*;
+use rlox::parser::*;
+use rlox::scanner::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+struct Counting;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const RUNS: u32 = 5;
+
+// builds up one long string, then keeps reading it and passing it around
+const STRINGS: &str = r#"
+var s = "";
+for (var i = 0; i < 2000; i = i + 1) {
+  s = s + "abcdefgh";
+}
+
+fun same(x) { return x; }
+var n = 0;
+for (var i = 0; i < 20000; i = i + 1) {
+  var t = s;
+  t = same(t);
+  if (t == s) n = n + 1;
+}
+print n;
+"#;
+
+// small functions and closures handed to other functions and called through them
+const FUNCTIONS: &str = r#"
+fun twice(f, x) { return f(f(x)); }
+fun compose(f, g) {
+  fun composed(x) { return g(f(x)); }
+  return composed;
+}
+fun inc(x) { return x + 1; }
+fun dbl(x) { return x * 2; }
+
+var total = 0;
+for (var i = 0; i < 20000; i = i + 1) {
+  var h = compose(inc, dbl);
+  total = total + twice(h, i) + twice(inc, i);
+}
+print total;
+"#;
+
+fn run(src: &str) -> Stats {
+    let toks = Scanner::new(src).scan_tokens();
+    let ast = Parser::new(&toks).parse().unwrap();
+
+    let mut stats = Stats::default();
+    for _ in 0..RUNS {
+        let mut ir = Interpreter::with_output(&ast, Box::new(io::sink()));
+        let a = ALLOCS.load(Ordering::Relaxed);
+        let b = BYTES.load(Ordering::Relaxed);
+        let start = Instant::now();
+        ir.interpret(&ast.root).unwrap();
+        stats.elapsed += start.elapsed();
+        stats.allocs += ALLOCS.load(Ordering::Relaxed) - a;
+        stats.bytes += BYTES.load(Ordering::Relaxed) - b;
+    }
+    stats
+}
+
+#[derive(Default)]
+struct Stats {
+    elapsed: Duration,
+    allocs: usize,
+    bytes: usize,
+}
+
+impl Stats {
+    fn report(&self, what: &str) {
+        println!(
+            "{}: {:?}/iter, {} allocs/iter, {} KiB allocated/iter",
+            what,
+            self.elapsed / RUNS,
+            self.allocs / RUNS as usize,
+            self.bytes / RUNS as usize / 1024
+        );
+    }
+}
+
+fn main() {
+    run(STRINGS).report("strings");
+    run(FUNCTIONS).report("functions");
+}
@@ -0,0 +1,106 @@
+use crate::expr::*;
+use crate::stmt::*;
+
+// nodes refer to each other by index into the arena instead of owning their children.  ids stay
+// valid for the life of the Ast, so anything that wants to hang extra info off a node (types,
+// resolved scopes, hit counts, ...) can just key a side table by them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ExprId(u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StmtId(u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FunId(u32);
+
+impl ExprId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl StmtId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl FunId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Ast<'src> {
+    exprs: Vec<Expr<'src>>,
+    stmts: Vec<Stmt<'src>>,
+    // line each statement starts on, indexed the same as stmts
+    lines: Vec<usize>,
+    funs: Vec<FunDecl<'src>>,
+    // top-level statements of the program, in order
+    pub root: Vec<StmtId>,
+}
+
+impl<'src> Ast<'src> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_expr(&mut self, expr: Expr<'src>) -> ExprId {
+        self.exprs.push(expr);
+        ExprId(self.exprs.len() as u32 - 1)
+    }
+
+    pub fn push_stmt(&mut self, stmt: Stmt<'src>, line: usize) -> StmtId {
+        self.stmts.push(stmt);
+        self.lines.push(line);
+        StmtId(self.stmts.len() as u32 - 1)
+    }
+
+    pub fn push_fun(&mut self, decl: FunDecl<'src>) -> FunId {
+        self.funs.push(decl);
+        FunId(self.funs.len() as u32 - 1)
+    }
+
+    pub fn expr(&self, id: ExprId) -> &Expr<'src> {
+        &self.exprs[id.index()]
+    }
+
+    pub fn stmt(&self, id: StmtId) -> &Stmt<'src> {
+        &self.stmts[id.index()]
+    }
+
+    pub fn expr_mut(&mut self, id: ExprId) -> &mut Expr<'src> {
+        &mut self.exprs[id.index()]
+    }
+
+    pub fn stmt_mut(&mut self, id: StmtId) -> &mut Stmt<'src> {
+        &mut self.stmts[id.index()]
+    }
+
+    pub fn fun_mut(&mut self, id: FunId) -> &mut FunDecl<'src> {
+        &mut self.funs[id.index()]
+    }
+
+    pub fn line(&self, id: StmtId) -> usize {
+        self.lines[id.index()]
+    }
+
+    // start line of every statement, in id order
+    pub fn lines(&self) -> &[usize] {
+        &self.lines
+    }
+
+    pub fn fun(&self, id: FunId) -> &FunDecl<'src> {
+        &self.funs[id.index()]
+    }
+
+    pub fn expr_count(&self) -> usize {
+        self.exprs.len()
+    }
+
+    pub fn stmt_count(&self) -> usize {
+        self.stmts.len()
+    }
+}
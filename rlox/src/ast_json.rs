@@ -0,0 +1,625 @@
+use crate::ast::*;
+use crate::bigint::*;
+use crate::expr::*;
+use crate::json::*;
+use crate::parser::MAX_TREE_DEPTH;
+use crate::r#type::*;
+use crate::stmt::*;
+use crate::token::*;
+use crate::token_type::*;
+use crate::value::*;
+
+// the Ast as a nested json tree rather than the flat arena it is in memory, so outside tools
+// don't need to know anything about node ids.  loading rebuilds the arena in the same order the
+// parser would have filled it
+
+#[derive(Debug, PartialEq)]
+pub struct LoadError {
+    pub msg: String,
+}
+
+impl LoadError {
+    fn new(msg: &str) -> Self {
+        Self {
+            msg: msg.to_string(),
+        }
+    }
+}
+
+type LoadResult<T> = Result<T, LoadError>;
+
+pub fn dump(ast: &Ast) -> Json {
+    let stmts = ast.root.iter().map(|s| dump_stmt(ast, *s)).collect();
+    Json::object(vec![("stmts", Json::Array(stmts))])
+}
+
+fn dump_token(tok: &Token) -> Json {
+    Json::object(vec![
+        ("type", Json::str(tok.ttype.name())),
+        ("lexeme", Json::str(tok.lexeme)),
+        ("line", Json::Number(tok.line as f64)),
+    ])
+}
+
+fn dump_type(ty: &Option<Type>) -> Json {
+    match ty {
+        Some(ty) => Json::String(ty.to_string()),
+        None => Json::Null,
+    }
+}
+
+fn dump_opt_expr(ast: &Ast, e: Option<ExprId>) -> Json {
+    match e {
+        Some(e) => dump_expr(ast, e),
+        None => Json::Null,
+    }
+}
+
+fn dump_stmts(ast: &Ast, stmts: &[StmtId]) -> Json {
+    Json::Array(stmts.iter().map(|s| dump_stmt(ast, *s)).collect())
+}
+
+fn dump_fun(ast: &Ast, fun: FunId) -> Json {
+    let decl = ast.fun(fun);
+    Json::object(vec![
+        ("name", dump_token(&decl.name)),
+        (
+            "params",
+            Json::Array(decl.params.iter().map(dump_token).collect()),
+        ),
+        (
+            "param_types",
+            Json::Array(decl.sig.params.iter().map(dump_type).collect()),
+        ),
+        ("ret", dump_type(&decl.sig.ret)),
+        ("generator", Json::Bool(decl.generator)),
+        ("body", dump_stmts(ast, &decl.body)),
+    ])
+}
+
+fn dump_stmt(ast: &Ast, id: StmtId) -> Json {
+    let mut fields = match ast.stmt(id) {
+        Stmt::Expr(e) => vec![("stmt", Json::str("Expr")), ("expr", dump_expr(ast, *e))],
+        Stmt::Print(e) => vec![("stmt", Json::str("Print")), ("expr", dump_expr(ast, *e))],
+        Stmt::If(cond, then, els) => vec![
+            ("stmt", Json::str("If")),
+            ("cond", dump_expr(ast, *cond)),
+            ("then", dump_stmt(ast, *then)),
+            (
+                "else",
+                match els {
+                    Some(els) => dump_stmt(ast, *els),
+                    None => Json::Null,
+                },
+            ),
+        ],
+        Stmt::Block(stmts) => vec![
+            ("stmt", Json::str("Block")),
+            ("body", dump_stmts(ast, stmts)),
+        ],
+        Stmt::Var(name, ty, init) => vec![
+            ("stmt", Json::str("Var")),
+            ("name", dump_token(name)),
+            ("type", dump_type(ty)),
+            ("init", dump_opt_expr(ast, *init)),
+        ],
+        Stmt::While(cond, body) => vec![
+            ("stmt", Json::str("While")),
+            ("cond", dump_expr(ast, *cond)),
+            ("body", dump_stmt(ast, *body)),
+        ],
+        Stmt::Loop(body) => vec![("stmt", Json::str("Loop")), ("body", dump_stmt(ast, *body))],
+        Stmt::Function(fun) => vec![
+            ("stmt", Json::str("Function")),
+            ("fun", dump_fun(ast, *fun)),
+        ],
+        Stmt::Return(keyword, val) => vec![
+            ("stmt", Json::str("Return")),
+            ("keyword", dump_token(keyword)),
+            ("value", dump_opt_expr(ast, *val)),
+        ],
+        Stmt::Throw(keyword, val) => vec![
+            ("stmt", Json::str("Throw")),
+            ("keyword", dump_token(keyword)),
+            ("value", dump_expr(ast, *val)),
+        ],
+        Stmt::Yield(keyword, val) => vec![
+            ("stmt", Json::str("Yield")),
+            ("keyword", dump_token(keyword)),
+            ("value", dump_opt_expr(ast, *val)),
+        ],
+        Stmt::ForIn(name, iter, body) => vec![
+            ("stmt", Json::str("ForIn")),
+            ("name", dump_token(name)),
+            ("iter", dump_expr(ast, *iter)),
+            ("body", dump_stmt(ast, *body)),
+        ],
+        Stmt::Try(body, catch, finally) => vec![
+            ("stmt", Json::str("Try")),
+            ("body", dump_stmt(ast, *body)),
+            (
+                "catch",
+                match catch {
+                    Some((name, block)) => Json::object(vec![
+                        ("name", dump_token(name)),
+                        ("body", dump_stmt(ast, *block)),
+                    ]),
+                    None => Json::Null,
+                },
+            ),
+            (
+                "finally",
+                match finally {
+                    Some(block) => dump_stmt(ast, *block),
+                    None => Json::Null,
+                },
+            ),
+        ],
+    };
+    fields.insert(1, ("line", Json::Number(ast.line(id) as f64)));
+    Json::object(fields)
+}
+
+fn dump_expr(ast: &Ast, id: ExprId) -> Json {
+    let fields = match ast.expr(id) {
+        Expr::Assign { name, value } => vec![
+            ("expr", Json::str("Assign")),
+            ("name", dump_token(name)),
+            ("value", dump_expr(ast, *value)),
+        ],
+        Expr::Binary { op, left, right } => vec![
+            ("expr", Json::str("Binary")),
+            ("op", dump_token(op)),
+            ("left", dump_expr(ast, *left)),
+            ("right", dump_expr(ast, *right)),
+        ],
+        Expr::Call {
+            callee,
+            paren,
+            args,
+        } => vec![
+            ("expr", Json::str("Call")),
+            ("callee", dump_expr(ast, *callee)),
+            ("paren", dump_token(paren)),
+            (
+                "args",
+                Json::Array(args.iter().map(|a| dump_expr(ast, *a)).collect()),
+            ),
+        ],
+        Expr::Get { object, name } => vec![
+            ("expr", Json::str("Get")),
+            ("object", dump_expr(ast, *object)),
+            ("name", dump_token(name)),
+        ],
+        Expr::Grouping { expr } => vec![
+            ("expr", Json::str("Grouping")),
+            ("inner", dump_expr(ast, *expr)),
+        ],
+        Expr::Literal { token } => {
+            vec![("expr", Json::str("Literal")), ("token", dump_token(token))]
+        }
+        Expr::Logical { op, left, right } => vec![
+            ("expr", Json::str("Logical")),
+            ("op", dump_token(op)),
+            ("left", dump_expr(ast, *left)),
+            ("right", dump_expr(ast, *right)),
+        ],
+        Expr::Unary { op, right } => vec![
+            ("expr", Json::str("Unary")),
+            ("op", dump_token(op)),
+            ("right", dump_expr(ast, *right)),
+        ],
+        Expr::Variable { name } => {
+            vec![("expr", Json::str("Variable")), ("name", dump_token(name))]
+        }
+        Expr::Function(fun) => vec![
+            ("expr", Json::str("Function")),
+            ("fun", dump_fun(ast, *fun)),
+        ],
+        Expr::Constant { value, line } => vec![
+            ("expr", Json::str("Constant")),
+            ("line", Json::Number(*line as f64)),
+            (
+                "value",
+                match value {
+                    Constant::Nil => Json::Null,
+                    Constant::Bool(b) => Json::Bool(*b),
+                    Constant::Number(n) => Json::Number(*n),
+                    // json numbers are floats, integers go out as their digits
+                    Constant::Int(n) => Json::object(vec![("int", Json::String(n.to_string()))]),
+                    Constant::BigInt(n) => Json::object(vec![("int", Json::String(n.to_string()))]),
+                    Constant::String(s) => Json::str(s),
+                },
+            ),
+        ],
+    };
+    Json::object(fields)
+}
+
+// lexemes in the loaded Ast point into `doc`, same as they'd point into the source text after a
+// regular parse
+pub fn load(doc: &Json) -> LoadResult<Ast<'_>> {
+    let mut loader = Loader {
+        ast: Ast::new(),
+        depth: 0,
+    };
+    let mut root = vec![];
+    for stmt in array(field(doc, "stmts")?)? {
+        root.push(loader.stmt(stmt)?);
+    }
+    let mut ast = loader.ast;
+    ast.root = root;
+    Ok(ast)
+}
+
+fn field<'a>(j: &'a Json, key: &str) -> LoadResult<&'a Json> {
+    j.get(key)
+        .ok_or_else(|| LoadError::new(&format!("missing field '{}'", key)))
+}
+
+fn string(j: &Json) -> LoadResult<&str> {
+    j.as_str()
+        .ok_or_else(|| LoadError::new(&format!("expected string, got {:?}", j)))
+}
+
+fn array(j: &Json) -> LoadResult<&[Json]> {
+    j.as_array()
+        .ok_or_else(|| LoadError::new(&format!("expected array, got {:?}", j)))
+}
+
+fn number(j: &Json) -> LoadResult<usize> {
+    j.as_usize()
+        .ok_or_else(|| LoadError::new(&format!("expected line number, got {:?}", j)))
+}
+
+fn boolean(j: &Json) -> LoadResult<bool> {
+    match j {
+        Json::Bool(b) => Ok(*b),
+        _ => Err(LoadError::new(&format!("expected boolean, got {:?}", j))),
+    }
+}
+
+fn optional(j: &Json) -> Option<&Json> {
+    match j.is_null() {
+        true => None,
+        false => Some(j),
+    }
+}
+
+fn token(j: &Json) -> LoadResult<Token<'_>> {
+    let name = string(field(j, "type")?)?;
+    let lexeme = string(field(j, "lexeme")?)?;
+    let line = number(field(j, "line")?)?;
+    match TokenType::from_name(name, lexeme) {
+        Some(ttype) => Ok(Token::new(ttype, lexeme, line)),
+        None => Err(LoadError::new(&format!(
+            "bad token '{}' of type {}",
+            lexeme, name
+        ))),
+    }
+}
+
+fn ty(j: &Json) -> LoadResult<Option<Type>> {
+    match optional(j) {
+        None => Ok(None),
+        Some(j) => {
+            let name = string(j)?;
+            match Type::from_name(name) {
+                Some(ty) => Ok(Some(ty)),
+                None => Err(LoadError::new(&format!("unknown type '{}'", name))),
+            }
+        }
+    }
+}
+
+struct Loader<'a> {
+    ast: Ast<'a>,
+    // how many statements and expressions deep we are.  a hand written file skips the parser,
+    // so it's held to the parser's limit here instead
+    depth: usize,
+}
+
+impl<'a> Loader<'a> {
+    fn stmts(&mut self, j: &'a Json) -> LoadResult<Vec<StmtId>> {
+        array(j)?.iter().map(|s| self.stmt(s)).collect()
+    }
+
+    fn opt_expr(&mut self, j: &'a Json) -> LoadResult<Option<ExprId>> {
+        optional(j).map(|e| self.expr(e)).transpose()
+    }
+
+    fn opt_stmt(&mut self, j: &'a Json) -> LoadResult<Option<StmtId>> {
+        optional(j).map(|s| self.stmt(s)).transpose()
+    }
+
+    fn fun(&mut self, j: &'a Json) -> LoadResult<FunId> {
+        let name = token(field(j, "name")?)?;
+        let params = array(field(j, "params")?)?
+            .iter()
+            .map(token)
+            .collect::<LoadResult<Vec<_>>>()?;
+        let param_types = array(field(j, "param_types")?)?
+            .iter()
+            .map(ty)
+            .collect::<LoadResult<Vec<_>>>()?;
+        if param_types.len() != params.len() {
+            return Err(LoadError::new("param_types doesn't match params"));
+        }
+        let ret = ty(field(j, "ret")?)?;
+        let generator = boolean(field(j, "generator")?)?;
+        let body = self.stmts(field(j, "body")?)?;
+        Ok(self.ast.push_fun(FunDecl {
+            name,
+            params,
+            body,
+            sig: Signature {
+                params: param_types,
+                ret,
+            },
+            generator,
+        }))
+    }
+
+    fn nested<T>(
+        &mut self,
+        j: &'a Json,
+        load: fn(&mut Self, &'a Json) -> LoadResult<T>,
+    ) -> LoadResult<T> {
+        if self.depth >= MAX_TREE_DEPTH {
+            return Err(LoadError::new("too much nesting"));
+        }
+        self.depth += 1;
+        let res = load(self, j);
+        self.depth -= 1;
+        res
+    }
+
+    fn stmt(&mut self, j: &'a Json) -> LoadResult<StmtId> {
+        self.nested(j, Self::any_stmt)
+    }
+
+    fn expr(&mut self, j: &'a Json) -> LoadResult<ExprId> {
+        self.nested(j, Self::any_expr)
+    }
+
+    fn any_stmt(&mut self, j: &'a Json) -> LoadResult<StmtId> {
+        let line = number(field(j, "line")?)?;
+        let stmt = match string(field(j, "stmt")?)? {
+            "Expr" => Stmt::Expr(self.expr(field(j, "expr")?)?),
+            "Print" => Stmt::Print(self.expr(field(j, "expr")?)?),
+            "If" => {
+                let cond = self.expr(field(j, "cond")?)?;
+                let then = self.stmt(field(j, "then")?)?;
+                let els = self.opt_stmt(field(j, "else")?)?;
+                Stmt::If(cond, then, els)
+            }
+            "Block" => Stmt::Block(self.stmts(field(j, "body")?)?),
+            "Var" => {
+                let name = token(field(j, "name")?)?;
+                let ty = ty(field(j, "type")?)?;
+                let init = self.opt_expr(field(j, "init")?)?;
+                Stmt::Var(name, ty, init)
+            }
+            "While" => {
+                let cond = self.expr(field(j, "cond")?)?;
+                let body = self.stmt(field(j, "body")?)?;
+                Stmt::While(cond, body)
+            }
+            "Loop" => Stmt::Loop(self.stmt(field(j, "body")?)?),
+            "Function" => Stmt::Function(self.fun(field(j, "fun")?)?),
+            "Return" => {
+                let keyword = token(field(j, "keyword")?)?;
+                Stmt::Return(keyword, self.opt_expr(field(j, "value")?)?)
+            }
+            "Throw" => {
+                let keyword = token(field(j, "keyword")?)?;
+                Stmt::Throw(keyword, self.expr(field(j, "value")?)?)
+            }
+            "Yield" => {
+                let keyword = token(field(j, "keyword")?)?;
+                Stmt::Yield(keyword, self.opt_expr(field(j, "value")?)?)
+            }
+            "ForIn" => {
+                let name = token(field(j, "name")?)?;
+                let iter = self.expr(field(j, "iter")?)?;
+                Stmt::ForIn(name, iter, self.stmt(field(j, "body")?)?)
+            }
+            "Try" => {
+                let body = self.stmt(field(j, "body")?)?;
+                let catch = match optional(field(j, "catch")?) {
+                    Some(c) => Some((token(field(c, "name")?)?, self.stmt(field(c, "body")?)?)),
+                    None => None,
+                };
+                let finally = self.opt_stmt(field(j, "finally")?)?;
+                Stmt::Try(body, catch, finally)
+            }
+            other => return Err(LoadError::new(&format!("unknown statement '{}'", other))),
+        };
+        Ok(self.ast.push_stmt(stmt, line))
+    }
+
+    fn any_expr(&mut self, j: &'a Json) -> LoadResult<ExprId> {
+        let expr = match string(field(j, "expr")?)? {
+            "Assign" => Expr::Assign {
+                name: token(field(j, "name")?)?,
+                value: self.expr(field(j, "value")?)?,
+            },
+            "Binary" => Expr::Binary {
+                op: token(field(j, "op")?)?,
+                left: self.expr(field(j, "left")?)?,
+                right: self.expr(field(j, "right")?)?,
+            },
+            "Call" => {
+                let callee = self.expr(field(j, "callee")?)?;
+                let paren = token(field(j, "paren")?)?;
+                let args = array(field(j, "args")?)?
+                    .iter()
+                    .map(|a| self.expr(a))
+                    .collect::<LoadResult<Vec<_>>>()?;
+                Expr::Call {
+                    callee,
+                    paren,
+                    args,
+                }
+            }
+            "Get" => Expr::Get {
+                object: self.expr(field(j, "object")?)?,
+                name: token(field(j, "name")?)?,
+            },
+            "Grouping" => Expr::Grouping {
+                expr: self.expr(field(j, "inner")?)?,
+            },
+            "Literal" => Expr::Literal {
+                token: token(field(j, "token")?)?,
+            },
+            "Logical" => Expr::Logical {
+                op: token(field(j, "op")?)?,
+                left: self.expr(field(j, "left")?)?,
+                right: self.expr(field(j, "right")?)?,
+            },
+            "Unary" => Expr::Unary {
+                op: token(field(j, "op")?)?,
+                right: self.expr(field(j, "right")?)?,
+            },
+            "Variable" => Expr::Variable {
+                name: token(field(j, "name")?)?,
+            },
+            "Function" => Expr::Function(self.fun(field(j, "fun")?)?),
+            "Constant" => Expr::Constant {
+                line: number(field(j, "line")?)?,
+                value: match field(j, "value")? {
+                    Json::Null => Constant::Nil,
+                    Json::Bool(b) => Constant::Bool(*b),
+                    Json::Number(n) => Constant::Number(*n),
+                    Json::String(s) => Constant::String(s.as_str().into()),
+                    obj @ Json::Object(_) => {
+                        let digits = string(field(obj, "int")?)?;
+                        BigInt::parse(digits)
+                            .and_then(|n| Constant::from_value(&Value::from_bigint(n)))
+                            .ok_or_else(|| {
+                                LoadError::new(&format!("bad integer constant '{}'", digits))
+                            })?
+                    }
+                    other => return Err(LoadError::new(&format!("bad constant {:?}", other))),
+                },
+            },
+            other => return Err(LoadError::new(&format!("unknown expression '{}'", other))),
+        };
+        Ok(self.ast.push_expr(expr))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::*;
+    use crate::scanner::*;
+
+    fn parse(src: &str) -> Json {
+        let toks = Scanner::new(src).scan_tokens();
+        dump(&Parser::new(&toks).parse().unwrap())
+    }
+
+    #[test]
+    fn snapshot() {
+        let json = parse("var x: Number = -(1 + 2);\nprint x;");
+        assert_eq!(
+            json.pretty(),
+            r#"{
+  "stmts": [
+    {
+      "stmt": "Var",
+      "line": 1,
+      "name": {
+        "type": "Identifier",
+        "lexeme": "x",
+        "line": 1
+      },
+      "type": "Number",
+      "init": {
+        "expr": "Unary",
+        "op": {
+          "type": "Minus",
+          "lexeme": "-",
+          "line": 1
+        },
+        "right": {
+          "expr": "Grouping",
+          "inner": {
+            "expr": "Binary",
+            "op": {
+              "type": "Plus",
+              "lexeme": "+",
+              "line": 1
+            },
+            "left": {
+              "expr": "Literal",
+              "token": {
+                "type": "Integer",
+                "lexeme": "1",
+                "line": 1
+              }
+            },
+            "right": {
+              "expr": "Literal",
+              "token": {
+                "type": "Integer",
+                "lexeme": "2",
+                "line": 1
+              }
+            }
+          }
+        }
+      }
+    },
+    {
+      "stmt": "Print",
+      "line": 2,
+      "expr": {
+        "expr": "Variable",
+        "name": {
+          "type": "Identifier",
+          "lexeme": "x",
+          "line": 2
+        }
+      }
+    }
+  ]
+}
+"#
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let src = r#"
+fun add(a: Number, b): Number { return a + b; }
+var f = fun (s) { return s + "!"; };
+for (var i = 0; i < 3; i = i + 1) {
+  if (i == 1 and true) print add(i, 2); else print f("x");
+}
+try { throw "t"; } catch (e) { print e; } finally { print nil; }
+while (false) { print e.message; }"#;
+        let json = parse(src);
+        let reparsed = Json::parse(&json.pretty()).unwrap();
+        let ast = load(&reparsed).unwrap();
+        assert_eq!(dump(&ast), json);
+        assert_eq!(ast.line(ast.root[2]), 4);
+    }
+
+    #[test]
+    fn bad_input() {
+        let doc = Json::parse(r#"{"stmts": [{"stmt": "Print", "line": 1}]}"#).unwrap();
+        assert_eq!(load(&doc).unwrap_err().msg, "missing field 'expr'");
+
+        let doc = Json::parse(
+            r#"{"stmts": [{"stmt": "Expr", "line": 1, "expr": {"expr": "Literal",
+                "token": {"type": "Number", "lexeme": "abc", "line": 1}}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            load(&doc).unwrap_err().msg,
+            "bad token 'abc' of type Number"
+        );
+    }
+}
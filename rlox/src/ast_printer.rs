@@ -1,19 +1,19 @@
+use crate::ast::*;
 use crate::expr::*;
 use crate::stmt::*;
 
 pub struct AstPrinter {}
 
 impl AstPrinter {
-    pub fn serialize(e: &Expr) -> String {
-        format!("{}", Self::parenthesize(e))
+    pub fn serialize(ast: &Ast, e: ExprId) -> String {
+        Self::parenthesize(ast, e)
     }
 
-    pub fn serialize_stmts(stmts: &Vec<Stmt>) -> String {
+    pub fn serialize_stmts(ast: &Ast, stmts: &[StmtId]) -> String {
         let mut buf = String::new();
         for stmt in stmts {
-            println!("{}", stmt);
-            match stmt {
-                Stmt::Expr(expr) => buf.push_str(&Self::parenthesize(expr)),
+            match ast.stmt(*stmt) {
+                Stmt::Expr(expr) => buf.push_str(&Self::parenthesize(ast, *expr)),
                 Stmt::Print(_) => {}
                 _ => {}
             }
@@ -21,33 +21,34 @@ impl AstPrinter {
         buf
     }
 
-    fn parenthesize(e: &Expr) -> String {
+    fn parenthesize(ast: &Ast, e: ExprId) -> String {
         let mut buf = String::new();
-        match e.etype {
-            ExprType::Binary => {
-                buf.push_str(&format!("({}", e.token.lexeme));
-                for c in &e.children {
-                    buf.push_str(&format!(" {}", &Self::parenthesize(&c)));
+        match ast.expr(e) {
+            Expr::Binary { op, left, right } => {
+                buf.push_str(&format!("({}", op.lexeme));
+                for c in &[left, right] {
+                    buf.push_str(&format!(" {}", &Self::parenthesize(ast, **c)));
                 }
-                buf.push_str(")");
+                buf.push(')');
             }
-            ExprType::Literal => {
-                buf.push_str(&format!("{}", e.token.lexeme));
+            Expr::Literal { token } => {
+                buf.push_str(token.lexeme);
             }
-            ExprType::Unary => {
-                buf.push_str(&format!("({}", e.token.lexeme));
-                buf.push_str(&format!(" {})", Self::parenthesize(&e.children[0])));
+            Expr::Unary { op, right } => {
+                buf.push_str(&format!("({}", op.lexeme));
+                buf.push_str(&format!(" {})", Self::parenthesize(ast, *right)));
             }
-            ExprType::Grouping => {
-                buf.push_str(&format!("(group"));
-                for c in &e.children {
-                    buf.push_str(&format!(" {}", c.token.lexeme));
-                }
-                buf.push_str(&format!(")"));
+            Expr::Grouping { expr } => {
+                buf.push_str(&format!("(group {})", Self::parenthesize(ast, *expr)));
             }
-            ExprType::Variable => {
-                buf.push_str(&format!("{}", e.token.lexeme));
+            Expr::Variable { name } => {
+                buf.push_str(name.lexeme);
             }
+            Expr::Constant { value, .. } => match value {
+                Constant::String(s) => buf.push_str(&format!("{:?}", s)),
+                Constant::Nil => buf.push_str("nil"),
+                _ => buf.push_str(&value.to_value().to_string()),
+            },
             _ => {} //  todo!()
         }
 
@@ -61,27 +62,36 @@ mod test {
     use crate::token::*;
     use crate::token_type::*;
 
+    fn literal<'src>(ast: &mut Ast<'src>, n: f64, lexeme: &'src str) -> ExprId {
+        ast.push_expr(Expr::Literal {
+            token: Token::new(TokenType::Number(n), lexeme, 1),
+        })
+    }
+
     #[test]
     fn it_prints() {
-        let e = Expr::new_binary(
-            Token::new(TokenType::Star, "*", 1),
-            Expr::new_literal(Token::new(TokenType::Number(1.0), "1", 1)),
-            Expr::new_literal(Token::new(TokenType::Number(2.0), "2", 1)),
-        );
-        assert_eq!(AstPrinter::serialize(&e), "(* 1 2)");
+        let mut ast = Ast::new();
+        let left = literal(&mut ast, 1.0, "1");
+        let right = literal(&mut ast, 2.0, "2");
+        let e = ast.push_expr(Expr::Binary {
+            op: Token::new(TokenType::Star, "*", 1),
+            left,
+            right,
+        });
+        assert_eq!(AstPrinter::serialize(&ast, e), "(* 1 2)");
 
-        let e = Expr::new_binary(
-            Token::new(TokenType::Star, "*", 1),
-            Expr::new_unary(
-                Token::new(TokenType::Minus, "-", 1),
-                Expr::new_literal(Token::new(TokenType::Number(123.0), "123", 1)),
-            ),
-            Expr::new_grouping(Expr::new_literal(Token::new(
-                TokenType::Number(45.67),
-                "45.67",
-                1,
-            ))),
-        );
-        assert_eq!(AstPrinter::serialize(&e), "(* (- 123) (group 45.67))");
+        let n = literal(&mut ast, 123.0, "123");
+        let left = ast.push_expr(Expr::Unary {
+            op: Token::new(TokenType::Minus, "-", 1),
+            right: n,
+        });
+        let n = literal(&mut ast, 45.67, "45.67");
+        let right = ast.push_expr(Expr::Grouping { expr: n });
+        let e = ast.push_expr(Expr::Binary {
+            op: Token::new(TokenType::Star, "*", 1),
+            left,
+            right,
+        });
+        assert_eq!(AstPrinter::serialize(&ast, e), "(* (- 123) (group 45.67))");
     }
 }
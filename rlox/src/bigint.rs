@@ -0,0 +1,362 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+
+// integers that went past i64.  sign plus magnitude, the magnitude in base 10^9 limbs with the
+// least significant first, which keeps printing and parsing trivial at the cost of a bit of
+// speed.  always normalized: no high zero limbs, and zero is never negative
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BigInt {
+    neg: bool,
+    mag: Vec<u32>,
+}
+
+const BASE: u64 = 1_000_000_000;
+const BASE_DIGITS: usize = 9;
+
+impl BigInt {
+    pub fn zero() -> Self {
+        Self {
+            neg: false,
+            mag: vec![],
+        }
+    }
+
+    pub fn from_i64(n: i64) -> Self {
+        let mut mag = vec![];
+        // unsigned_abs so i64::MIN doesn't overflow
+        let mut u = n.unsigned_abs();
+        while u > 0 {
+            mag.push((u % BASE) as u32);
+            u /= BASE;
+        }
+        Self { neg: n < 0, mag }
+    }
+
+    // decimal digits with an optional leading '-'
+    pub fn parse(s: &str) -> Option<Self> {
+        let (neg, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let mut mag = vec![];
+        let mut end = digits.len();
+        while end > 0 {
+            let start = end.saturating_sub(BASE_DIGITS);
+            mag.push(digits[start..end].parse().unwrap());
+            end = start;
+        }
+        Some(Self { neg, mag }.normalized())
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        let mut u: u64 = 0;
+        for limb in self.mag.iter().rev() {
+            u = u.checked_mul(BASE)?.checked_add(*limb as u64)?;
+        }
+        if self.neg {
+            0i64.checked_sub_unsigned(u)
+        } else {
+            i64::try_from(u).ok()
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        // going through the digits gets correct rounding for free
+        self.to_string().parse().unwrap()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.neg
+    }
+
+    // roughly how much memory the digits take, for Meter::alloc
+    pub fn size(&self) -> usize {
+        self.mag.len() * 4
+    }
+
+    pub fn neg(&self) -> Self {
+        Self {
+            neg: !self.neg,
+            mag: self.mag.clone(),
+        }
+        .normalized()
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        if self.neg == other.neg {
+            return Self {
+                neg: self.neg,
+                mag: add_mag(&self.mag, &other.mag),
+            };
+        }
+        // different signs: take the smaller magnitude off the larger one
+        match cmp_mag(&self.mag, &other.mag) {
+            Ordering::Less => Self {
+                neg: other.neg,
+                mag: sub_mag(&other.mag, &self.mag),
+            }
+            .normalized(),
+            _ => Self {
+                neg: self.neg,
+                mag: sub_mag(&self.mag, &other.mag),
+            }
+            .normalized(),
+        }
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        let mut out = vec![0u64; self.mag.len() + other.mag.len()];
+        for (i, a) in self.mag.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in other.mag.iter().enumerate() {
+                let cur = out[i + j] + *a as u64 * *b as u64 + carry;
+                out[i + j] = cur % BASE;
+                carry = cur / BASE;
+            }
+            out[i + other.mag.len()] += carry;
+        }
+        Self {
+            neg: self.neg != other.neg,
+            mag: out.into_iter().map(|l| l as u32).collect(),
+        }
+        .normalized()
+    }
+
+    // quotient rounded toward negative infinity and the remainder that goes with it, so the
+    // remainder always has the divisor's sign.  None for division by zero
+    pub fn div_mod_floor(&self, other: &Self) -> Option<(Self, Self)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (q, r) = div_mag(&self.mag, &other.mag);
+        let mut q = Self {
+            neg: self.neg != other.neg,
+            mag: q,
+        }
+        .normalized();
+        let mut r = Self {
+            neg: self.neg,
+            mag: r,
+        }
+        .normalized();
+        if !r.is_zero() && r.neg != other.neg {
+            q = q.sub(&Self::from_i64(1));
+            r = r.add(other);
+        }
+        Some((q, r))
+    }
+
+    fn normalized(mut self) -> Self {
+        while self.mag.last() == Some(&0) {
+            self.mag.pop();
+        }
+        if self.mag.is_empty() {
+            self.neg = false;
+        }
+        self
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.neg, other.neg) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.mag, &other.mag),
+            (true, true) => cmp_mag(&other.mag, &self.mag),
+        }
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut limbs = self.mag.iter().rev();
+        match limbs.next() {
+            None => return write!(f, "0"),
+            Some(top) => write!(f, "{}{}", if self.neg { "-" } else { "" }, top)?,
+        }
+        for limb in limbs {
+            write!(f, "{:09}", limb)?;
+        }
+        Ok(())
+    }
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let cur = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        out.push((cur % BASE) as u32);
+        carry = cur / BASE;
+    }
+    if carry > 0 {
+        out.push(carry as u32);
+    }
+    out
+}
+
+// a - b, with a at least as big as b
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = a.to_vec();
+    let mut borrow = 0i64;
+    for (i, limb) in out.iter_mut().enumerate() {
+        let mut cur = *limb as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if cur < 0 {
+            cur += BASE as i64;
+            borrow = 1;
+        }
+        *limb = cur as u32;
+    }
+    out
+}
+
+fn mul_small(a: &[u32], m: u64) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u64;
+    for limb in a {
+        let cur = *limb as u64 * m + carry;
+        out.push((cur % BASE) as u32);
+        carry = cur / BASE;
+    }
+    out.push(carry as u32);
+    out
+}
+
+// truncating division of magnitudes, schoolbook style.  both sides get scaled first so the top
+// limb of the divisor is at least BASE / 2, which keeps each guessed quotient limb within two of
+// the real one (knuth's algorithm D)
+fn div_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_mag(a, b) == Ordering::Less {
+        return (vec![], a.to_vec());
+    }
+
+    let scale = BASE / (*b.last().unwrap() as u64 + 1);
+    let mut rem = mul_small(a, scale);
+    let mut div = mul_small(b, scale);
+    while div.last() == Some(&0) {
+        div.pop();
+    }
+    let n = div.len();
+    if rem.len() < a.len() + 1 {
+        rem.push(0);
+    }
+
+    let top = *div.last().unwrap() as u64;
+    let mut q = vec![0u32; rem.len() - n];
+    for j in (0..q.len()).rev() {
+        let hi = rem[j + n] as u64 * BASE + rem[j + n - 1] as u64;
+        let mut guess = (hi / top).min(BASE - 1);
+        loop {
+            let prod = mul_small(&div, guess);
+            let window = &rem[j..=j + n];
+            if cmp_mag(&trimmed(window), &trimmed(&prod)) != Ordering::Less {
+                let diff = sub_mag(window, &prod);
+                rem[j..=j + n].copy_from_slice(&diff);
+                break;
+            }
+            guess -= 1;
+        }
+        q[j] = guess as u32;
+    }
+
+    // undo the scaling on what's left over
+    let mut r = vec![0u32; n];
+    let mut carry = 0u64;
+    for i in (0..n).rev() {
+        let cur = carry * BASE + rem[i] as u64;
+        r[i] = (cur / scale) as u32;
+        carry = cur % scale;
+    }
+    (q, r)
+}
+
+fn trimmed(limbs: &[u32]) -> Vec<u32> {
+    let mut v = limbs.to_vec();
+    while v.last() == Some(&0) {
+        v.pop();
+    }
+    v
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn big(s: &str) -> BigInt {
+        BigInt::parse(s).unwrap()
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = big("123456789012345678901234567890");
+        let b = big("-987654321098765432109876543210");
+        assert_eq!(a.add(&b).to_string(), "-864197532086419753208641975320");
+        assert_eq!(a.sub(&b).to_string(), "1111111110111111111011111111100");
+        assert_eq!(
+            a.mul(&b).to_string(),
+            "-121932631137021795226185032733622923332237463801111263526900"
+        );
+        assert_eq!(a.sub(&a), BigInt::zero());
+        assert_eq!(big("-0").to_string(), "0");
+
+        let max = BigInt::from_i64(i64::MAX);
+        assert_eq!(max.to_i64(), Some(i64::MAX));
+        assert_eq!(BigInt::from_i64(i64::MIN).to_i64(), Some(i64::MIN));
+        assert_eq!(max.add(&BigInt::from_i64(1)).to_i64(), None);
+        assert!(b < a && a < a.add(&BigInt::from_i64(1)));
+        assert_eq!(big("1000000000000000000000").to_f64(), 1e21);
+    }
+
+    #[test]
+    fn floor_division() {
+        let cases = [
+            ("7", "2", "3", "1"),
+            ("-7", "2", "-4", "1"),
+            ("7", "-2", "-4", "-1"),
+            ("-7", "-2", "3", "-1"),
+            ("6", "3", "2", "0"),
+        ];
+        for (a, b, q, r) in cases {
+            let (got_q, got_r) = big(a).div_mod_floor(&big(b)).unwrap();
+            assert_eq!((got_q.to_string(), got_r.to_string()), (q.into(), r.into()));
+        }
+        assert!(big("1").div_mod_floor(&BigInt::zero()).is_none());
+
+        // multi-limb divisors, checked by multiplying back out
+        let a = big("98765432109876543210987654321098765432109876543210");
+        let b = big("-1234567890123456789012345");
+        let (q, r) = a.div_mod_floor(&b).unwrap();
+        assert_eq!(q.mul(&b).add(&r), a);
+        assert!(r.is_negative() && r > b);
+
+        let two = big("4294967296000000000000000001");
+        let (q, r) = two.mul(&two).div_mod_floor(&two).unwrap();
+        assert_eq!((q, r), (two, BigInt::zero()));
+    }
+}
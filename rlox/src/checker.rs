@@ -0,0 +1,511 @@
+use crate::ast::*;
+use crate::error::*;
+use crate::expr::*;
+use crate::r#type::*;
+use crate::stmt::*;
+use crate::token::*;
+use crate::token_type::*;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Debug)]
+struct Binding {
+    ty: Type,
+    sig: Option<Signature>,
+    // the declaration of an unannotated local, whose type is only what its initializer inferred
+    inferred: Option<StmtId>,
+}
+
+impl Binding {
+    fn new(ty: Type) -> Self {
+        Self {
+            ty,
+            sig: None,
+            inferred: None,
+        }
+    }
+}
+
+// static pass over the parsed program.  annotated names are checked against their annotations,
+// unannotated locals get whatever their initializer infers to unless something assigns them a
+// different type, and everything else is Object, which never produces a mismatch.  nothing here
+// is needed to run a script, it just catches the obvious stuff before execution starts
+pub struct Checker<'a> {
+    ast: &'a Ast<'a>,
+    scopes: Vec<HashMap<String, Binding>>,
+    // return annotation of each function we're currently inside of
+    returns: Vec<Option<Type>>,
+    diags: Vec<Diagnostic>,
+    strict: bool,
+    // unannotated locals that get assigned something their initializer's type doesn't cover.
+    // they're Object from the declaration on
+    widened: HashSet<StmtId>,
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(ast: &'a Ast<'a>, strict: bool) -> Self {
+        let mut globals = HashMap::new();
+        let natives = [
+            ("clock", vec![], Some(Type::Number)),
+            ("args", vec![], Some(Type::Object)),
+            ("exit", vec![Some(Type::Number)], Some(Type::Nil)),
+        ];
+        for (name, params, ret) in natives {
+            globals.insert(
+                name.to_string(),
+                Binding {
+                    ty: Type::Function,
+                    sig: Some(Signature { params, ret }),
+                    inferred: None,
+                },
+            );
+        }
+        Self {
+            ast,
+            scopes: vec![globals],
+            returns: vec![],
+            diags: vec![],
+            strict,
+            widened: HashSet::new(),
+        }
+    }
+
+    // an assignment that widens a local can come after reads that went by the narrower type,
+    // in a loop say.  so go again with what was widened until that stops changing.  widening
+    // only ever makes more things Object, which doesn't take long to settle
+    pub fn check(self) -> Vec<Diagnostic> {
+        let (ast, strict) = (self.ast, self.strict);
+        let mut checker = self;
+        loop {
+            let widened = checker.widened.len();
+            checker.run();
+            if checker.widened.len() == widened {
+                return checker.diags;
+            }
+            let mut again = Checker::new(ast, strict);
+            again.widened = checker.widened;
+            checker = again;
+        }
+    }
+
+    fn run(&mut self) {
+        let ast = self.ast;
+        // hoist top-level functions so calls ahead of the declaration still get checked
+        for stmt in &ast.root {
+            if let Stmt::Function(fun) = ast.stmt(*stmt) {
+                let decl = ast.fun(*fun);
+                self.declare_function(&decl.name, &decl.sig);
+            }
+        }
+
+        for stmt in &ast.root {
+            if let Stmt::Function(fun) = ast.stmt(*stmt) {
+                let decl = ast.fun(*fun);
+                if self.strict && !decl.sig.is_annotated() {
+                    self.warning(
+                        decl.name.line,
+                        &format!("function '{}' is not fully annotated", decl.name.lexeme),
+                    );
+                }
+            }
+            self.check_stmt(*stmt);
+        }
+    }
+
+    fn error(&mut self, line: usize, msg: &str) {
+        self.diags.push(Diagnostic::error(line, msg));
+    }
+
+    fn warning(&mut self, line: usize, msg: &str) {
+        self.diags.push(Diagnostic::warning(line, msg));
+    }
+
+    fn is_global(&self) -> bool {
+        self.scopes.len() == 1
+    }
+
+    fn define(&mut self, name: &str, binding: Binding) {
+        let scope = self.scopes.last_mut().unwrap();
+        scope.insert(name.to_string(), binding);
+    }
+
+    fn declare_function(&mut self, name: &Token, sig: &Signature) {
+        self.define(
+            name.lexeme,
+            Binding {
+                ty: Type::Function,
+                sig: Some(sig.clone()),
+                inferred: None,
+            },
+        );
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn check_block(&mut self, stmts: &[StmtId]) {
+        self.scopes.push(HashMap::new());
+        for stmt in stmts {
+            self.check_stmt(*stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn check_stmt(&mut self, id: StmtId) {
+        let ast = self.ast;
+        match ast.stmt(id) {
+            Stmt::Expr(expr) | Stmt::Print(expr) => {
+                self.check_expr(*expr);
+            }
+            Stmt::Var(name, ty, init) => self.check_var(id, name, ty, *init),
+            Stmt::Block(stmts) => self.check_block(stmts),
+            Stmt::If(cond, then, els) => {
+                self.check_expr(*cond);
+                self.check_stmt(*then);
+                if let Some(els) = els {
+                    self.check_stmt(*els);
+                }
+            }
+            Stmt::While(cond, body) => {
+                self.check_expr(*cond);
+                self.check_stmt(*body);
+            }
+            Stmt::Loop(body) => self.check_stmt(*body),
+            Stmt::Function(fun) => self.check_function(ast.fun(*fun)),
+            Stmt::Return(keyword, val) => {
+                let ty = match val {
+                    Some(expr) => self.check_expr(*expr),
+                    None => Type::Nil,
+                };
+                if let Some(Some(expect)) = self.returns.last().cloned() {
+                    if !expect.accepts(&ty) {
+                        self.error(
+                            keyword.line,
+                            &format!("expected return type {} but got {}", expect, ty),
+                        );
+                    }
+                }
+            }
+            Stmt::Throw(_, expr) => {
+                self.check_expr(*expr);
+            }
+            Stmt::Yield(_, expr) => {
+                if let Some(expr) = expr {
+                    self.check_expr(*expr);
+                }
+            }
+            Stmt::ForIn(name, iter, body) => {
+                self.check_expr(*iter);
+                self.scopes.push(HashMap::new());
+                self.define(name.lexeme, Binding::new(Type::Object));
+                self.check_stmt(*body);
+                self.scopes.pop();
+            }
+            Stmt::Try(body, catch, finally) => {
+                self.check_stmt(*body);
+                if let Some((name, block)) = catch {
+                    self.scopes.push(HashMap::new());
+                    self.define(name.lexeme, Binding::new(Type::Object));
+                    self.check_stmt(*block);
+                    self.scopes.pop();
+                }
+                if let Some(block) = finally {
+                    self.check_stmt(*block);
+                }
+            }
+        }
+    }
+
+    fn check_var(&mut self, id: StmtId, name: &Token, ty: &Option<Type>, init: Option<ExprId>) {
+        let init_ty = init.map(|expr| self.check_expr(expr));
+
+        let declared = match (ty, init_ty) {
+            (Some(ty), Some(init_ty)) => {
+                if !ty.accepts(&init_ty) {
+                    self.error(
+                        name.line,
+                        &format!(
+                            "cannot initialize '{}' of type {} with {}",
+                            name.lexeme, ty, init_ty
+                        ),
+                    );
+                }
+                ty.clone()
+            }
+            (Some(ty), None) => ty.clone(),
+            // globals can be reassigned from anywhere, only infer for locals
+            (None, Some(init_ty)) if !self.is_global() && !self.widened.contains(&id) => {
+                let binding = Binding {
+                    inferred: Some(id),
+                    ..Binding::new(init_ty)
+                };
+                self.define(name.lexeme, binding);
+                return;
+            }
+            _ => Type::Object,
+        };
+        self.define(name.lexeme, Binding::new(declared));
+    }
+
+    fn check_function(&mut self, decl: &FunDecl) {
+        if !self.is_global() && !decl.is_anonymous() {
+            self.declare_function(&decl.name, &decl.sig);
+        }
+
+        self.scopes.push(HashMap::new());
+        for (param, ty) in decl.params.iter().zip(&decl.sig.params) {
+            let ty = ty.clone().unwrap_or(Type::Object);
+            self.define(param.lexeme, Binding::new(ty));
+        }
+        self.returns.push(decl.sig.ret.clone());
+        for stmt in &decl.body {
+            self.check_stmt(*stmt);
+        }
+        self.returns.pop();
+        self.scopes.pop();
+    }
+
+    fn check_expr(&mut self, id: ExprId) -> Type {
+        let ast = self.ast;
+        match ast.expr(id) {
+            Expr::Literal { token } => match token.ttype {
+                TokenType::Number(_) | TokenType::Integer(_) | TokenType::BigInteger => {
+                    Type::Number
+                }
+                TokenType::String(_) => Type::String,
+                TokenType::True | TokenType::False => Type::Boolean,
+                TokenType::Nil => Type::Nil,
+                _ => Type::Object,
+            },
+            Expr::Grouping { expr } => self.check_expr(*expr),
+            Expr::Variable { name } => match self.lookup(name.lexeme) {
+                Some(binding) => binding.ty.clone(),
+                None => Type::Object,
+            },
+            Expr::Assign { name, value } => {
+                let val = self.check_expr(*value);
+                if let Some(binding) = self.lookup(name.lexeme) {
+                    let ty = binding.ty.clone();
+                    match binding.inferred {
+                        // only a guess from the initializer, not something to hold the script to
+                        Some(var) if !ty.accepts(&val) => {
+                            self.widened.insert(var);
+                        }
+                        Some(_) => {}
+                        None if !ty.accepts(&val) => self.error(
+                            name.line,
+                            &format!("cannot assign {} to '{}' of type {}", val, name.lexeme, ty),
+                        ),
+                        None => {}
+                    }
+                }
+                val
+            }
+            Expr::Unary { op, right } => {
+                let right = self.check_expr(*right);
+                match op.ttype {
+                    TokenType::Bang => Type::Boolean,
+                    _ => {
+                        self.expect_number(op, &right);
+                        Type::Number
+                    }
+                }
+            }
+            Expr::Binary { op, left, right } => self.check_binary(op, *left, *right),
+            Expr::Logical { left, right, .. } => {
+                let left = self.check_expr(*left);
+                let right = self.check_expr(*right);
+                if left == right {
+                    left
+                } else {
+                    Type::Object
+                }
+            }
+            Expr::Call {
+                callee,
+                paren,
+                args,
+            } => self.check_call(*callee, paren, args),
+            Expr::Get { object, .. } => {
+                self.check_expr(*object);
+                Type::Object
+            }
+            Expr::Function(fun) => {
+                self.check_function(ast.fun(*fun));
+                Type::Function
+            }
+            Expr::Constant { value, .. } => match value {
+                Constant::Nil => Type::Nil,
+                Constant::Bool(_) => Type::Boolean,
+                Constant::Number(_) | Constant::Int(_) | Constant::BigInt(_) => Type::Number,
+                Constant::String(_) => Type::String,
+            },
+        }
+    }
+
+    fn expect_number(&mut self, op: &Token, ty: &Type) {
+        if !Type::Number.accepts(ty) {
+            self.error(
+                op.line,
+                &format!("operand of '{}' must be Number, got {}", op.lexeme, ty),
+            );
+        }
+    }
+
+    fn check_binary(&mut self, op: &Token, left: ExprId, right: ExprId) -> Type {
+        let left = self.check_expr(left);
+        let right = self.check_expr(right);
+
+        match op.ttype {
+            TokenType::EqualEqual | TokenType::BangEqual => Type::Boolean,
+            TokenType::Plus => match (&left, &right) {
+                (Type::Number, Type::Number) => Type::Number,
+                (Type::String, Type::String) => Type::String,
+                (Type::Object, _) | (_, Type::Object) => Type::Object,
+                _ => {
+                    self.error(
+                        op.line,
+                        &format!(
+                            "operands of '+' must be two Numbers or two Strings, got {} and {}",
+                            left, right
+                        ),
+                    );
+                    Type::Object
+                }
+            },
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => {
+                self.expect_number(op, &left);
+                self.expect_number(op, &right);
+                Type::Boolean
+            }
+            _ => {
+                self.expect_number(op, &left);
+                self.expect_number(op, &right);
+                Type::Number
+            }
+        }
+    }
+
+    fn check_call(&mut self, callee: ExprId, paren: &Token, args: &[ExprId]) -> Type {
+        self.check_expr(callee);
+        let args: Vec<Type> = args.iter().map(|arg| self.check_expr(*arg)).collect();
+
+        let callee = match self.ast.expr(callee) {
+            Expr::Variable { name } => name,
+            _ => return Type::Object,
+        };
+        let sig = match self.lookup(callee.lexeme) {
+            Some(Binding { sig: Some(sig), .. }) => sig.clone(),
+            _ => return Type::Object,
+        };
+
+        // arity is the interpreter's problem, only compare what lines up
+        if sig.params.len() == args.len() {
+            for (i, (param, arg)) in sig.params.iter().zip(&args).enumerate() {
+                if let Some(param) = param {
+                    if !param.accepts(arg) {
+                        self.error(
+                            paren.line,
+                            &format!(
+                                "argument {} of '{}' expects {} but got {}",
+                                i + 1,
+                                callee.lexeme,
+                                param,
+                                arg
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+        sig.ret.unwrap_or(Type::Object)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::*;
+    use crate::scanner::*;
+
+    fn check(src: &str, strict: bool) -> Vec<Diagnostic> {
+        let toks = Scanner::new(src).scan_tokens();
+        let ast = Parser::new(&toks).parse().unwrap();
+        Checker::new(&ast, strict).check()
+    }
+
+    #[test]
+    fn annotations() {
+        let diags = check(
+            r#"
+var x: Number = 1;
+var y: String = 2;
+x = "nope";
+fun add(a: Number, b: Number): Number {
+  return a + b;
+}
+fun name(): String {
+  return 1;
+}
+add(1, "two");
+var z: Number = add(1, 2);"#,
+            false,
+        );
+        let lines: Vec<usize> = diags.iter().map(|d| d.line).collect();
+        assert_eq!(lines, vec![3, 4, 9, 11]);
+        assert!(diags.iter().all(|d| d.severity == Severity::Error));
+    }
+
+    #[test]
+    fn inferred_locals() {
+        let diags = check(
+            r#"
+var g = 1;
+g = "globals stay untyped";
+{
+  var n = 1;
+  n = n + 1;
+  var s = "a" + "b";
+  print s - 1;
+  var found = nil;
+  found = 1;
+  var msg = "none";
+  msg = nil;
+  var last = nil;
+  while (n < 5) {
+    if (last != nil) print last + 1;
+    last = n;
+    n = n + 1;
+  }
+  var typed: Number = 1;
+  typed = "annotated ones are held to it";
+}"#,
+            false,
+        );
+        assert_eq!(
+            diags,
+            vec![
+                Diagnostic::error(8, "operand of '-' must be Number, got String"),
+                Diagnostic::error(20, "cannot assign String to 'typed' of type Number"),
+            ]
+        );
+    }
+
+    #[test]
+    fn strict_mode() {
+        let src = r#"
+fun typed(a: Number): Number { return a; }
+fun untyped(a) { return a; }"#;
+        assert!(check(src, false).is_empty());
+        assert_eq!(
+            check(src, true),
+            vec![Diagnostic::warning(
+                3,
+                "function 'untyped' is not fully annotated"
+            )]
+        );
+    }
+}
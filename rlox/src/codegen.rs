@@ -0,0 +1,881 @@
+use crate::ast::*;
+use crate::bigint::*;
+use crate::error::*;
+use crate::expr::*;
+use crate::interpreter::*;
+use crate::stmt::*;
+use crate::token::*;
+use crate::token_type::*;
+use std::collections::{HashMap, HashSet};
+
+const RUNTIME: &str = include_str!("runtime.c");
+
+// RT_MAX_ARGS in runtime.c, the most params a function can have
+const MAX_ARGS: usize = 255;
+
+// where a name lives once the C is running.  a local that a closure captures is kept in a cell
+// (see ObjCell in runtime.c) rather than straight in its slot, and the closure copies the cell
+// into a slot of its own when it starts, so both ends see the same variable
+#[derive(Clone, Copy, Debug)]
+enum Var {
+    Local(usize),
+    Global(usize),
+}
+
+// a try whose handler is still registered while the code inside it runs.  returning from in
+// there has to take the handler down and run the finally block on the way out
+struct TryCtx {
+    handler: String,
+    finally: Option<StmtId>,
+}
+
+// one per C function being generated.  every local and every intermediate result gets a slot
+// in the function's frame on rt_stack, which is all the collector ever needs to look at
+#[derive(Default)]
+struct FnCtx {
+    // 0 for the script, otherwise one more than the n in fun_n
+    id: usize,
+    code: String,
+    prologue: String,
+    slots: usize,
+    // empty at the top level of the script, where declarations go global
+    scopes: Vec<Vec<(String, usize)>>,
+    // slots of local functions further down a block that's being generated, see CGen::block
+    hoisted: HashSet<usize>,
+    // name, slot in this function, and where to copy it from in the enclosing one
+    captures: Vec<(String, usize, Var)>,
+    tries: Vec<TryCtx>,
+    // how many try statements the code being generated is inside of, handlers or not
+    in_try: usize,
+    indent: usize,
+}
+
+impl FnCtx {
+    fn slot(&mut self) -> usize {
+        self.slots += 1;
+        self.slots - 1
+    }
+}
+
+// lowers a checked program to a standalone C file (see runtime.c for the other half).  the
+// generated code sticks to what the interpreter does, quirks included, so scripts print the
+// same either way.  generators aren't supported
+pub struct CGen<'a> {
+    ast: &'a Ast<'a>,
+    // functions currently being generated, innermost last.  [0] is the script itself
+    fns: Vec<FnCtx>,
+    globals: Vec<String>,
+    global_ids: HashMap<String, usize>,
+    protos: String,
+    defs: String,
+    next_fun: usize,
+    next_try: usize,
+    errors: Vec<Diagnostic>,
+    // slots that hold a cell, by function id and slot
+    boxed: HashSet<(usize, usize)>,
+}
+
+impl<'a> CGen<'a> {
+    pub fn new(ast: &'a Ast<'a>) -> Self {
+        let mut gen = Self {
+            ast,
+            fns: vec![FnCtx {
+                indent: 1,
+                ..FnCtx::default()
+            }],
+            globals: vec![],
+            global_ids: HashMap::new(),
+            protos: String::new(),
+            defs: String::new(),
+            next_fun: 0,
+            next_try: 0,
+            errors: vec![],
+            boxed: HashSet::new(),
+        };
+        for native in natives() {
+            gen.global(&native.name);
+        }
+        gen
+    }
+
+    pub fn generate(mut self, source_name: &str) -> Result<String, Vec<Diagnostic>> {
+        let ast = self.ast;
+        // a local only turns out to be captured once the closure using it has been generated,
+        // and by then the code around it that reads and writes it has been too.  a first go
+        // through finds out which ones are, so this one can keep them in cells from the start
+        let mut scout = CGen::new(ast);
+        scout.stmts(&ast.root);
+        self.boxed = scout.boxed;
+
+        for stmt in &ast.root {
+            self.stmt(*stmt);
+        }
+        if !self.errors.is_empty() {
+            return Err(self.errors);
+        }
+
+        let script = self.fns.pop().unwrap();
+        let names: Vec<String> = self.globals.iter().map(|g| c_string(g)).collect();
+        let mut out = String::from(RUNTIME);
+        out.push_str(&format!(
+            "\n/* ---- generated from {} ---- */\n\n",
+            source_name
+        ));
+        out.push_str(&format!(
+            "static Value globals[{}];\nstatic const char *global_names[] = {{{}}};\n\n",
+            self.globals.len(),
+            names.join(", ")
+        ));
+        out.push_str(&self.protos);
+        out.push('\n');
+        out.push_str(&self.defs);
+        out.push_str("int main(void) {\n");
+        out.push_str(&format!(
+            "    rt_init(globals, global_names, {});\n",
+            self.globals.len()
+        ));
+        out.push_str(&format!("    Value *fp = rt_enter({});\n", script.slots));
+        out.push_str(&script.code);
+        out.push_str("    rt_leave(fp);\n    return 0;\n}\n");
+        Ok(out)
+    }
+
+    fn ctx(&mut self) -> &mut FnCtx {
+        self.fns.last_mut().unwrap()
+    }
+
+    fn slot(&mut self) -> usize {
+        self.ctx().slot()
+    }
+
+    fn emit(&mut self, line: &str) {
+        let ctx = self.ctx();
+        for _ in 0..ctx.indent {
+            ctx.code.push_str("    ");
+        }
+        ctx.code.push_str(line);
+        ctx.code.push('\n');
+    }
+
+    fn open(&mut self, line: &str) {
+        self.emit(line);
+        self.ctx().indent += 1;
+    }
+
+    fn close(&mut self, line: &str) {
+        self.ctx().indent -= 1;
+        self.emit(line);
+    }
+
+    fn unsupported(&mut self, line: usize, what: &str) {
+        self.errors.push(Diagnostic::error(
+            line,
+            &format!("{} aren't supported when compiling to C", what),
+        ));
+    }
+
+    fn global(&mut self, name: &str) -> usize {
+        if let Some(id) = self.global_ids.get(name) {
+            return *id;
+        }
+        self.globals.push(name.to_string());
+        self.global_ids
+            .insert(name.to_string(), self.globals.len() - 1);
+        self.globals.len() - 1
+    }
+
+    fn declare(&mut self, name: &str) -> Var {
+        if self.ctx().scopes.is_empty() {
+            return Var::Global(self.global(name));
+        }
+        let slot = self.slot();
+        let ctx = self.ctx();
+        ctx.scopes
+            .last_mut()
+            .unwrap()
+            .push((name.to_string(), slot));
+        Var::Local(slot)
+    }
+
+    fn resolve(&mut self, name: &str) -> Var {
+        self.resolve_in(self.fns.len() - 1, name)
+    }
+
+    // same lookup the interpreter does at runtime: locals of the running function, then whatever
+    // it captured, then globals.  anything found as a local further out becomes a capture of
+    // every function in between
+    fn resolve_in(&mut self, f: usize, name: &str) -> Var {
+        let ctx = &self.fns[f];
+        // the function's own code can't see a local function ahead of its declaration, only
+        // closures can
+        let visible = |slot: &usize| f < self.fns.len() - 1 || !ctx.hoisted.contains(slot);
+        for scope in ctx.scopes.iter().rev() {
+            if let Some((_, slot)) = scope
+                .iter()
+                .rev()
+                .find(|(n, slot)| n == name && visible(slot))
+            {
+                return Var::Local(*slot);
+            }
+        }
+        if let Some((_, slot, _)) = ctx.captures.iter().find(|(n, _, _)| n == name) {
+            return Var::Local(*slot);
+        }
+        if f == 0 {
+            return Var::Global(self.global(name));
+        }
+
+        match self.resolve_in(f - 1, name) {
+            Var::Global(id) => Var::Global(id),
+            Var::Local(from) => {
+                self.boxed.insert((self.fns[f - 1].id, from));
+                let ctx = &mut self.fns[f];
+                let slot = ctx.slot();
+                let id = ctx.id;
+                ctx.prologue.push_str(&format!(
+                    "    fp[{}] = self->captured[{}];\n",
+                    slot,
+                    ctx.captures.len()
+                ));
+                ctx.captures
+                    .push((name.to_string(), slot, Var::Local(from)));
+                self.boxed.insert((id, slot));
+                Var::Local(slot)
+            }
+        }
+    }
+
+    // a local's value as C, something that can be assigned to as well as read
+    fn local(&self, slot: usize) -> String {
+        if self.boxed.contains(&(self.fns.last().unwrap().id, slot)) {
+            format!("RT_CELL(fp[{}])->value", slot)
+        } else {
+            format!("fp[{}]", slot)
+        }
+    }
+
+    // what a fresh local starting out as `val` goes in its slot as
+    fn initial(&self, slot: usize, val: &str) -> String {
+        if self.boxed.contains(&(self.fns.last().unwrap().id, slot)) {
+            format!("rt_cell({})", val)
+        } else {
+            val.to_string()
+        }
+    }
+
+    fn assign(&mut self, var: Var, val: &str, line: usize) {
+        match var {
+            Var::Local(slot) => self.emit(&format!("{} = {};", self.local(slot), val)),
+            Var::Global(id) => self.emit(&format!("rt_set_global({}, {}, {});", id, val, line)),
+        }
+    }
+
+    // every run through a declaration makes a new variable, and a new cell for a boxed one.
+    // the value goes in the slot before the cell gets allocated, a caught error isn't anywhere
+    // the collector looks until then
+    fn define(&mut self, var: Var, val: &str) {
+        match var {
+            Var::Local(slot) => {
+                self.emit(&format!("fp[{}] = {};", slot, val));
+                let slot_val = format!("fp[{}]", slot);
+                let init = self.initial(slot, &slot_val);
+                if init != slot_val {
+                    self.emit(&format!("fp[{}] = {};", slot, init));
+                }
+            }
+            Var::Global(id) => self.emit(&format!("globals[{}] = {};", id, val)),
+        }
+    }
+
+    // stores `val` in a fresh slot and hands back the slot
+    fn temp(&mut self, val: &str) -> String {
+        let slot = self.slot();
+        self.emit(&format!("fp[{}] = {};", slot, val));
+        format!("fp[{}]", slot)
+    }
+
+    fn stmts(&mut self, stmts: &[StmtId]) {
+        for stmt in stmts {
+            self.stmt(*stmt);
+        }
+    }
+
+    // the statements of a block or function body, in a scope of their own.  a closure in there
+    // sees the block's scope as it is when it runs, like in the interpreter, so the block's
+    // local functions get their slots up front.  that way two of them can call each other
+    fn block(&mut self, stmts: &[StmtId]) {
+        let ast = self.ast;
+        for stmt in stmts {
+            if let Stmt::Function(fun) = ast.stmt(*stmt) {
+                if let Var::Local(slot) = self.declare(ast.fun(*fun).name.lexeme) {
+                    self.ctx().hoisted.insert(slot);
+                    let init = self.initial(slot, "rt_nil()");
+                    self.emit(&format!("fp[{}] = {};", slot, init));
+                }
+            }
+        }
+        self.stmts(stmts);
+    }
+
+    fn stmt(&mut self, id: StmtId) {
+        let ast = self.ast;
+        match ast.stmt(id) {
+            Stmt::Expr(expr) => {
+                self.expr(*expr);
+            }
+            Stmt::Print(expr) => {
+                let val = self.expr(*expr);
+                self.emit(&format!("rt_print({});", val));
+            }
+            Stmt::Var(name, _, init) => {
+                let val = match init {
+                    Some(init) => self.expr(*init),
+                    None => "rt_nil()".to_string(),
+                };
+                let var = self.declare(name.lexeme);
+                self.define(var, &val);
+            }
+            Stmt::Block(stmts) => {
+                self.open("{");
+                self.ctx().scopes.push(vec![]);
+                self.block(stmts);
+                self.ctx().scopes.pop();
+                self.close("}");
+            }
+            Stmt::If(cond, then, els) => {
+                let cond = self.expr(*cond);
+                self.open(&format!("if (rt_truthy({})) {{", cond));
+                self.stmt(*then);
+                if let Some(els) = els {
+                    self.close("} else {");
+                    self.ctx().indent += 1;
+                    self.stmt(*els);
+                }
+                self.close("}");
+            }
+            Stmt::While(cond, body) => {
+                self.open("for (;;) {");
+                let cond = self.expr(*cond);
+                self.emit(&format!("if (!rt_truthy({})) break;", cond));
+                self.stmt(*body);
+                self.close("}");
+            }
+            Stmt::Loop(body) => {
+                self.open("for (;;) {");
+                self.stmt(*body);
+                self.close("}");
+            }
+            Stmt::Function(fun) => {
+                let decl = ast.fun(*fun);
+                let val = self.function(*fun);
+                if self.ctx().scopes.is_empty() {
+                    let var = self.declare(decl.name.lexeme);
+                    self.define(var, &val);
+                } else {
+                    // closures may already hold the slot's cell, so fill it in rather than
+                    // making a new one
+                    let ctx = self.ctx();
+                    let (_, slot) = ctx
+                        .scopes
+                        .last()
+                        .unwrap()
+                        .iter()
+                        .find(|(n, slot)| n == decl.name.lexeme && ctx.hoisted.contains(slot))
+                        .cloned()
+                        .unwrap();
+                    ctx.hoisted.remove(&slot);
+                    self.assign(Var::Local(slot), &val, decl.name.line);
+                }
+            }
+            Stmt::Return(keyword, val) => self.ret(keyword, *val),
+            Stmt::Throw(keyword, val) => {
+                let val = self.expr(*val);
+                self.emit(&format!("rt_throw({}, {});", val, keyword.line));
+            }
+            Stmt::Try(body, catch, finally) => self.try_stmt(ast.line(id), *body, catch, *finally),
+            Stmt::Yield(keyword, _) => self.unsupported(keyword.line, "generators"),
+            Stmt::ForIn(name, _, _) => self.unsupported(name.line, "for-in loops"),
+        }
+    }
+
+    fn ret(&mut self, keyword: &Token, val: Option<ExprId>) {
+        if self.fns.len() == 1 {
+            self.emit(&format!(
+                "rt_error({}, \"can't return from top-level code\");",
+                keyword.line
+            ));
+            return;
+        }
+
+        if let Some(val) = val {
+            if self.tail_call(val) {
+                return;
+            }
+        }
+        let val = match val {
+            Some(val) => self.expr(val),
+            None => "rt_nil()".to_string(),
+        };
+        let val = self.temp(&val);
+
+        // leave every try we're inside of, running finally blocks innermost first.  each finally
+        // only sees the tries outside of it
+        let tries = std::mem::take(&mut self.ctx().tries);
+        for (i, ctx) in tries.iter().enumerate().rev() {
+            self.emit(&format!("rt_handler = {}.prev;", ctx.handler));
+            if let Some(finally) = ctx.finally {
+                self.ctx().tries.extend(tries[..i].iter().map(|t| TryCtx {
+                    handler: t.handler.clone(),
+                    finally: t.finally,
+                }));
+                self.stmt(finally);
+                self.ctx().tries.clear();
+            }
+        }
+        self.ctx().tries = tries;
+
+        self.open("{");
+        self.emit(&format!("Value ret = {};", val));
+        self.emit("rt_leave_fn(fp);");
+        self.emit("return ret;");
+        self.close("}");
+    }
+
+    // try { body } catch (e) { catch } finally { finally } turns into
+    //
+    //   if (RT_TRY(body)) { ...; rt_handler = body.prev; }
+    //   else if (RT_TRY(catch)) { e = body.err; ...; rt_handler = catch.prev; }
+    //   else { pending = catch.err; }
+    //   finally...
+    //   if (pending) rethrow
+    //
+    // with the catch handler only there when something needs to run after the catch block
+    fn try_stmt(
+        &mut self,
+        line: usize,
+        body: StmtId,
+        catch: &Option<(Token, StmtId)>,
+        finally: Option<StmtId>,
+    ) {
+        let n = self.next_try;
+        self.next_try += 1;
+        let h_body = format!("h{}_body", n);
+        let h_catch = format!("h{}_catch", n);
+        let pending = format!("pending{}", n);
+        let err = self.slot();
+        self.ctx().in_try += 1;
+
+        self.open("{");
+        self.emit(&format!("Handler {};", h_body));
+        if finally.is_some() {
+            self.emit(&format!("volatile int {} = 0;", pending));
+        }
+        self.open(&format!("if (RT_TRY({})) {{", h_body));
+        self.ctx().tries.push(TryCtx {
+            handler: h_body.clone(),
+            finally,
+        });
+        self.stmt(body);
+        self.ctx().tries.pop();
+        self.emit(&format!("rt_handler = {}.prev;", h_body));
+        self.close("} else {");
+        self.ctx().indent += 1;
+
+        match (catch, finally) {
+            (Some((name, block)), Some(_)) => {
+                self.emit(&format!("Handler {};", h_catch));
+                self.open(&format!("if (RT_TRY({})) {{", h_catch));
+                self.catch_block(
+                    name,
+                    *block,
+                    &format!("{}.err", h_body),
+                    Some(&h_catch),
+                    finally,
+                );
+                self.emit(&format!("rt_handler = {}.prev;", h_catch));
+                self.close("} else {");
+                self.ctx().indent += 1;
+                self.emit(&format!("{} = 1;", pending));
+                self.emit(&format!("fp[{}] = {}.err;", err, h_catch));
+                self.close("}");
+            }
+            (Some((name, block)), None) => {
+                self.catch_block(name, *block, &format!("{}.err", h_body), None, None);
+            }
+            (None, _) => {
+                self.emit(&format!("{} = 1;", pending));
+                self.emit(&format!("fp[{}] = {}.err;", err, h_body));
+            }
+        }
+        self.close("}");
+
+        if let Some(finally) = finally {
+            self.stmt(finally);
+            self.emit(&format!(
+                "if ({}) rt_throw(fp[{}], {});",
+                pending, err, line
+            ));
+        }
+        self.close("}");
+        self.ctx().in_try -= 1;
+    }
+
+    fn catch_block(
+        &mut self,
+        name: &Token,
+        block: StmtId,
+        err: &str,
+        handler: Option<&str>,
+        finally: Option<StmtId>,
+    ) {
+        self.ctx().scopes.push(vec![]);
+        let var = self.declare(name.lexeme);
+        self.define(var, err);
+        // returning from in here skips the rethrow but still has to run the finally block
+        if let Some(handler) = handler {
+            self.ctx().tries.push(TryCtx {
+                handler: handler.to_string(),
+                finally,
+            });
+        }
+        self.stmt(block);
+        if handler.is_some() {
+            self.ctx().tries.pop();
+        }
+        self.ctx().scopes.pop();
+    }
+
+    // generates the C function for a declaration and returns a slot holding the closure
+    fn function(&mut self, fun: FunId) -> String {
+        let ast = self.ast;
+        let decl = ast.fun(fun);
+        if decl.generator {
+            self.unsupported(decl.name.line, "generators");
+        }
+
+        let name = format!("fun_{}", self.next_fun);
+        self.next_fun += 1;
+        self.fns.push(FnCtx {
+            id: self.next_fun,
+            scopes: vec![vec![]],
+            indent: 1,
+            ..FnCtx::default()
+        });
+
+        for (i, param) in decl.params.iter().enumerate() {
+            let slot = self.slot();
+            let init = self.initial(slot, &format!("args[{}]", i));
+            let ctx = self.ctx();
+            ctx.scopes[0].push((param.lexeme.to_string(), slot));
+            ctx.prologue
+                .push_str(&format!("    fp[{}] = {};\n", slot, init));
+        }
+        self.block(&decl.body);
+
+        let ctx = self.fns.pop().unwrap();
+        self.protos.push_str(&format!(
+            "static Value {}(ObjFun *self, Value *args);\n",
+            name
+        ));
+        self.defs.push_str(&format!(
+            "/* {}, line {} */\nstatic Value {}(ObjFun *self, Value *args) {{\n",
+            decl.label(),
+            decl.name.line,
+            name
+        ));
+        self.defs.push_str(&format!(
+            "    Value *fp = rt_enter_fn({});\n    (void)self;\n    (void)args;\n",
+            ctx.slots
+        ));
+        self.defs.push_str(&ctx.prologue);
+        self.defs.push_str(&ctx.code);
+        self.defs
+            .push_str("    rt_leave_fn(fp);\n    return rt_nil();\n}\n\n");
+
+        let closure = self.temp(&format!(
+            "rt_closure({}, {}, {}, {})",
+            name,
+            c_string(decl.label()),
+            decl.params.len(),
+            ctx.captures.len()
+        ));
+        for (i, (_, _, from)) in ctx.captures.iter().enumerate() {
+            let from = match from {
+                Var::Local(slot) => format!("fp[{}]", slot),
+                Var::Global(_) => unreachable!("globals are never captured"),
+            };
+            self.emit(&format!("rt_capture({}, {}, {});", closure, i, from));
+        }
+        closure
+    }
+
+    // the args of a call in slots next to each other, and a pointer to the first
+    fn args(&mut self, args: &[ExprId]) -> String {
+        let base = self.ctx().slots;
+        self.ctx().slots += args.len();
+        for (i, arg) in args.iter().enumerate() {
+            let val = self.expr(*arg);
+            self.emit(&format!("fp[{}] = {};", base + i, val));
+        }
+        match args.len() {
+            0 => "NULL".to_string(),
+            _ => format!("&fp[{}]", base),
+        }
+    }
+
+    // `return f(x)` hands f and x to whoever called us rather than calling f here, like the
+    // interpreter does.  not inside a try, where the call has to happen with the handler still
+    // up, and not with more args than any function can take
+    fn tail_call(&mut self, val: ExprId) -> bool {
+        let ast = self.ast;
+        let (callee, paren, args) = match ast.expr(val) {
+            Expr::Call {
+                callee,
+                paren,
+                args,
+            } if self.ctx().in_try == 0 && args.len() <= MAX_ARGS => (callee, paren, args),
+            _ => return false,
+        };
+        let callee = self.expr(*callee);
+        let argv = self.args(args);
+        self.emit(&format!(
+            "return rt_tail({}, {}, {}, {}, fp);",
+            callee,
+            argv,
+            args.len(),
+            paren.line
+        ));
+        true
+    }
+
+    // emits whatever it takes to compute an expression and returns a C expression for the
+    // result.  that's always either a slot or a constant, so it can be used more than once and
+    // nothing that runs later can change it
+    fn expr(&mut self, id: ExprId) -> String {
+        let ast = self.ast;
+        match ast.expr(id) {
+            Expr::Literal { token } => match token.ttype {
+                TokenType::Number(n) => c_number(n),
+                TokenType::Integer(n) => c_int(n),
+                // the same check Interpreter::eval_literal makes, a loaded ast could say anything
+                TokenType::BigInteger => match BigInt::parse(token.lexeme) {
+                    Some(n) => self.temp(&format!("rt_big_lit(\"{}\")", n)),
+                    None => {
+                        let msg = format!("bad integer literal {:?}", token.lexeme);
+                        self.emit(&format!(
+                            "rt_error({}, \"%s\", {});",
+                            token.line,
+                            c_string(&msg)
+                        ));
+                        "rt_nil()".to_string()
+                    }
+                },
+                TokenType::String(s) => self.temp(&format!("rt_str({}, {})", c_string(s), s.len())),
+                TokenType::True => "rt_bool(1)".to_string(),
+                TokenType::False => "rt_bool(0)".to_string(),
+                _ => "rt_nil()".to_string(),
+            },
+            Expr::Constant { value, .. } => match value {
+                Constant::Nil => "rt_nil()".to_string(),
+                Constant::Bool(b) => format!("rt_bool({})", *b as u8),
+                Constant::Number(n) => c_number(*n),
+                Constant::Int(n) => c_int(*n),
+                Constant::BigInt(n) => self.temp(&format!("rt_big_lit(\"{}\")", n)),
+                Constant::String(s) => self.temp(&format!("rt_str({}, {})", c_string(s), s.len())),
+            },
+            Expr::Grouping { expr } => self.expr(*expr),
+            Expr::Variable { name } => match self.resolve(name.lexeme) {
+                Var::Local(slot) => self.temp(&self.local(slot)),
+                // args() hands out a generator
+                Var::Global(_) if name.lexeme == "args" => {
+                    self.unsupported(name.line, "script arguments");
+                    "rt_nil()".to_string()
+                }
+                Var::Global(g) => self.temp(&format!("rt_global({}, {})", g, name.line)),
+            },
+            Expr::Assign { name, value } => {
+                let val = self.expr(*value);
+                let var = self.resolve(name.lexeme);
+                self.assign(var, &val, name.line);
+                val
+            }
+            Expr::Binary { op, left, right } => {
+                let left = self.expr(*left);
+                let right = self.expr(*right);
+                let call = match op.ttype {
+                    TokenType::EqualEqual => format!("rt_eq({}, {})", left, right),
+                    TokenType::BangEqual => format!("rt_ne({}, {})", left, right),
+                    tt => {
+                        let func = match tt {
+                            TokenType::Plus => "rt_add",
+                            TokenType::Minus => "rt_sub",
+                            TokenType::Star => "rt_mul",
+                            TokenType::Slash => "rt_div",
+                            TokenType::SlashSlash => "rt_idiv",
+                            TokenType::Percent => "rt_mod",
+                            TokenType::Greater => "rt_gt",
+                            TokenType::GreaterEqual => "rt_ge",
+                            TokenType::Less => "rt_lt",
+                            _ => "rt_le",
+                        };
+                        format!("{}({}, {}, {})", func, left, right, op.line)
+                    }
+                };
+                self.temp(&call)
+            }
+            Expr::Unary { op, right } => {
+                let right = self.expr(*right);
+                let func = match op.ttype {
+                    TokenType::Minus => "rt_neg",
+                    _ => "rt_not",
+                };
+                self.temp(&format!("{}({}, {})", func, right, op.line))
+            }
+            Expr::Logical { op, left, right } => {
+                let left = self.expr(*left);
+                let result = self.temp(&left);
+                let test = match op.ttype {
+                    TokenType::Or => "!",
+                    _ => "",
+                };
+                self.open(&format!("if ({}rt_truthy({})) {{", test, result));
+                let right = self.expr(*right);
+                self.emit(&format!("{} = {};", result, right));
+                self.close("}");
+                result
+            }
+            Expr::Call {
+                callee,
+                paren,
+                args,
+            } => {
+                let callee = self.expr(*callee);
+                let argv = self.args(args);
+                self.temp(&format!(
+                    "rt_call({}, {}, {}, {})",
+                    callee,
+                    argv,
+                    args.len(),
+                    paren.line
+                ))
+            }
+            Expr::Get { object, name } => {
+                let object = self.expr(*object);
+                self.temp(&format!(
+                    "rt_get({}, {}, {})",
+                    object,
+                    c_string(name.lexeme),
+                    name.line
+                ))
+            }
+            Expr::Function(fun) => self.function(*fun),
+        }
+    }
+}
+
+fn c_number(n: f64) -> String {
+    if n.is_nan() {
+        "rt_num(NAN)".to_string()
+    } else if n.is_infinite() {
+        format!("rt_num({}INFINITY)", if n < 0.0 { "-" } else { "" })
+    } else {
+        format!("rt_num({:?})", n)
+    }
+}
+
+fn c_int(n: i64) -> String {
+    match n {
+        // -9223372036854775808 would be negating a literal that doesn't fit
+        i64::MIN => "rt_int(LLONG_MIN)".to_string(),
+        n => format!("rt_int({}LL)", n),
+    }
+}
+
+// a C string literal with the same bytes.  anything outside printable ascii goes out as octal
+fn c_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for b in s.bytes() {
+        match b {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b'\n' => out.push_str("\\n"),
+            b'\t' => out.push_str("\\t"),
+            // stop trigraphs like ??= from sneaking through
+            b'?' => out.push_str("\\?"),
+            0x20..=0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::*;
+    use crate::scanner::*;
+
+    fn generate(src: &str) -> Result<String, Vec<Diagnostic>> {
+        let toks = Scanner::new(src).scan_tokens();
+        let ast = Parser::new(&toks).parse().unwrap();
+        CGen::new(&ast).generate("test.lox")
+    }
+
+    #[test]
+    fn lowers_closures() {
+        let c = generate(
+            r#"
+fun outer(a) {
+  var b = 2;
+  fun inner() { return a + b; }
+  return inner;
+}
+print outer(1)();"#,
+        )
+        .unwrap();
+        // inner shares both of outer's locals.  outer keeps them in cells, inner gets handed the
+        // cells.  inner's own slot in outer is set up before anything else in the body runs
+        assert!(c.contains("static Value fun_1(ObjFun *self, Value *args) {"));
+        assert!(c.contains("rt_closure(fun_1, \"inner\", 0, 2)"));
+        assert!(c.contains("fp[0] = rt_cell(args[0]);\n    fp[1] = rt_nil();"));
+        assert!(c.contains("fp[2] = rt_cell(fp[2]);"));
+        assert!(c.contains("fp[1] = fp[3];"));
+        assert!(c.contains("= self->captured[1];"));
+        assert!(c.contains("RT_CELL(fp[2])->value"));
+        assert!(c.contains(
+            "static const char *global_names[] = {\"clock\", \"args\", \"exit\", \"outer\"};"
+        ));
+    }
+
+    #[test]
+    fn lowers_tail_calls() {
+        let c = generate(
+            r#"
+fun down(n) {
+  if (n == 0) return 0;
+  try { return down(n - 1); } catch (e) {}
+  return down(n - 1);
+}
+print down(3);"#,
+        )
+        .unwrap();
+        // only the one outside the try.  the other is a plain call, like the script's
+        assert_eq!(c.matches("return rt_tail(").count(), 1);
+        assert_eq!(c.matches("= rt_call(").count(), 2);
+    }
+
+    #[test]
+    fn escapes_strings() {
+        assert_eq!(c_string("a\"b\\c\n?é"), "\"a\\\"b\\\\c\\n\\?\\303\\251\"");
+        assert_eq!(c_number(0.1), "rt_num(0.1)");
+        assert_eq!(c_number(f64::NEG_INFINITY), "rt_num(-INFINITY)");
+    }
+
+    #[test]
+    fn rejects_generators() {
+        let errs = generate("fun g() { yield 1; }\nfor (x in g()) print x;").unwrap_err();
+        let lines: Vec<usize> = errs.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![1, 1, 2]);
+    }
+}
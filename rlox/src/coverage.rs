@@ -0,0 +1,105 @@
+use crate::ast::*;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+// statement executions per source line, so a line holding an if and its block counts both.  every
+// line that starts a statement is seeded with zero up front, so code that never ran (uncalled
+// functions, untaken branches) still shows up as missed
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Coverage {
+    pub hits: BTreeMap<usize, u64>,
+}
+
+impl Coverage {
+    pub fn new(ast: &Ast) -> Self {
+        let hits = ast.lines().iter().map(|line| (*line, 0)).collect();
+        Self { hits }
+    }
+
+    pub fn hit(&mut self, line: usize) {
+        *self.hits.entry(line).or_default() += 1;
+    }
+
+    // (lines executed, lines that could have been)
+    pub fn summary(&self) -> (usize, usize) {
+        let hit = self.hits.values().filter(|n| **n > 0).count();
+        (hit, self.hits.len())
+    }
+
+    // gcov style listing: hit count, ##### for lines that never ran, - for lines with no
+    // statement on them
+    pub fn annotate(&self, source: &str) -> String {
+        let mut out = String::new();
+        for (i, text) in source.lines().enumerate() {
+            let count = match self.hits.get(&(i + 1)) {
+                Some(0) => "#####".to_string(),
+                Some(n) => n.to_string(),
+                None => "-".to_string(),
+            };
+            let _ = writeln!(out, "{:>9}:{:>5}:{}", count, i + 1, text);
+        }
+        out
+    }
+
+    // lcov tracefile for a single source file
+    pub fn lcov(&self, path: &str) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "TN:");
+        let _ = writeln!(out, "SF:{}", path);
+        for (line, hits) in &self.hits {
+            let _ = writeln!(out, "DA:{},{}", line, hits);
+        }
+        let (hit, total) = self.summary();
+        let _ = writeln!(out, "LF:{}", total);
+        let _ = writeln!(out, "LH:{}", hit);
+        let _ = writeln!(out, "end_of_record");
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::*;
+    use crate::parser::*;
+    use crate::scanner::*;
+    use std::io;
+
+    #[test]
+    fn records_lines() {
+        let src = r#"var x = 1;
+fun never() {
+  print "nope";
+}
+if (x > 0) {
+  x = x + 1;
+} else {
+  x = 0;
+}
+"#;
+        let toks = Scanner::new(src).scan_tokens();
+        let ast = Parser::new(&toks).parse().unwrap();
+        let mut ir = Interpreter::with_output(&ast, Box::new(io::sink()));
+        ir.enable_coverage();
+        ir.run(&ast.root).unwrap();
+        let cov = ir.take_coverage().unwrap();
+
+        assert_eq!(cov.summary(), (4, 7));
+        assert_eq!(
+            cov.annotate(src),
+            r#"        1:    1:var x = 1;
+        1:    2:fun never() {
+    #####:    3:  print "nope";
+        -:    4:}
+        2:    5:if (x > 0) {
+        1:    6:  x = x + 1;
+    #####:    7:} else {
+    #####:    8:  x = 0;
+        -:    9:}
+"#
+        );
+        assert_eq!(
+            cov.lcov("t.lox"),
+            "TN:\nSF:t.lox\nDA:1,1\nDA:2,1\nDA:3,0\nDA:5,2\nDA:6,1\nDA:7,0\nDA:8,0\nLF:7\nLH:4\nend_of_record\n"
+        );
+    }
+}
@@ -1,19 +1,61 @@
 use crate::error::*;
 use crate::value::*;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
-pub type ValMap = HashMap<String, Value>;
+// where a variable's value lives.  shared rather than copied when a closure captures it, so an
+// assignment on either side is seen by the other
+pub type Slot = Rc<RefCell<Value>>;
+pub type ValMap = HashMap<String, Slot>;
+
+// a block's worth of variables.  shared too, a closure holds on to the scopes it was made in so
+// names declared there after it was made still resolve
+pub type Scope = Rc<RefCell<ValMap>>;
+
+pub fn slot(val: Value) -> Slot {
+    Rc::new(RefCell::new(val))
+}
+
+fn scope() -> Scope {
+    Rc::new(RefCell::new(HashMap::new()))
+}
+
+#[derive(Debug)]
+pub struct SavedScopes {
+    values: Vec<Scope>,
+    gen: usize,
+}
+
+impl SavedScopes {
+    // an empty scope on top of the ones a function was made in, what a function body starts out
+    // with
+    pub fn new(enclosing: &[Scope]) -> Self {
+        let mut values = enclosing.to_vec();
+        values.push(scope());
+        Self {
+            gen: values.len(),
+            values,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Environment {
-    values: Vec<ValMap>,
+    values: Vec<Scope>,
     gen: usize,
 }
 
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Environment {
     pub fn new() -> Self {
         Self {
-            values: vec![HashMap::new()],
+            values: vec![scope()],
             gen: 0,
         }
     }
@@ -22,12 +64,14 @@ impl Environment {
     pub fn bump(&mut self) {
         self.gen += 1;
         while self.values.len() <= self.gen {
-            self.values.push(HashMap::new());
+            self.values.push(scope());
         }
     }
 
     pub fn debump(&mut self) {
         if self.gen > 0 {
+            // the next bump gets a fresh scope, a closure made in this one may still be using it
+            self.values[self.gen] = scope();
             self.gen -= 1;
         }
     }
@@ -35,19 +79,65 @@ impl Environment {
     pub fn bump_num(&mut self, num: usize) {
         self.gen += num;
         while self.values.len() <= self.gen {
-            self.values.push(HashMap::new());
+            self.values.push(scope());
         }
     }
 
+    // how many scopes deep we are, 0 meaning globals
+    pub fn depth(&self) -> usize {
+        self.gen
+    }
+
     pub fn reset(&mut self) -> usize {
         let num = self.gen;
         self.gen = 0;
         num
     }
 
+    // function bodies only get to see globals, the scopes the function was made in and their own
+    // scopes.  stash every scope above the globals off to the side for the duration of the call
+    // and hand them back afterwards
+    pub fn enter_function(&mut self, enclosing: &[Scope]) -> SavedScopes {
+        let saved = SavedScopes {
+            values: self.values.split_off(1),
+            gen: self.gen,
+        };
+        self.values.extend_from_slice(enclosing);
+        self.gen = enclosing.len();
+        self.bump();
+        saved
+    }
+
+    pub fn leave_function(&mut self, saved: SavedScopes) {
+        self.values.truncate(1);
+        self.values.extend(saved.values);
+        self.gen = saved.gen;
+    }
+
+    // trade every scope above the globals for the ones in `scopes`.  doing it twice puts things
+    // back, which is how generators step in and out of their own locals
+    pub fn swap_scopes(&mut self, scopes: &mut SavedScopes) {
+        let mut values = self.values.split_off(1);
+        std::mem::swap(&mut values, &mut scopes.values);
+        self.values.extend(values);
+        std::mem::swap(&mut self.gen, &mut scopes.gen);
+    }
+
+    // the chain of scopes currently in view, outermost first.  the scopes themselves rather than
+    // what's in them right now, so a function declared further down the same block is still
+    // found.  globals are left out since function bodies can see those anyway
+    pub fn capture(&self) -> Vec<Scope> {
+        self.values[1..=self.gen].to_vec()
+    }
+
     pub fn define(&mut self, name: &str, val: Value) {
-        let vals = &mut self.values[self.gen];
-        vals.insert(name.to_string(), val);
+        self.define_slot(name, slot(val));
+    }
+
+    // bring a variable that lives somewhere else into scope, like one a closure captured
+    pub fn define_slot(&mut self, name: &str, slot: Slot) {
+        let mut vals = self.values[self.gen].borrow_mut();
+        vals.insert(name.to_string(), slot);
     }
 
     pub fn assign(&mut self, name: &str, val: Value, line: usize) -> Result<(), RuntimeError> {
@@ -61,9 +151,8 @@ impl Environment {
         line: usize,
         gen: usize,
     ) -> Result<(), RuntimeError> {
-        let vals = &mut self.values[gen];
-        if vals.contains_key(name) {
-            vals.insert(name.to_string(), val);
+        if let Some(slot) = self.values[gen].borrow().get(name) {
+            *slot.borrow_mut() = val;
             return Ok(());
         }
 
@@ -82,9 +171,9 @@ impl Environment {
     }
 
     fn get_impl(&self, name: &str, line: usize, gen: usize) -> Result<Value, RuntimeError> {
-        let vals = &self.values[gen];
-        if let Some(val) = vals.get(name) {
-            return Ok(val.clone());
+        let vals = self.values[gen].borrow();
+        if let Some(slot) = vals.get(name) {
+            return Ok(slot.borrow().clone());
         }
 
         if gen == 0 {
@@ -119,24 +208,67 @@ mod test {
     #[test]
     fn env() {
         let mut env = Environment::new();
-        env.define("a", Value::String("foo".to_string()));
+        env.define("a", Value::String("foo".into()));
 
         env.bump();
-        env.assign("a", Value::String("bar".to_string()), 0)
-            .unwrap();
-        assert_get(&env, "a", Value::String("bar".to_string()));
+        env.assign("a", Value::String("bar".into()), 0).unwrap();
+        assert_get(&env, "a", Value::String("bar".into()));
 
-        env.define("b", Value::String("baz".to_string()));
-        assert_get(&env, "a", Value::String("bar".to_string()));
+        env.define("b", Value::String("baz".into()));
+        assert_get(&env, "a", Value::String("bar".into()));
 
         env.bump();
-        env.assign("a", Value::String("final".to_string()), 0)
-            .unwrap();
-        assert_get(&env, "a", Value::String("final".to_string()));
+        env.assign("a", Value::String("final".into()), 0).unwrap();
+        assert_get(&env, "a", Value::String("final".into()));
 
         env.debump();
         env.debump();
-        assert_get(&env, "a", Value::String("final".to_string()));
+        assert_get(&env, "a", Value::String("final".into()));
         assert_none(&env, "b");
     }
+
+    #[test]
+    fn reads_share_strings() {
+        let mut env = Environment::new();
+        let s: std::rc::Rc<str> = "x".repeat(1000).into();
+        env.define("s", Value::String(s.clone()));
+        match env.get("s", 0).unwrap() {
+            Value::String(got) => assert!(std::rc::Rc::ptr_eq(&got, &s)),
+            other => panic!("expected a string, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn captures_share_slots() {
+        let mut env = Environment::new();
+        env.bump();
+        env.define("n", Value::Int(0));
+        let captured = env.capture();
+
+        env.assign("n", Value::Int(1), 0).unwrap();
+        assert_eq!(*captured[0].borrow()["n"].borrow(), Value::Int(1));
+
+        let saved = env.enter_function(&captured);
+        env.assign("n", Value::Int(2), 0).unwrap();
+        env.leave_function(saved);
+        assert_get(&env, "n", Value::Int(2));
+    }
+
+    #[test]
+    fn captures_see_later_declarations() {
+        let mut env = Environment::new();
+        env.bump();
+        let captured = env.capture();
+        env.define("later", Value::Int(1));
+
+        let saved = env.enter_function(&captured);
+        assert_get(&env, "later", Value::Int(1));
+        env.leave_function(saved);
+
+        // leaving the block hands the next one a fresh scope, the captured one keeps its locals
+        env.debump();
+        env.bump();
+        assert_none(&env, "later");
+        assert_eq!(*captured[0].borrow()["later"].borrow(), Value::Int(1));
+    }
 }
@@ -1,14 +1,179 @@
+use crate::function::*;
+use crate::value::*;
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ErrorKind {
+    // built-in failures raised by the interpreter itself (bad operands, undefined variables, ...)
+    Runtime,
+    // user-level `throw` carrying an arbitrary value
+    Throw(Box<Value>),
+    // not really an error, but unwinding out of a function body on `return` rides the same
+    // path as exceptions do.  never catchable
+    Return(Box<Value>),
+    // `return f(x)`: the call gets handed back to whoever called us so it can be made from
+    // there, without the rust stack growing.  never catchable either
+    TailCall(Box<(Rc<LoxFunction>, Vec<Value>)>),
+    // exit(code) winding the whole script down.  nothing catches it, finally blocks still run
+    Exit(i32),
+    // a script went over one of its Limits.  never catchable, or a script could catch the error
+    // and carry on past the limit
+    StepLimit,
+    DepthLimit,
+    TimeLimit,
+    MemoryLimit,
+}
+
+// one entry per lox function we unwound through.  line is where that function was called from
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub name: String,
+    pub line: usize,
+}
+
 #[derive(Debug)]
 pub struct RuntimeError {
     pub msg: String,
     pub line: usize,
+    pub kind: ErrorKind,
+    pub trace: Vec<Frame>,
 }
 
 impl RuntimeError {
     pub fn new(msg: &str, line: usize) -> Self {
         Self {
             msg: msg.to_string(),
-            line: line,
+            line,
+            kind: ErrorKind::Runtime,
+            trace: vec![],
+        }
+    }
+
+    pub fn throw(val: Value, line: usize) -> Self {
+        Self {
+            msg: format!("{}", val),
+            line,
+            kind: ErrorKind::Throw(Box::new(val)),
+            trace: vec![],
+        }
+    }
+
+    pub fn ret(val: Value, line: usize) -> Self {
+        Self {
+            msg: "can't return from top-level code".to_string(),
+            line,
+            kind: ErrorKind::Return(Box::new(val)),
+            trace: vec![],
+        }
+    }
+
+    pub fn tail_call(func: Rc<LoxFunction>, args: Vec<Value>, line: usize) -> Self {
+        Self {
+            msg: String::new(),
+            line,
+            kind: ErrorKind::TailCall(Box::new((func, args))),
+            trace: vec![],
+        }
+    }
+
+    pub fn exit(code: i32, line: usize) -> Self {
+        Self {
+            msg: format!("exit({})", code),
+            line,
+            kind: ErrorKind::Exit(code),
+            trace: vec![],
+        }
+    }
+
+    pub fn limit(kind: ErrorKind, msg: &str, line: usize) -> Self {
+        Self {
+            msg: msg.to_string(),
+            line,
+            kind,
+            trace: vec![],
+        }
+    }
+
+    // control flow isn't an error to the script, and neither are the limits.  they're kinds of
+    // their own so the host can tell which one a run hit, but a sandboxed script that could
+    // catch StepLimit in a loop would never stop
+    pub fn is_catchable(&self) -> bool {
+        !matches!(
+            self.kind,
+            ErrorKind::Return(_)
+                | ErrorKind::TailCall(_)
+                | ErrorKind::Exit(_)
+                | ErrorKind::StepLimit
+                | ErrorKind::DepthLimit
+                | ErrorKind::TimeLimit
+                | ErrorKind::MemoryLimit
+        )
+    }
+
+    // what a `catch (e)` clause binds.  thrown values come through untouched, built-in errors get
+    // wrapped into an error object exposing `message` and `line`
+    pub fn value(&self) -> Value {
+        match &self.kind {
+            ErrorKind::Throw(val) => (**val).clone(),
+            _ => Value::Error(ErrorObject::new(&self.msg, self.line)),
+        }
+    }
+
+    pub fn push_frame(&mut self, name: &str, line: usize) {
+        self.trace.push(Frame {
+            name: name.to_string(),
+            line,
+        });
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ErrorKind::Throw(val) => write!(f, "uncaught exception: {}", val)?,
+            _ => write!(f, "runtime error: {}", self.msg)?,
+        }
+
+        // the error line sits in the innermost function, each frame then knows the line its
+        // caller was on.  whatever called the outermost frame is the script itself
+        let mut line = self.line;
+        for frame in &self.trace {
+            write!(f, "\n[line {}] in {}()", line, frame.name)?;
+            line = frame.line;
+        }
+        write!(f, "\n[line {}] in script", line)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// static analysis findings, reported before anything gets to run
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub line: usize,
+    pub msg: String,
+}
+
+impl Diagnostic {
+    pub fn error(line: usize, msg: &str) -> Self {
+        Self {
+            severity: Severity::Error,
+            line,
+            msg: msg.to_string(),
+        }
+    }
+
+    pub fn warning(line: usize, msg: &str) -> Self {
+        Self {
+            severity: Severity::Warning,
+            line,
+            msg: msg.to_string(),
         }
     }
 }
@@ -1,128 +1,89 @@
+use crate::ast::*;
+use crate::bigint::*;
 use crate::token::*;
-use crate::token_type::*;
-use std::fmt;
+use crate::value::*;
+use std::rc::Rc;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub enum ExprType {
-    Assign,
-    Binary,
-    Call,
-    Unary,
-    Grouping,
-    Literal,
-    Logical,
-    Variable,
+// one variant per kind of expression.  children are ids into the owning Ast
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr<'src> {
+    Assign {
+        name: Token<'src>,
+        value: ExprId,
+    },
+    Binary {
+        op: Token<'src>,
+        left: ExprId,
+        right: ExprId,
+    },
+    Call {
+        callee: ExprId,
+        paren: Token<'src>,
+        args: Vec<ExprId>,
+    },
+    Get {
+        object: ExprId,
+        name: Token<'src>,
+    },
+    Grouping {
+        expr: ExprId,
+    },
+    Literal {
+        token: Token<'src>,
+    },
+    Logical {
+        op: Token<'src>,
+        left: ExprId,
+        right: ExprId,
+    },
+    Unary {
+        op: Token<'src>,
+        right: ExprId,
+    },
+    Variable {
+        name: Token<'src>,
+    },
+    // fun (a, b) { ... } used as a value
+    Function(FunId),
+    // a value worked out ahead of time by the optimizer, in place of whatever computed it
+    Constant {
+        value: Constant,
+        line: usize,
+    },
 }
 
+// the subset of values that can be known before running anything
 #[derive(Clone, Debug, PartialEq)]
-pub struct Expr {
-    pub etype: ExprType,
-    pub token: Token,
-    pub children: Vec<Expr>,
+pub enum Constant {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Int(i64),
+    BigInt(BigInt),
+    String(Rc<str>),
 }
 
-impl fmt::Display for Expr {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut buf = String::new();
-        buf.push_str(&format!("type:{:?} children:", self.etype));
-        for c in &self.children {
-            buf.push_str(&format!("{}", c));
+impl Constant {
+    pub fn from_value(val: &Value) -> Option<Constant> {
+        match val {
+            Value::Nil => Some(Constant::Nil),
+            Value::Bool(b) => Some(Constant::Bool(*b)),
+            Value::Number(n) => Some(Constant::Number(*n)),
+            Value::Int(n) => Some(Constant::Int(*n)),
+            Value::BigInt(n) => Some(Constant::BigInt(n.clone())),
+            Value::String(s) => Some(Constant::String(s.clone())),
+            _ => None,
         }
-        write!(f, "{}", buf)
-    }
-}
-
-impl Expr {
-    pub fn new_assign(token: Token, val: Expr) -> Expr {
-        let e = Expr {
-            etype: ExprType::Assign,
-            token: token,
-            children: vec![val],
-        };
-        e
-    }
-
-    pub fn new_binary(token: Token, left: Expr, right: Expr) -> Expr {
-        let e = Expr {
-            etype: ExprType::Binary,
-            token: token,
-            children: vec![left, right],
-        };
-        e
     }
 
-    pub fn new_unary(token: Token, node: Expr) -> Expr {
-        let e = Expr {
-            etype: ExprType::Unary,
-            token: token,
-            children: vec![node],
-        };
-        e
-    }
-
-    pub fn new_grouping(expr: Expr) -> Expr {
-        let e = Expr {
-            etype: ExprType::Grouping,
-            // hack in a token type because I really don't want to deal with
-            // token nullability in however many places these things appear
-            token: Token::new(TokenType::EOF, "", 0),
-            children: vec![expr],
-        };
-        e
-    }
-
-    pub fn new_literal(token: Token) -> Expr {
-        let e = Expr {
-            etype: ExprType::Literal,
-            token: token,
-            children: vec![],
-        };
-        e
-    }
-
-    pub fn new_or(left: Expr, token: Token, right: Expr) -> Expr {
-        let e = Expr {
-            etype: ExprType::Logical,
-            token: token,
-            children: vec![left, right],
-        };
-        e
-    }
-
-    pub fn new_and(left: Expr, token: Token, right: Expr) -> Expr {
-        let e = Expr {
-            etype: ExprType::Logical,
-            token: token,
-            children: vec![left, right],
-        };
-        e
-    }
-
-    #[allow(dead_code)]
-    pub fn new_var_init(token: Token, initializer: Expr) -> Expr {
-        let e = Expr {
-            etype: ExprType::Variable,
-            token: token,
-            children: vec![initializer],
-        };
-        e
-    }
-
-    pub fn new_var(token: Token) -> Expr {
-        let e = Expr {
-            etype: ExprType::Variable,
-            token: token,
-            children: vec![],
-        };
-        e
-    }
-
-    pub fn new_call(_callee: &Expr, token: Token, args: Vec<Expr>) -> Expr {
-        let e = Expr {
-            etype: ExprType::Call,
-            token: token,
-            children: args,
-        };
-        e
+    pub fn to_value(&self) -> Value {
+        match self {
+            Constant::Nil => Value::Nil,
+            Constant::Bool(b) => Value::Bool(*b),
+            Constant::Number(n) => Value::Number(*n),
+            Constant::Int(n) => Value::Int(*n),
+            Constant::BigInt(n) => Value::BigInt(n.clone()),
+            Constant::String(s) => Value::String(s.clone()),
+        }
     }
 }
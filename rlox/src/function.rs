@@ -1,24 +1,71 @@
-use crate::expr::*;
+use crate::ast::*;
+use crate::environment::*;
 use crate::interpreter::*;
-use crate::stmt::*;
 use crate::value::*;
+use std::fmt;
+use std::rc::Rc;
 
-#[derive(Clone, Debug, PartialEq)]
+// the declaration itself stays in the Ast, values just carry its id plus enough to describe it
+#[derive(Clone)]
 pub struct LoxFunction {
-    pub decl: Vec<Stmt>,
+    pub fun: FunId,
+    pub name: String,
+    pub arity: usize,
+    // the scopes that were in view where the function was created, shared with the code that made
+    // it.  None for functions made at the top level, which only ever need globals
+    pub captured: Option<Rc<Vec<Scope>>>,
+}
+
+// a closure can end up in one of its own captured variables, so neither of these looks inside
+// them.  two closures are the same if they came from the same declaration in the same scope
+impl PartialEq for LoxFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.fun == other.fun
+            && match (&self.captured, &other.captured) {
+                (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+                (a, b) => a.is_none() && b.is_none(),
+            }
+    }
+}
+
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LoxFunction")
+            .field("fun", &self.fun)
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .field(
+                "captured",
+                &self.captured.as_ref().map(|scopes| {
+                    scopes
+                        .iter()
+                        .flat_map(|s| s.borrow().keys().cloned().collect::<Vec<_>>())
+                        .collect::<Vec<_>>()
+                }),
+            )
+            .finish()
+    }
 }
 
 impl LoxFunction {
-    pub fn new(stmt: &Stmt) -> Self {
+    pub fn new(fun: FunId, name: &str, arity: usize, captured: Option<Vec<Scope>>) -> Self {
         Self {
-            decl: vec![stmt.clone()],
+            fun,
+            name: name.to_string(),
+            arity,
+            captured: captured.map(Rc::new),
         }
     }
 }
 
-impl Callable for LoxFunction {
-    fn arity() -> u8 {
-        0
+// on the Rc rather than the function itself so call_function can hand out more references to it
+impl Callable for Rc<LoxFunction> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
     }
 
     // fn(x, y);
@@ -26,28 +73,75 @@ impl Callable for LoxFunction {
     //
     // fn(1, 2) -> 3
     //
-    fn call(&self, interpreter: &mut Interpreter, args: Vec<Expr>) -> Value {
-        let mut env = interpreter.new_function_env();
-        for i in 0..args.len() {
-            let arg = &args[i];
-            let res = interpreter.eval(arg);
-            match res {
-                Ok(val) => {
-                    env.define(&arg.token.lexeme, val);
-                }
-                Err(_) => {
-                    return Value::Nil;
-                }
-            }
+    fn call(&self, interpreter: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+        interpreter.call_function(self, args)
+    }
+}
+
+pub type NativeFn = fn(&mut Interpreter, &[Value]) -> InterpreterResult;
+
+// functions implemented on the rust side, like clock()
+#[derive(Clone, Debug)]
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub func: NativeFn,
+    // reaches outside the interpreter (clock, filesystem, ...), see Limits::host_natives
+    pub host: bool,
+    // set for methods pulled off a value, like gen.next.  handed to func ahead of the args
+    pub this: Option<Box<Value>>,
+}
+
+impl NativeFunction {
+    pub fn new(name: &str, arity: usize, func: NativeFn) -> Self {
+        Self {
+            name: name.to_string(),
+            arity,
+            func,
+            host: false,
+            this: None,
         }
+    }
+
+    pub fn bind(&self, this: Value) -> Self {
+        Self {
+            this: Some(Box::new(this)),
+            ..self.clone()
+        }
+    }
+
+    pub fn host(name: &str, arity: usize, func: NativeFn) -> Self {
+        Self {
+            host: true,
+            ..Self::new(name, arity, func)
+        }
+    }
+}
+
+// comparing fn pointers is unreliable, natives are unique by name anyhow
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Callable for NativeFunction {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, mut args: Vec<Value>) -> InterpreterResult {
+        if let Some(this) = &self.this {
+            args.insert(0, (**this).clone());
+        }
+        (self.func)(interpreter, &args)
+    }
 
-        let _res = interpreter.eval_function_block(&self.decl, &mut env);
-        // let val: Value;
-        // match res {
-        //     Ok(v) => val = v,
-        //     Err(_) => val = Value::Nil,
-        // }
-        // todo: no return values yet
-        Value::Nil
+    fn is_native(&self) -> bool {
+        true
     }
 }
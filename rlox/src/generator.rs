@@ -0,0 +1,114 @@
+use crate::ast::*;
+use crate::environment::*;
+use crate::error::*;
+use crate::value::*;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+// where a suspended generator is inside its body.  eval_stmt keeps its place on the rust stack,
+// which can't be set aside halfway through, so generator bodies run off an explicit stack of
+// these instead (see Interpreter::resume).  statements that can't hold a yield still go through
+// eval_stmt as usual
+#[derive(Debug)]
+pub enum Cursor {
+    // a run of statements.  scoped if a scope got pushed for it that needs popping at the end
+    Block {
+        stmts: Vec<StmtId>,
+        pos: usize,
+        scoped: bool,
+    },
+    // a While or Loop statement, the condition gets rechecked every time we come back around
+    Loop(StmtId),
+    ForIn {
+        stmt: StmtId,
+        gen: GeneratorRef,
+    },
+    Try {
+        stmt: StmtId,
+        stage: TryStage,
+        // whatever was unwinding when finally started, raised again once it's done
+        pending: Option<RuntimeError>,
+    },
+}
+
+impl Cursor {
+    pub fn block(stmts: Vec<StmtId>, scoped: bool) -> Self {
+        Cursor::Block {
+            stmts,
+            pos: 0,
+            scoped,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TryStage {
+    Body,
+    Catch,
+    Finally,
+}
+
+// a generator's locals plus where it left off
+#[derive(Debug)]
+pub struct Suspension {
+    pub scopes: SavedScopes,
+    pub stack: Vec<Cursor>,
+}
+
+#[derive(Debug)]
+pub enum State {
+    Suspended(Suspension),
+    // the frame is checked out while the body runs, asking for another value now is an error
+    Running,
+    // no body at all, just values worked out up front, like what args() hands back
+    Values(std::vec::IntoIter<Value>),
+    Done,
+}
+
+#[derive(Debug)]
+pub struct Generator {
+    pub name: String,
+    pub state: State,
+    // pulled ahead of time to answer `done`, handed out by the next call to next()
+    pub peeked: Option<Value>,
+}
+
+// generators are shared, not copied.  two values are equal only if they're the same generator
+#[derive(Clone, Debug)]
+pub struct GeneratorRef(pub Rc<RefCell<Generator>>);
+
+impl GeneratorRef {
+    pub fn new(name: &str, scopes: SavedScopes, body: &[StmtId]) -> Self {
+        GeneratorRef(Rc::new(RefCell::new(Generator {
+            name: name.to_string(),
+            state: State::Suspended(Suspension {
+                scopes,
+                stack: vec![Cursor::block(body.to_vec(), false)],
+            }),
+            peeked: None,
+        })))
+    }
+}
+
+impl GeneratorRef {
+    pub fn of_values(name: &str, values: Vec<Value>) -> Self {
+        GeneratorRef(Rc::new(RefCell::new(Generator {
+            name: name.to_string(),
+            state: State::Values(values.into_iter()),
+            peeked: None,
+        })))
+    }
+}
+
+impl PartialEq for GeneratorRef {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Display for GeneratorRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "generator {}", self.0.borrow().name)
+    }
+}
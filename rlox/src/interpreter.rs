@@ -1,57 +1,158 @@
+use crate::ast::*;
+use crate::bigint::*;
+use crate::coverage::*;
 use crate::environment::*;
 use crate::error::*;
 use crate::expr::*;
 use crate::function::*;
+use crate::generator::*;
+use crate::limits::*;
 use crate::lox::*;
+use crate::number::{self, is_number};
+use crate::profiler::*;
 use crate::stmt::*;
 use crate::token::*;
 use crate::token_type::*;
 use crate::value::*;
 
-use std::mem;
+use std::cmp::Ordering;
+use std::io::{self, Write};
+use std::rc::Rc;
 use std::time::SystemTime;
 
 pub type InterpreterResult = Result<Value, RuntimeError>;
 pub type ExecuteResult = Result<(), RuntimeError>;
 
-#[derive(Debug)]
-pub struct Interpreter {
+pub struct Interpreter<'a> {
+    ast: &'a Ast<'a>,
     env: Environment,
+    out: Box<dyn Write>,
+    meter: Meter,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+    // line of the statement that started last, for natives that need to blame somewhere
+    line: usize,
+    // whether a `return f(x)` running now can leave the call to our caller.  only in plain
+    // function bodies, outside of any try: the call has to happen before a finally block runs,
+    // and whatever it throws has to be catchable
+    tail_calls: bool,
+    // whatever followed the script on the command line, see args()
+    args: Vec<String>,
 }
 
 pub trait Callable {
-    fn arity() -> u8;
-    fn call(&self, interpreter: &mut Interpreter, args: Vec<Expr>) -> Value;
-}
+    fn name(&self) -> &str;
+    fn arity(&self) -> usize;
+    fn call(&self, interpreter: &mut Interpreter, args: Vec<Value>) -> InterpreterResult;
 
-struct Clock {}
+    fn is_native(&self) -> bool {
+        false
+    }
+}
 
-impl Callable for Clock {
-    fn arity() -> u8 {
-        0
+// gen.next(), bound with the generator as its first arg.  nil once it has nothing left
+fn generator_next(interpreter: &mut Interpreter, args: &[Value]) -> InterpreterResult {
+    match &args[0] {
+        Value::Generator(gen) => {
+            let line = interpreter.line;
+            Ok(interpreter.next_value(gen, line)?.unwrap_or(Value::Nil))
+        }
+        other => Err(RuntimeError::new(
+            &format!("next() called on {}", other.type_name()),
+            interpreter.line,
+        )),
     }
-    fn call(&self, _: &mut Interpreter, _args: Vec<Expr>) -> Value {
-        Value::Number(
-            SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs() as f64,
-        )
+}
+
+fn clock(_: &mut Interpreter, _args: &[Value]) -> InterpreterResult {
+    Ok(Value::Number(
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as f64,
+    ))
+}
+
+// the script's command line arguments, one string at a time.  there are no lists, so it's a
+// generator: `for (arg in args()) ...`
+fn args(interpreter: &mut Interpreter, _args: &[Value]) -> InterpreterResult {
+    let args = interpreter
+        .args
+        .iter()
+        .map(|arg| Value::String(arg.as_str().into()))
+        .collect();
+    Ok(Value::Generator(GeneratorRef::of_values("args", args)))
+}
+
+// stops the script, finally blocks included, and has rlox exit with `code`
+fn exit(interpreter: &mut Interpreter, args: &[Value]) -> InterpreterResult {
+    match &args[0] {
+        Value::Int(code @ 0..=255) => Err(RuntimeError::exit(*code as i32, interpreter.line)),
+        _ => Err(RuntimeError::new(
+            "exit() expects a code from 0 to 255",
+            interpreter.line,
+        )),
     }
 }
 
-impl Interpreter {
-    pub fn new() -> Self {
+// everything predefined in the global scope.  static passes use this to know what's callable
+pub fn natives() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::host("clock", 0, clock),
+        NativeFunction::new("args", 0, args),
+        NativeFunction::new("exit", 1, exit),
+    ]
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(ast: &'a Ast<'a>) -> Self {
+        Self::with_output(ast, Box::new(io::stdout()))
+    }
+
+    // print statements go wherever `out` points, handy for capturing script output
+    pub fn with_output(ast: &'a Ast<'a>, out: Box<dyn Write>) -> Self {
         let mut env = Environment::new();
-        env.define(
-            "clock",
-            // lame hardcoded weird literal for now
-            Value::Function(LoxFunction::new(&Stmt::new_expr(&Expr::new_literal(
-                Token::new(TokenType::Number(1.0), "1.0", 0),
-            )))),
-        );
-        env.bump();
-        Interpreter { env: env }
+        for native in natives() {
+            env.define(&native.name.clone(), Value::Native(Rc::new(native)));
+        }
+        Interpreter {
+            ast,
+            env,
+            out,
+            meter: Meter::new(Limits::default()),
+            profiler: None,
+            coverage: None,
+            line: 0,
+            tail_calls: false,
+            args: vec![],
+        }
+    }
+
+    pub fn set_args(&mut self, args: &[String]) {
+        self.args = args.to_vec();
+    }
+
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(Coverage::new(self.ast));
+    }
+
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    pub fn enable_profiling(&mut self) {
+        self.profiler = Some(Profiler::new());
+    }
+
+    // hands back what was recorded so far, with every open frame closed out
+    pub fn take_profile(&mut self) -> Option<Profiler> {
+        let mut profiler = self.profiler.take()?;
+        profiler.finish();
+        Some(profiler)
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.meter = Meter::new(limits);
     }
 
     // environments basically form a graph structure.  for example, if we're a few blocks deep,
@@ -66,171 +167,666 @@ impl Interpreter {
     // |
     // --> fn2 ----> 1 -> 2
     //
-    // instead of dealing with Rc<RefCell> swap headaches, just expose a helper here that clones
-    // the environment only at the globals level.  it's less than efficient but it's a hack that
-    // buys me time while I continue the journey...
-    pub fn new_function_env(&self) -> Environment {
-        let mut env = self.env.clone();
-        env.reset();
-        env
+    // instead of dealing with Rc<RefCell> swap headaches, a function call just sets the caller's
+    // scopes aside so the body only sees globals (see Environment::enter_function).  it's less
+    // than complete but it's a hack that buys me time while I continue the journey...
+    //
+    // functions created below the top level share the scopes in view at that point (see
+    // LoxFunction::captured), so a closure and the function that made it see each other's
+    // assignments and declarations
+    pub fn interpret(&mut self, stmts: &[StmtId]) -> ExecuteResult {
+        let res = self.run(stmts);
+        match &res {
+            Err(RuntimeError {
+                kind: ErrorKind::Exit(_),
+                ..
+            }) => {}
+            Err(e) => Lox::runtime_error(e),
+            Ok(_) => {}
+        }
+        res
     }
 
-    pub fn interpret(&mut self, stmts: &Vec<Stmt>) -> ExecuteResult {
+    // same as interpret minus the error reporting
+    pub fn run(&mut self, stmts: &[StmtId]) -> ExecuteResult {
+        self.meter.restart();
         for stmt in stmts {
-            match self.execute(&stmt) {
-                Err(n) => {
-                    Lox::runtime_error(&n.msg);
-                    return Err(n);
-                }
-                _ => {}
-            }
+            self.execute(*stmt)?;
         }
         Ok(())
     }
 
-    pub fn execute(&mut self, stmt: &Stmt) -> ExecuteResult {
-        self.eval_stmt(&stmt)
+    pub fn execute(&mut self, stmt: StmtId) -> ExecuteResult {
+        self.eval_stmt(stmt)
+    }
+
+    pub fn eval_stmt(&mut self, id: StmtId) -> ExecuteResult {
+        self.trace(id)?;
+        self.exec_stmt(id)
+    }
+
+    // bookkeeping for every statement that starts, however it ends up being run
+    fn trace(&mut self, id: StmtId) -> ExecuteResult {
+        let line = self.ast.line(id);
+        self.line = line;
+        self.meter.step(line)?;
+        if let Some(profiler) = &mut self.profiler {
+            profiler.line(line);
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.hit(line);
+        }
+        Ok(())
     }
 
-    pub fn eval_stmt(&mut self, stmt: &Stmt) -> ExecuteResult {
-        match stmt {
+    fn exec_stmt(&mut self, id: StmtId) -> ExecuteResult {
+        let ast = self.ast;
+        match ast.stmt(id) {
             Stmt::Expr(expr) => {
-                let _ = self.eval(&expr)?;
+                let _ = self.eval(*expr)?;
             }
-            Stmt::Var(token, expr) => self.eval_var(&token, &expr)?,
+            Stmt::Var(token, _ty, expr) => self.eval_var(token, *expr)?,
             Stmt::Block(stmts) => {
+                // scope has to be popped even when unwinding, a catch further up keeps going
                 self.env.bump();
-                self.eval_block(stmts)?;
+                let res = self.eval_block(stmts);
                 self.env.debump();
+                res?;
+            }
+            Stmt::Print(expr) => self.eval_print(*expr)?,
+            Stmt::If(expr, then, els) => self.eval_if(*expr, *then, *els)?,
+            Stmt::While(cond, body) => self.eval_while(*cond, *body)?,
+            Stmt::Loop(body) => loop {
+                self.eval_stmt(*body)?;
+            },
+            Stmt::Function(fun) => {
+                let func = self.make_function(*fun)?;
+                self.env.define(ast.fun(*fun).name.lexeme, func);
             }
-            Stmt::Print(expr) => self.eval_print(&expr)?,
-            Stmt::If(expr, then, els) => self.eval_if(expr, then, els)?,
-            Stmt::While(cond, body) => self.eval_while(cond, body)?,
+            Stmt::Return(keyword, expr) => {
+                let mut val = Value::Nil;
+                if let Some(expr) = expr {
+                    if let (
+                        true,
+                        Expr::Call {
+                            callee,
+                            paren,
+                            args,
+                        },
+                    ) = (self.tail_calls, ast.expr(*expr))
+                    {
+                        return Err(self.eval_tail_call(*callee, paren, args)?);
+                    }
+                    val = self.eval(*expr)?;
+                }
+                return Err(RuntimeError::ret(val, keyword.line));
+            }
+            Stmt::Throw(keyword, expr) => {
+                let val = self.eval(*expr)?;
+                return Err(RuntimeError::throw(val, keyword.line));
+            }
+            Stmt::Try(body, catch, finally) => self.eval_try(*body, catch, *finally)?,
+            Stmt::ForIn(name, iter, body) => self.eval_for_in(name, *iter, *body)?,
+            // the parser only lets yield into function bodies, and those run through resume
+            Stmt::Yield(keyword, _) => {
+                return Err(RuntimeError::new(
+                    "can't yield outside a generator",
+                    keyword.line,
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn eval_for_in(&mut self, name: &Token, iter: ExprId, body: StmtId) -> ExecuteResult {
+        let gen = Self::generator(self.eval(iter)?, name.line)?;
+        while let Some(val) = self.next_value(&gen, name.line)? {
+            self.env.bump();
+            self.env.define(name.lexeme, val);
+            let res = self.eval_stmt(body);
+            self.env.debump();
+            res?;
         }
         Ok(())
     }
 
-    pub fn eval_if(
+    fn generator(val: Value, line: usize) -> Result<GeneratorRef, RuntimeError> {
+        match val {
+            Value::Generator(gen) => Ok(gen),
+            other => Err(RuntimeError::new(
+                &format!("can only loop over generators, not {}", other.type_name()),
+                line,
+            )),
+        }
+    }
+
+    // runs a generator up to its next yield.  None once the body is finished, for good
+    pub fn next_value(
         &mut self,
-        cond: &Expr,
-        then: &Box<Stmt>,
-        els: &Box<Option<Stmt>>,
-    ) -> ExecuteResult {
-        if Self::is_truthy(&self.eval(&cond)?) {
-            self.eval_stmt(&*then)?;
-        } else {
-            if let Some(stmt) = &**els {
-                self.eval_stmt(&stmt)?;
+        gen: &GeneratorRef,
+        line: usize,
+    ) -> Result<Option<Value>, RuntimeError> {
+        let (name, mut frame) = {
+            let mut g = gen.0.borrow_mut();
+            if let Some(val) = g.peeked.take() {
+                return Ok(Some(val));
+            }
+            match std::mem::replace(&mut g.state, State::Running) {
+                State::Suspended(frame) => (g.name.clone(), frame),
+                State::Values(mut vals) => {
+                    let val = vals.next();
+                    if val.is_some() {
+                        g.state = State::Values(vals);
+                    } else {
+                        g.state = State::Done;
+                    }
+                    return Ok(val);
+                }
+                State::Running => {
+                    return Err(RuntimeError::new(
+                        &format!("generator {}() is already running", g.name),
+                        line,
+                    ))
+                }
+                State::Done => {
+                    g.state = State::Done;
+                    return Ok(None);
+                }
+            }
+        };
+
+        let res = match self.meter.enter_call(line) {
+            Ok(()) => {
+                let res = self.resume(&mut frame);
+                self.meter.leave_call();
+                res
+            }
+            Err(e) => Err(e),
+        };
+        let mut g = gen.0.borrow_mut();
+        match res {
+            Ok(Some(val)) => {
+                g.state = State::Suspended(frame);
+                Ok(Some(val))
+            }
+            Ok(None) => {
+                g.state = State::Done;
+                Ok(None)
+            }
+            Err(mut e) => {
+                g.state = State::Done;
+                e.push_frame(&name, line);
+                Err(e)
             }
         }
+    }
+
+    fn resume(&mut self, frame: &mut Suspension) -> Result<Option<Value>, RuntimeError> {
+        self.env.swap_scopes(&mut frame.scopes);
+        // nothing would pick up a tail call at the other end of next()
+        let tail_calls = std::mem::replace(&mut self.tail_calls, false);
+        let res = self.run_cursors(&mut frame.stack);
+        self.tail_calls = tail_calls;
+        self.env.swap_scopes(&mut frame.scopes);
+        res
+    }
+
+    fn run_cursors(&mut self, stack: &mut Vec<Cursor>) -> Result<Option<Value>, RuntimeError> {
+        while !stack.is_empty() {
+            match self.step(stack) {
+                Ok(Some(val)) => return Ok(Some(val)),
+                Ok(None) => {}
+                Err(e) => {
+                    if let Err(e) = self.unwind(stack, e) {
+                        // falling off the end and returning both just mean the generator's done
+                        return match e.kind {
+                            ErrorKind::Return(_) => Ok(None),
+                            _ => Err(e),
+                        };
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    // moves the innermost cursor along by a statement.  Some when that statement was a yield
+    fn step(&mut self, stack: &mut Vec<Cursor>) -> Result<Option<Value>, RuntimeError> {
+        let ast = self.ast;
+        match stack.last_mut().unwrap() {
+            Cursor::Block { stmts, pos, scoped } => {
+                if *pos == stmts.len() {
+                    if *scoped {
+                        self.env.debump();
+                    }
+                    stack.pop();
+                    return Ok(None);
+                }
+                let id = stmts[*pos];
+                *pos += 1;
+                return self.start(stack, id);
+            }
+            Cursor::Loop(id) => {
+                let body = match ast.stmt(*id) {
+                    Stmt::While(cond, body) => {
+                        if !Self::is_truthy(&self.eval(*cond)?) {
+                            stack.pop();
+                            return Ok(None);
+                        }
+                        *body
+                    }
+                    Stmt::Loop(body) => *body,
+                    other => unreachable!("loop cursor on {:?}", other),
+                };
+                stack.push(Cursor::block(vec![body], false));
+            }
+            Cursor::ForIn { stmt, gen } => {
+                let (name, body) = match ast.stmt(*stmt) {
+                    Stmt::ForIn(name, _, body) => (name, *body),
+                    other => unreachable!("for-in cursor on {:?}", other),
+                };
+                let gen = gen.clone();
+                match self.next_value(&gen, name.line)? {
+                    Some(val) => {
+                        self.env.bump();
+                        self.env.define(name.lexeme, val);
+                        stack.push(Cursor::block(vec![body], true));
+                    }
+                    None => {
+                        stack.pop();
+                    }
+                }
+            }
+            // whichever part of the try was running finished normally
+            Cursor::Try {
+                stmt,
+                stage,
+                pending,
+            } => {
+                let finally = match ast.stmt(*stmt) {
+                    Stmt::Try(_, _, finally) => *finally,
+                    other => unreachable!("try cursor on {:?}", other),
+                };
+                match (*stage, finally) {
+                    (TryStage::Body, Some(block)) | (TryStage::Catch, Some(block)) => {
+                        *stage = TryStage::Finally;
+                        stack.push(Cursor::block(vec![block], false));
+                    }
+                    _ => {
+                        let pending = pending.take();
+                        stack.pop();
+                        if let Some(e) = pending {
+                            return Err(e);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    // the generator version of eval_stmt.  anything that could have a yield inside gets a
+    // cursor pushed for it, everything else just runs
+    fn start(
+        &mut self,
+        stack: &mut Vec<Cursor>,
+        id: StmtId,
+    ) -> Result<Option<Value>, RuntimeError> {
+        let ast = self.ast;
+        self.trace(id)?;
+        match ast.stmt(id) {
+            Stmt::Block(stmts) => {
+                self.env.bump();
+                stack.push(Cursor::block(stmts.clone(), true));
+            }
+            Stmt::If(cond, then, els) => {
+                let branch = match Self::is_truthy(&self.eval(*cond)?) {
+                    true => Some(*then),
+                    false => *els,
+                };
+                if let Some(branch) = branch {
+                    stack.push(Cursor::block(vec![branch], false));
+                }
+            }
+            Stmt::While(..) | Stmt::Loop(..) => stack.push(Cursor::Loop(id)),
+            Stmt::ForIn(name, iter, _) => {
+                let gen = Self::generator(self.eval(*iter)?, name.line)?;
+                stack.push(Cursor::ForIn { stmt: id, gen });
+            }
+            Stmt::Try(body, ..) => {
+                stack.push(Cursor::Try {
+                    stmt: id,
+                    stage: TryStage::Body,
+                    pending: None,
+                });
+                stack.push(Cursor::block(vec![*body], false));
+            }
+            Stmt::Yield(_, val) => {
+                let val = match val {
+                    Some(expr) => self.eval(*expr)?,
+                    None => Value::Nil,
+                };
+                return Ok(Some(val));
+            }
+            _ => self.exec_stmt(id)?,
+        }
+        Ok(None)
+    }
+
+    // pops cursors until a try wants to deal with the error.  hands the error back if nothing
+    // in the generator did
+    fn unwind(&mut self, stack: &mut Vec<Cursor>, e: RuntimeError) -> ExecuteResult {
+        let ast = self.ast;
+        while let Some(cursor) = stack.last_mut() {
+            match cursor {
+                Cursor::Block { scoped: true, .. } => self.env.debump(),
+                Cursor::Try {
+                    stmt,
+                    stage,
+                    pending,
+                } => {
+                    let (catch, finally) = match ast.stmt(*stmt) {
+                        Stmt::Try(_, catch, finally) => (catch, *finally),
+                        other => unreachable!("try cursor on {:?}", other),
+                    };
+                    if let (TryStage::Body, Some((name, block))) = (*stage, catch) {
+                        if e.is_catchable() {
+                            *stage = TryStage::Catch;
+                            self.env.bump();
+                            self.env.define(name.lexeme, e.value());
+                            stack.push(Cursor::block(vec![*block], true));
+                            return Ok(());
+                        }
+                    }
+                    if let (TryStage::Body | TryStage::Catch, Some(block)) = (*stage, finally) {
+                        *stage = TryStage::Finally;
+                        *pending = Some(e);
+                        stack.push(Cursor::block(vec![block], false));
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+            stack.pop();
+        }
+        Err(e)
+    }
+
+    pub fn eval_if(&mut self, cond: ExprId, then: StmtId, els: Option<StmtId>) -> ExecuteResult {
+        if Self::is_truthy(&self.eval(cond)?) {
+            self.eval_stmt(then)?;
+        } else if let Some(stmt) = els {
+            self.eval_stmt(stmt)?;
+        }
         Ok(())
     }
 
-    pub fn eval_while(&mut self, cond: &Expr, body: &Box<Stmt>) -> ExecuteResult {
-        while Self::is_truthy(&self.eval(&cond)?) {
+    pub fn eval_try(
+        &mut self,
+        body: StmtId,
+        catch: &Option<(Token, StmtId)>,
+        finally: Option<StmtId>,
+    ) -> ExecuteResult {
+        let tail_calls = std::mem::replace(&mut self.tail_calls, false);
+        let res = self.eval_try_blocks(body, catch, finally);
+        self.tail_calls = tail_calls;
+        res
+    }
+
+    fn eval_try_blocks(
+        &mut self,
+        body: StmtId,
+        catch: &Option<(Token, StmtId)>,
+        finally: Option<StmtId>,
+    ) -> ExecuteResult {
+        let mut res = self.eval_stmt(body);
+
+        if let (Err(e), Some((name, block))) = (&res, catch) {
+            if e.is_catchable() {
+                let val = e.value();
+                self.env.bump();
+                self.env.define(name.lexeme, val);
+                let caught = self.eval_stmt(*block);
+                self.env.debump();
+                res = caught;
+            }
+        }
+
+        // finally always runs.  if it blows up itself, that error wins over whatever was
+        // already in flight
+        if let Some(block) = finally {
+            self.eval_stmt(block)?;
+        }
+        res
+    }
+
+    pub fn eval_while(&mut self, cond: ExprId, body: StmtId) -> ExecuteResult {
+        while Self::is_truthy(&self.eval(cond)?) {
             self.eval_stmt(body)?;
         }
         Ok(())
     }
 
-    pub fn eval_block(&mut self, stmts: &Vec<Stmt>) -> ExecuteResult {
+    pub fn eval_block(&mut self, stmts: &[StmtId]) -> ExecuteResult {
         for stmt in stmts {
-            self.execute(&stmt)?;
+            self.execute(*stmt)?;
         }
         Ok(())
     }
 
+    fn make_function(&mut self, fun: FunId) -> InterpreterResult {
+        let decl = self.ast.fun(fun);
+        let captured = match self.env.depth() {
+            0 => None,
+            _ => Some(self.env.capture()),
+        };
+        if let Some(captured) = &captured {
+            self.meter
+                .alloc(captured.len() * size_of::<Scope>(), decl.name.line)?;
+        }
+        Ok(Value::Function(Rc::new(LoxFunction::new(
+            fun,
+            decl.label(),
+            decl.params.len(),
+            captured,
+        ))))
+    }
+
+    pub fn call_function(&mut self, func: &Rc<LoxFunction>, args: Vec<Value>) -> InterpreterResult {
+        let decl = self.ast.fun(func.fun);
+        let enclosing = func.captured.as_deref().map_or(&[][..], |c| c.as_slice());
+        let bindings: Vec<_> = decl
+            .params
+            .iter()
+            .map(|p| p.lexeme)
+            .zip(args.into_iter().map(slot))
+            .collect();
+
+        // generators set up their locals and then wait for someone to ask for a value
+        if decl.generator {
+            let mut scopes = SavedScopes::new(enclosing);
+            self.env.swap_scopes(&mut scopes);
+            for (name, slot) in bindings {
+                self.env.define_slot(name, slot);
+            }
+            self.env.swap_scopes(&mut scopes);
+            return Ok(Value::Generator(GeneratorRef::new(
+                &func.name, scopes, &decl.body,
+            )));
+        }
+        self.eval_function_block(&decl.body, enclosing, bindings)
+    }
+
     pub fn eval_function_block(
         &mut self,
-        stmts: &Vec<Stmt>,
-        env: &mut Environment,
-    ) -> ExecuteResult {
-        mem::swap(&mut self.env, env);
-        for stmt in stmts {
-            self.execute(&stmt)?;
+        stmts: &[StmtId],
+        enclosing: &[Scope],
+        args: Vec<(&str, Slot)>,
+    ) -> InterpreterResult {
+        let saved = self.env.enter_function(enclosing);
+        for (name, slot) in args {
+            self.env.define_slot(name, slot);
+        }
+        let tail_calls = std::mem::replace(&mut self.tail_calls, true);
+        let res = self.eval_block(stmts);
+        self.tail_calls = tail_calls;
+        self.env.leave_function(saved);
+
+        match res {
+            Ok(()) => Ok(Value::Nil),
+            Err(RuntimeError {
+                kind: ErrorKind::Return(val),
+                ..
+            }) => Ok(*val),
+            Err(e) => Err(e),
         }
-        mem::swap(&mut self.env, env);
-        Ok(())
     }
 
-    pub fn eval(&mut self, expr: &Expr) -> InterpreterResult {
-        match expr.etype {
-            ExprType::Grouping => return self.eval(&expr.children[0]),
-            ExprType::Assign => return self.eval_assign(&expr),
-            ExprType::Literal => return self.eval_literal(&expr),
-            ExprType::Binary => return self.eval_binary(&expr),
-            ExprType::Unary => return self.eval_unary(&expr),
-            ExprType::Call => return self.eval_call(&expr),
-            ExprType::Variable => {
-                let name = &expr.token.lexeme;
-                match self.env.get(name, expr.token.line) {
-                    Ok(v) => return Ok(v),
-                    Err(e) => return Err(RuntimeError::new(&e.msg, expr.token.line)),
+    pub fn eval(&mut self, id: ExprId) -> InterpreterResult {
+        let ast = self.ast;
+        match ast.expr(id) {
+            Expr::Grouping { expr } => self.eval(*expr),
+            Expr::Assign { name, value } => self.eval_assign(name, *value),
+            Expr::Literal { token } => self.eval_literal(token),
+            Expr::Binary { op, left, right } => self.eval_binary(op, *left, *right),
+            Expr::Unary { op, right } => self.eval_unary(op, *right),
+            Expr::Call {
+                callee,
+                paren,
+                args,
+            } => self.eval_call(*callee, paren, args),
+            Expr::Get { object, name } => self.eval_get(*object, name),
+            Expr::Variable { name } => match self.env.get(name.lexeme, name.line) {
+                Ok(v) => Ok(v),
+                Err(e) => Err(RuntimeError::new(&e.msg, name.line)),
+            },
+            Expr::Logical { op, left, right } => self.eval_logical(op, *left, *right),
+            Expr::Function(fun) => self.make_function(*fun),
+            Expr::Constant { value, line } => {
+                if let Constant::String(s) = value {
+                    self.meter.alloc(s.len(), *line)?;
                 }
+                Ok(value.to_value())
             }
-            ExprType::Logical => return self.eval_logical(&expr),
         }
     }
 
-    fn eval_assign(&mut self, expr: &Expr) -> InterpreterResult {
-        let val = self.eval(&expr.children[0])?;
-        self.env
-            .assign(&expr.token.lexeme, val.clone(), expr.token.line)?;
+    fn eval_assign(&mut self, name: &Token, value: ExprId) -> InterpreterResult {
+        let val = self.eval(value)?;
+        self.env.assign(name.lexeme, val.clone(), name.line)?;
         Ok(val)
     }
 
-    fn eval_literal(&self, expr: &Expr) -> InterpreterResult {
-        match &expr.token.ttype {
-            TokenType::String(s) => return Ok(Value::String(s.to_string())),
-            TokenType::Number(n) => return Ok(Value::Number(*n)),
-            TokenType::True => return Ok(Value::Bool(true)),
-            TokenType::False => return Ok(Value::Bool(false)),
-            TokenType::Nil => return Ok(Value::Nil),
-            _ => {
-                return Err(RuntimeError::new(
-                    &format!("unhandled literal {:?}", expr.token.lexeme),
-                    expr.token.line,
-                ))
+    fn eval_literal(&mut self, token: &Token) -> InterpreterResult {
+        match &token.ttype {
+            TokenType::String(s) => {
+                self.meter.alloc(s.len(), token.line)?;
+                Ok(Value::String(Rc::from(*s)))
+            }
+            TokenType::Number(n) => Ok(Value::Number(*n)),
+            TokenType::Integer(n) => Ok(Value::Int(*n)),
+            TokenType::BigInteger => {
+                self.meter.alloc(token.lexeme.len(), token.line)?;
+                // the scanner only hands out digits, but a loaded ast could say anything
+                match BigInt::parse(token.lexeme) {
+                    Some(n) => Ok(Value::from_bigint(n)),
+                    None => Err(RuntimeError::new(
+                        &format!("bad integer literal {:?}", token.lexeme),
+                        token.line,
+                    )),
+                }
             }
+            TokenType::True => Ok(Value::Bool(true)),
+            TokenType::False => Ok(Value::Bool(false)),
+            TokenType::Nil => Ok(Value::Nil),
+            _ => Err(RuntimeError::new(
+                &format!("unhandled literal {:?}", token.lexeme),
+                token.line,
+            )),
         }
     }
 
-    fn eval_binary(&mut self, expr: &Expr) -> InterpreterResult {
-        let left = self.eval(&expr.children[0])?;
-        let right = self.eval(&expr.children[1])?;
-        if let (Value::Number(ln), Value::Number(rn)) = (&left, &right) {
-            match expr.token.ttype {
-                TokenType::Minus => return Ok(Value::Number(ln - rn)),
-                TokenType::Plus => return Ok(Value::Number(ln + rn)),
-                TokenType::Slash => return Ok(Value::Number(ln / rn)),
-                TokenType::Star => return Ok(Value::Number(ln * rn)),
+    fn eval_binary(&mut self, op: &Token, left: ExprId, right: ExprId) -> InterpreterResult {
+        let left = self.eval(left)?;
+        let right = self.eval(right)?;
+        if let (TokenType::Plus, Value::String(ls), Value::String(rs)) = (op.ttype, &left, &right) {
+            self.meter.alloc(ls.len() + rs.len(), op.line)?;
+        }
+        // bigint arithmetic is charged up front, a big enough multiply would blow well past the
+        // time limit before the meter got another look
+        let charged = number::big_result_size(op.ttype, &left, &right);
+        if let Some(size) = charged {
+            self.meter.alloc(size, op.line)?;
+        }
+        let val = Self::binary_op(op, left, right)?;
+        if let (None, Value::BigInt(n)) = (charged, &val) {
+            self.meter.alloc(n.size(), op.line)?;
+        }
+        Ok(val)
+    }
 
-                TokenType::Greater => return Ok(Value::Bool(ln > rn)),
-                TokenType::GreaterEqual => return Ok(Value::Bool(ln >= rn)),
-                TokenType::Less => return Ok(Value::Bool(ln < rn)),
-                TokenType::LessEqual => return Ok(Value::Bool(ln <= rn)),
-                _ => {
+    // the operator itself once both sides are known.  shared with constant folding so the two
+    // can't disagree on what an expression means
+    pub fn binary_op(op: &Token, left: Value, right: Value) -> InterpreterResult {
+        match op.ttype {
+            TokenType::EqualEqual => return Self::is_equal(&left, &right),
+            TokenType::BangEqual => {
+                return Ok(Value::Bool(!Self::is_truthy(&Self::is_equal(
+                    &left, &right,
+                )?)))
+            }
+            _ => {}
+        }
+        if is_number(&left) && is_number(&right) {
+            // see number.rs for how ints, bigints and floats mix
+            let ord = number::compare(&left, &right);
+            match op.ttype {
+                TokenType::Greater => return Ok(Value::Bool(ord == Some(Ordering::Greater))),
+                TokenType::GreaterEqual => {
+                    return Ok(Value::Bool(matches!(
+                        ord,
+                        Some(Ordering::Greater | Ordering::Equal)
+                    )))
+                }
+                TokenType::Less => return Ok(Value::Bool(ord == Some(Ordering::Less))),
+                TokenType::LessEqual => {
+                    return Ok(Value::Bool(matches!(
+                        ord,
+                        Some(Ordering::Less | Ordering::Equal)
+                    )))
+                }
+                _ => {}
+            }
+            match number::arith(op.ttype, &left, &right) {
+                Some(Ok(val)) => return Ok(val),
+                Some(Err(msg)) => return Err(RuntimeError::new(&msg, op.line)),
+                None => {
                     return Err(RuntimeError::new(
                         &format!(
                             "unexpected operator {} for binary arguments {:?} and {:?}",
-                            expr.token.lexeme, left, right
+                            op.lexeme, left, right
                         ),
-                        expr.token.line,
+                        op.line,
                     ))
                 }
             }
         }
 
         if let (Value::String(ls), Value::String(rs)) = (&left, &right) {
-            match expr.token.ttype {
-                TokenType::Plus => return Ok(Value::String(format!("{}{}", ls, rs))),
+            match op.ttype {
+                TokenType::Plus => {
+                    let mut s = String::with_capacity(ls.len() + rs.len());
+                    s.push_str(ls);
+                    s.push_str(rs);
+                    return Ok(Value::String(s.into()));
+                }
                 _ => {
                     return Err(RuntimeError::new(
                         &format!(
                             "unexpected operator {} for string arguments {:?} and {:?}",
-                            expr.token.lexeme, &ls, &rs
+                            op.lexeme, &ls, &rs
                         ),
-                        expr.token.line,
+                        op.line,
                     ))
                 }
             }
@@ -239,24 +835,171 @@ impl Interpreter {
         Err(RuntimeError::new(
             &format!(
                 "unexpected binary arguments {:?} and {:?} for operator {}",
-                left, right, expr.token.lexeme
+                left, right, op.lexeme
             ),
-            expr.token.line,
+            op.line,
         ))
     }
 
-    fn eval_call(&mut self, expr: &Expr) -> InterpreterResult {
-        let func = LoxFunction::new(&Stmt::new_expr(expr));
-        if func.decl.len() > 0 {
+    fn eval_call(
+        &mut self,
+        callee: ExprId,
+        paren: &Token,
+        arg_exprs: &[ExprId],
+    ) -> InterpreterResult {
+        let callee = self.eval(callee)?;
+        let mut args = vec![];
+        for arg in arg_exprs {
+            args.push(self.eval(*arg)?);
+        }
+        self.call_value(callee, args, paren.line)
+    }
+
+    // the call in `return f(x)`, evaluated up to the point of actually calling.  a lox function
+    // comes back as a TailCall for the caller's call() to make in our place, anything else just
+    // gets called here and returned like usual
+    fn eval_tail_call(
+        &mut self,
+        callee: ExprId,
+        paren: &Token,
+        arg_exprs: &[ExprId],
+    ) -> Result<RuntimeError, RuntimeError> {
+        let callee = self.eval(callee)?;
+        let mut args = vec![];
+        for arg in arg_exprs {
+            args.push(self.eval(*arg)?);
+        }
+        match callee {
+            Value::Function(func) if !self.ast.fun(func.fun).generator => {
+                Ok(RuntimeError::tail_call(func, args, paren.line))
+            }
+            callee => {
+                let val = self.call_value(callee, args, paren.line)?;
+                Ok(RuntimeError::ret(val, paren.line))
+            }
+        }
+    }
+
+    fn call_value(&mut self, callee: Value, args: Vec<Value>, line: usize) -> InterpreterResult {
+        match callee {
+            Value::Function(func) => self.call(&func, args, line),
+            Value::Native(func) => {
+                if func.host && !self.meter.limits.host_natives {
+                    return Err(RuntimeError::new(
+                        &format!("{}() is not available in this sandbox", func.name),
+                        line,
+                    ));
+                }
+                self.call(func.as_ref(), args, line)
+            }
+            _ => Err(RuntimeError::new(
+                &format!("can only call functions, not {}", callee.type_name()),
+                line,
+            )),
         }
-        todo!();
     }
 
-    fn eval_logical(&mut self, expr: &Expr) -> InterpreterResult {
-        let left = self.eval(&expr.children[0])?;
+    fn call(&mut self, callee: &dyn Callable, args: Vec<Value>, line: usize) -> InterpreterResult {
+        Self::check_arity(callee, &args, line)?;
+        let mut res = self.call_frame(callee, args, line);
+
+        // the body ended in `return f(x)`.  make that call from here, in the frame we just
+        // left, so recursing this way doesn't go any deeper.  the trace then skips straight from
+        // whatever fails to our caller, same as the stack
+        let mut last: Option<Rc<LoxFunction>> = None;
+        while let Err(RuntimeError {
+            kind: ErrorKind::TailCall(call),
+            line: tail_line,
+            ..
+        }) = res
+        {
+            let (func, args) = *call;
+            if let Err(mut e) = Self::check_arity(&func, &args, tail_line) {
+                e.push_frame(last.as_ref().map_or(callee.name(), |f| f.name()), line);
+                return Err(e);
+            }
+            res = self.call_frame(&func, args, line);
+            last = Some(func);
+        }
+        res
+    }
+
+    fn check_arity(callee: &dyn Callable, args: &[Value], line: usize) -> ExecuteResult {
+        if args.len() != callee.arity() {
+            return Err(RuntimeError::new(
+                &format!(
+                    "{}() expects {} args but got {}",
+                    callee.name(),
+                    callee.arity(),
+                    args.len()
+                ),
+                line,
+            ));
+        }
+        Ok(())
+    }
+
+    fn call_frame(
+        &mut self,
+        callee: &dyn Callable,
+        args: Vec<Value>,
+        line: usize,
+    ) -> InterpreterResult {
+        // record the frame on the way out so uncaught errors can print a lox stack trace
+        self.meter.enter_call(line)?;
+        if let Some(profiler) = &mut self.profiler {
+            profiler.enter(callee.name(), callee.is_native());
+        }
+        let res = callee.call(self, args).map_err(|mut e| {
+            e.push_frame(callee.name(), line);
+            e
+        });
+        if let Some(profiler) = &mut self.profiler {
+            profiler.leave();
+        }
+        self.meter.leave_call();
+        res
+    }
+
+    // error objects are the only thing with properties so far
+    fn eval_get(&mut self, object: ExprId, name: &Token) -> InterpreterResult {
+        let object = self.eval(object)?;
+        if let Value::Error(err) = &object {
+            match name.lexeme {
+                "message" => return Ok(Value::String(err.msg.as_str().into())),
+                "line" => return Ok(Value::Int(err.line as i64)),
+                _ => {}
+            }
+        }
+        if let Value::Generator(gen) = &object {
+            match name.lexeme {
+                "next" => {
+                    let next = NativeFunction::new("next", 0, generator_next);
+                    return Ok(Value::Native(Rc::new(next.bind(object.clone()))));
+                }
+                // finding out means running ahead to the next yield, which next() then returns
+                "done" => {
+                    if gen.0.borrow().peeked.is_none() {
+                        let peeked = self.next_value(gen, name.line)?;
+                        gen.0.borrow_mut().peeked = peeked;
+                    }
+                    return Ok(Value::Bool(gen.0.borrow().peeked.is_none()));
+                }
+                _ => {}
+            }
+        }
+
+        Err(RuntimeError::new(
+            &format!("undefined property '{}' on {}", name.lexeme, object),
+            name.line,
+        ))
+    }
+
+    fn eval_logical(&mut self, op: &Token, left: ExprId, right: ExprId) -> InterpreterResult {
+        let left = self.eval(left)?;
 
         // short-circuit behaviors
-        if expr.token.ttype == TokenType::Or {
+        if op.ttype == TokenType::Or {
             // or with a truthy left means we're done
             if Self::is_truthy(&left) {
                 return Ok(left);
@@ -268,62 +1011,70 @@ impl Interpreter {
             }
         }
 
-        return self.eval(&expr.children[1]);
+        self.eval(right)
     }
 
-    fn eval_unary(&mut self, expr: &Expr) -> InterpreterResult {
-        let right = self.eval(&expr.children[0])?;
-        if let Value::Number(n) = right {
-            match expr.token.ttype {
-                TokenType::Minus => return Ok(Value::Number(-n)),
+    fn eval_unary(&mut self, op: &Token, right: ExprId) -> InterpreterResult {
+        let right = self.eval(right)?;
+        Self::unary_op(op, right)
+    }
+
+    pub fn unary_op(op: &Token, right: Value) -> InterpreterResult {
+        if is_number(&right) {
+            match op.ttype {
+                TokenType::Minus => return Ok(number::negate(&right).unwrap()),
                 TokenType::Bang => return Ok(Value::Bool(Self::is_truthy(&right))),
                 _ => {
                     return Err(RuntimeError::new(
                         &format!("unexpected unary argument {:?}", right),
-                        expr.token.line,
+                        op.line,
                     ))
                 }
             }
         }
 
-        return Err(RuntimeError::new(
+        Err(RuntimeError::new(
             &format!("unhandled {:?}", right),
-            expr.token.line,
-        ));
+            op.line,
+        ))
     }
 
-    fn eval_var(&mut self, tok: &Token, initializer: &Option<Expr>) -> ExecuteResult {
+    fn eval_var(&mut self, tok: &Token, initializer: Option<ExprId>) -> ExecuteResult {
         let mut val = Value::Nil;
         if let Some(expr) = initializer {
             val = self.eval(expr)?;
         }
-        self.env.define(&tok.lexeme, val);
+        self.meter
+            .alloc(size_of::<Value>() + tok.lexeme.len(), tok.line)?;
+        self.env.define(tok.lexeme, val);
         Ok(())
     }
 
-    fn eval_print(&mut self, expr: &Expr) -> ExecuteResult {
-        let val = self.eval(&expr)?;
-        println!("{}", val);
+    fn eval_print(&mut self, expr: ExprId) -> ExecuteResult {
+        let val = self.eval(expr)?;
+        let _ = writeln!(self.out, "{}", val);
         Ok(())
     }
 
-    fn is_truthy(val: &Value) -> bool {
+    pub fn is_truthy(val: &Value) -> bool {
         match val {
             Value::Bool(b) => *b,
             Value::Nil => false,
-            Value::Number(_) => true,
+            Value::Number(_) | Value::Int(_) | Value::BigInt(_) => true,
             Value::String(_) => true,
             Value::Function(_) => true,
+            Value::Native(_) => true,
+            Value::Generator(_) => true,
+            Value::Error(_) => true,
         }
     }
 
-    #[allow(dead_code)]
     fn is_equal(a: &Value, b: &Value) -> InterpreterResult {
         if let (Value::Nil, Value::Nil) = (&a, &b) {
             return Ok(Value::Bool(true));
         }
-        if let (Value::Number(ln), Value::Number(rn)) = (&a, &b) {
-            return Ok(Value::Bool(ln == rn));
+        if is_number(a) && is_number(b) {
+            return Ok(Value::Bool(number::compare(a, b) == Some(Ordering::Equal)));
         }
         if let (Value::String(ls), Value::String(rs)) = (&a, &b) {
             return Ok(Value::Bool(ls == rs));
@@ -332,3 +1083,508 @@ impl Interpreter {
         Ok(Value::Bool(false))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::*;
+    use crate::scanner::*;
+    use crate::test_util::*;
+    use std::time::Duration;
+
+    fn run(src: &str) -> (String, ExecuteResult) {
+        run_limited(src, Limits::default())
+    }
+
+    fn run_limited(src: &str, limits: Limits) -> (String, ExecuteResult) {
+        let toks = Scanner::new(src).scan_tokens();
+        let ast = Parser::new(&toks).parse().unwrap();
+        run_with(&ast, |ir| ir.set_limits(limits))
+    }
+
+    #[test]
+    fn throw_catch_finally() {
+        let (out, res) = run(r#"
+try {
+  print "before";
+  throw "boom";
+  print "skipped";
+} catch (e) {
+  print e;
+} finally {
+  print "finally";
+}"#);
+        assert!(res.is_ok());
+        assert_eq!(out, "before\nboom\nfinally\n");
+    }
+
+    #[test]
+    fn runtime_errors_are_catchable() {
+        let (out, res) = run(r#"
+try {
+  print nope;
+} catch (e) {
+  print e.message;
+  print e.line;
+}
+try {
+  print "a" - 1;
+} catch (e) {
+  print e.line;
+}"#);
+        assert!(res.is_ok());
+        assert_eq!(out, "undefined variable 'nope'\n3\n9\n");
+    }
+
+    #[test]
+    fn finally_runs_on_return() {
+        let (out, res) = run(r#"
+fun f() {
+  try {
+    return 1;
+  } finally {
+    print "cleanup";
+  }
+  return 2;
+}
+print f();"#);
+        assert!(res.is_ok());
+        assert_eq!(out, "cleanup\n1\n");
+    }
+
+    #[test]
+    fn uncaught_trace() {
+        let (_, res) = run(r#"
+fun inner() {
+  throw "deep";
+}
+fun outer() {
+  inner();
+}
+outer();"#);
+        let err = res.unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::Throw(Box::new(Value::String("deep".into())))
+        );
+        assert_eq!(
+            format!("{}", err),
+            "uncaught exception: deep\n[line 3] in inner()\n[line 6] in outer()\n[line 8] in script"
+        );
+    }
+
+    #[test]
+    fn anonymous_functions() {
+        let (out, res) = run(r#"
+fun apply(f, x) {
+  return f(x);
+}
+fun adder(n) {
+  return fun (x) { return x + n; };
+}
+fun make() {
+  return fun () { return "made"; };
+}
+var double = fun (x) { return x * 2; };
+print apply(double, 4);
+print apply(fun (x) { return x - 1; }, 4);
+print adder(10)(5);
+print make()();
+{
+  fun fact(n) {
+    if (n < 2) return 1;
+    return n * fact(n - 1);
+  }
+  print apply(fact, 5);
+}"#);
+        assert!(res.is_ok());
+        assert_eq!(out, "8\n3\n15\nmade\n120\n");
+    }
+
+    #[test]
+    fn closures_share_variables() {
+        let (out, res) = run(r#"
+fun counter() {
+  var i = 0;
+  return fun () { i = i + 1; return i; };
+}
+var c = counter();
+print c();
+print c();
+fun outer() {
+  var n = 0;
+  fun inc() { n = n + 1; }
+  inc();
+  inc();
+  return n;
+}
+print outer();
+fun late() {
+  var x = 1;
+  fun get() { return x; }
+  x = 2;
+  return get();
+}
+print late();
+fun pair() {
+  var shared = 0;
+  var set = fun (v) { shared = v; };
+  var get = fun () { return shared; };
+  set(7);
+  return get;
+}
+print pair()();
+{
+  fun isEven(n) { if (n == 0) return true; return isOdd(n - 1); }
+  fun isOdd(n) { if (n == 0) return false; return isEven(n - 1); }
+  print isEven(4);
+}"#);
+        assert!(res.is_ok());
+        assert_eq!(out, "1\n2\n2\n2\n7\ntrue\n");
+    }
+
+    #[test]
+    fn tail_calls() {
+        let (out, res) = run(r#"
+fun count(n, acc) {
+  if (n == 0) return acc;
+  return count(n - 1, acc + 1);
+}
+print count(1000000, 0);
+
+fun even(n) { if (n == 0) return true; return odd(n - 1); }
+fun odd(n) { if (n == 0) return false; return even(n - 1); }
+print odd(100001);
+
+// still catchable, since calls inside a try don't count
+fun risky(n) { if (n == 0) throw "bottom"; return risky(n - 1); }
+fun careful() {
+  try { return risky(3); } catch (e) { return "caught " + e; }
+}
+print careful();"#);
+        assert!(res.is_ok());
+        assert_eq!(out, "1000000\ntrue\ncaught bottom\n");
+
+        let (_, res) = run(r#"
+fun inner(n) {
+  throw "deep";
+}
+fun outer(n) {
+  return inner(n);
+}
+outer(1);
+"#);
+        // outer's frame went away when it handed the call off
+        assert_eq!(
+            format!("{}", res.unwrap_err()),
+            "uncaught exception: deep\n[line 3] in inner()\n[line 8] in script"
+        );
+    }
+
+    #[test]
+    fn integers() {
+        let (out, res) = run(r#"
+fun fact(n) {
+  var acc = 1;
+  for (var i = 2; i <= n; i = i + 1) acc = acc * i;
+  return acc;
+}
+print fact(20);
+print fact(30);
+print fact(30) // fact(28);
+print fact(30) % 1000000007;
+print -fact(25) // 7;
+print 9223372036854775807 + 1 - 1;
+print 123456789012345678901234567890 == 123456789012345678901234567890;
+print fact(25) > 1e20;
+print fact(25) / fact(24);"#);
+        assert!(res.is_ok());
+        assert_eq!(
+            out,
+            "2432902008176640000\n\
+             265252859812191058636308480000000\n\
+             870\n\
+             109361473\n\
+             -2215887149047283712000000\n\
+             9223372036854775807\n\
+             true\n\
+             true\n\
+             25.0\n"
+        );
+
+        // big numbers count against the memory limit like strings do
+        let (_, res) = run_limited(
+            "var x = 2; while (true) { x = x * x; }",
+            Limits {
+                bytes: Some(1 << 16),
+                ..Limits::default()
+            },
+        );
+        assert_eq!(res.unwrap_err().kind, ErrorKind::MemoryLimit);
+    }
+
+    #[test]
+    fn call_non_callable() {
+        let (_, res) = run(r#"
+var x = 1;
+x();"#);
+        let err = res.unwrap_err();
+        assert_eq!(err.msg, "can only call functions, not number");
+        assert_eq!(err.line, 3);
+
+        let (_, res) = run(r#"
+try { nope; } catch (e) { e.message(); }"#);
+        assert_eq!(res.unwrap_err().msg, "can only call functions, not string");
+    }
+
+    #[test]
+    fn limits() {
+        let spin = "while (true) {}";
+        let (_, res) = run_limited(
+            spin,
+            Limits {
+                steps: Some(1000),
+                ..Limits::default()
+            },
+        );
+        assert_eq!(res.unwrap_err().kind, ErrorKind::StepLimit);
+
+        let (_, res) = run_limited(
+            spin,
+            Limits {
+                time: Some(Duration::from_millis(10)),
+                ..Limits::default()
+            },
+        );
+        assert_eq!(res.unwrap_err().kind, ErrorKind::TimeLimit);
+
+        let (_, res) = run_limited(
+            "fun f(n) { return 1 + f(n + 1); } f(0);",
+            Limits {
+                call_depth: Some(50),
+                ..Limits::default()
+            },
+        );
+        let err = res.unwrap_err();
+        assert_eq!(err.kind, ErrorKind::DepthLimit);
+        assert_eq!(err.trace.len(), 50);
+
+        let (_, res) = run_limited(
+            r#"var s = "x"; while (true) { s = s + s; }"#,
+            Limits {
+                bytes: Some(1 << 20),
+                ..Limits::default()
+            },
+        );
+        assert_eq!(res.unwrap_err().kind, ErrorKind::MemoryLimit);
+    }
+
+    #[test]
+    fn limits_are_not_catchable() {
+        let (out, res) = run_limited(
+            r#"
+fun f() { return 1 + f(); }
+try {
+  f();
+} catch (e) {
+  print e.message;
+}"#,
+            Limits {
+                call_depth: Some(10),
+                ..Limits::default()
+            },
+        );
+        assert_eq!(res.unwrap_err().kind, ErrorKind::DepthLimit);
+        assert_eq!(out, "");
+
+        // catching the error and going round again used to outlast the time limit forever
+        let (_, res) = run_limited(
+            "while (true) { try { while (true) {} } catch (e) {} }",
+            Limits {
+                time: Some(Duration::from_millis(50)),
+                ..Limits::default()
+            },
+        );
+        assert_eq!(res.unwrap_err().kind, ErrorKind::TimeLimit);
+
+        // and a finally block doesn't get to carry on past it either
+        let (out, res) = run_limited(
+            r#"
+try {
+  while (true) {}
+} finally {
+  print "finally";
+  while (true) {}
+}"#,
+            Limits {
+                steps: Some(1000),
+                ..Limits::default()
+            },
+        );
+        assert_eq!(res.unwrap_err().kind, ErrorKind::StepLimit);
+        assert_eq!(out, "");
+    }
+
+    #[test]
+    fn sandboxed_natives() {
+        let (_, res) = run_limited(
+            "print clock();",
+            Limits {
+                host_natives: false,
+                ..Limits::default()
+            },
+        );
+        assert_eq!(
+            res.unwrap_err().msg,
+            "clock() is not available in this sandbox"
+        );
+    }
+
+    #[test]
+    fn script_args_and_exit() {
+        let src = r#"
+for (arg in args()) print arg;
+var a = args();
+print a.next() + a.next();
+print a.done;
+try {
+  exit(3);
+} catch (e) {
+  print "not caught";
+} finally {
+  print "finally";
+}
+print "not reached";
+"#;
+        let toks = Scanner::new(src).scan_tokens();
+        let ast = Parser::new(&toks).parse().unwrap();
+        let (out, res) = run_with(&ast, |ir| {
+            ir.set_args(&["x".to_string(), "y z".to_string()])
+        });
+        assert_eq!(res.unwrap_err().kind, ErrorKind::Exit(3));
+        assert_eq!(out, "x\ny z\nxy z\ntrue\nfinally\n");
+
+        let (_, res) = run("exit(256);");
+        assert_eq!(res.unwrap_err().msg, "exit() expects a code from 0 to 255");
+    }
+
+    #[test]
+    fn generators() {
+        let (out, res) = run(r#"
+fun count(n) {
+  for (var i = 0; i < n; i = i + 1) {
+    print "make " + "x";
+    yield i;
+  }
+}
+for (x in count(2)) print x;
+
+fun naturals() {
+  var i = 0;
+  while (true) {
+    yield i;
+    i = i + 1;
+  }
+}
+var g = naturals();
+print g.next();
+print g.next();
+print g.done;
+print g.next();
+
+fun pair(a, b) {
+  yield a;
+  yield b;
+  return;
+  yield "never";
+}
+var p = pair("a", "b");
+print p.next();
+print p.next();
+print p.done;
+print p.next();
+print p;
+"#);
+        assert!(res.is_ok());
+        assert_eq!(
+            out,
+            "make x\n0\nmake x\n1\n0\n1\nfalse\n2\na\nb\ntrue\n(nil)\ngenerator pair\n"
+        );
+    }
+
+    #[test]
+    fn nested_generators() {
+        let (out, res) = run(r#"
+fun range(lo, hi) {
+  for (var i = lo; i < hi; i = i + 1) yield i;
+}
+fun pairs(n) {
+  for (a in range(0, n)) {
+    for (var b in range(a, n)) {
+      var label = a + b * 10;
+      yield label;
+    }
+  }
+}
+for (p in pairs(2)) print p;
+fun scale(k) {
+  return fun (gen) {
+    for (x in gen) yield x * k;
+  };
+}
+for (x in scale(100)(range(1, 3))) print x;
+"#);
+        assert!(res.is_ok());
+        assert_eq!(out, "0\n10\n11\n100\n200\n");
+    }
+
+    #[test]
+    fn generator_errors() {
+        let (out, res) = run(r#"
+fun careful() {
+  try {
+    yield 1;
+    throw "inside";
+  } catch (e) {
+    yield "caught " + e;
+  } finally {
+    print "cleanup";
+  }
+  yield 2;
+  throw "later";
+}
+var g = careful();
+print g.next();
+print g.next();
+print g.next();
+try {
+  g.next();
+} catch (e) {
+  print "outside " + e;
+}
+print g.done;
+fun bad() {
+  yield 1;
+  yield nope;
+}
+for (x in bad()) print x;
+"#);
+        assert_eq!(
+            out,
+            "1\ncaught inside\ncleanup\n2\noutside later\ntrue\n1\n"
+        );
+        let err = res.unwrap_err();
+        assert_eq!(err.msg, "undefined variable 'nope'");
+        assert_eq!(err.line, 26);
+        assert_eq!(err.trace[0].name, "bad");
+        assert_eq!(err.trace[0].line, 28);
+
+        let (_, res) = run("for (x in 3) print x;");
+        assert_eq!(
+            res.unwrap_err().msg,
+            "can only loop over generators, not number"
+        );
+        let (_, res) = run("fun g() { yield g2.next(); } var g2 = g(); g2.next();");
+        assert_eq!(res.unwrap_err().msg, "generator g() is already running");
+    }
+}
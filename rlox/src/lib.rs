@@ -0,0 +1,31 @@
+#![allow(dead_code)]
+
+pub mod ast;
+pub mod ast_json;
+pub mod ast_printer;
+pub mod bigint;
+pub mod checker;
+pub mod codegen;
+pub mod coverage;
+pub mod environment;
+pub mod error;
+pub mod expr;
+pub mod function;
+pub mod generator;
+pub mod interpreter;
+pub mod json;
+pub mod limits;
+pub mod lint;
+pub mod lox;
+pub mod number;
+pub mod optimizer;
+pub mod parser;
+pub mod profiler;
+pub mod scanner;
+pub mod stmt;
+#[cfg(test)]
+mod test_util;
+pub mod token;
+pub mod token_type;
+pub mod r#type;
+pub mod value;
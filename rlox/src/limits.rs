@@ -0,0 +1,139 @@
+use crate::error::*;
+use std::time::{Duration, Instant};
+
+// caps on what a single run is allowed to consume, for scripts we don't trust.  None means no
+// limit.  call_depth is also what keeps deep recursion from blowing the rust stack, so keep it
+// well under what the host thread can take.  the default is sized for the stack the rlox binary
+// runs scripts on, see INTERPRETER_STACK in main.rs; embedders on a smaller stack should lower it
+#[derive(Clone, Debug)]
+pub struct Limits {
+    pub steps: Option<u64>,
+    pub call_depth: Option<usize>,
+    pub time: Option<Duration>,
+    pub bytes: Option<usize>,
+    // natives that reach outside the interpreter, like reading the clock or touching files
+    pub host_natives: bool,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            steps: None,
+            call_depth: Some(DEFAULT_CALL_DEPTH),
+            time: None,
+            bytes: None,
+            host_natives: true,
+        }
+    }
+}
+
+// a lox call costs up to ~200K of rust stack in a debug build, with an expression nested as deep
+// as the parser allows around it, and a good deal less in release
+pub const DEFAULT_CALL_DEPTH: usize = 1000;
+
+// checking the clock on every statement is measurable, only look every so often
+const TIME_CHECK_INTERVAL: u64 = 1024;
+
+// keeps a running tally against a set of Limits
+#[derive(Debug)]
+pub struct Meter {
+    pub limits: Limits,
+    steps: u64,
+    depth: usize,
+    bytes: usize,
+    started: Instant,
+    // the limit that's been hit, if one has.  it stays hit: whatever runs afterwards, finally
+    // blocks included, fails the same way on its first step
+    tripped: Option<(ErrorKind, String)>,
+}
+
+impl Meter {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            steps: 0,
+            depth: 0,
+            bytes: 0,
+            started: Instant::now(),
+            tripped: None,
+        }
+    }
+
+    pub fn restart(&mut self) {
+        *self = Self::new(self.limits.clone());
+    }
+
+    fn trip(&mut self, kind: ErrorKind, msg: String, line: usize) -> Result<(), RuntimeError> {
+        let err = RuntimeError::limit(kind.clone(), &msg, line);
+        self.tripped = Some((kind, msg));
+        Err(err)
+    }
+
+    fn check_tripped(&self, line: usize) -> Result<(), RuntimeError> {
+        match &self.tripped {
+            Some((kind, msg)) => Err(RuntimeError::limit(kind.clone(), msg, line)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn step(&mut self, line: usize) -> Result<(), RuntimeError> {
+        self.check_tripped(line)?;
+        self.steps += 1;
+        if let Some(max) = self.limits.steps {
+            if self.steps > max {
+                return self.trip(
+                    ErrorKind::StepLimit,
+                    format!("step limit of {} exceeded", max),
+                    line,
+                );
+            }
+        }
+
+        if let Some(max) = self.limits.time {
+            if self.steps.is_multiple_of(TIME_CHECK_INTERVAL) && self.started.elapsed() > max {
+                return self.trip(
+                    ErrorKind::TimeLimit,
+                    format!("time limit of {:?} exceeded", max),
+                    line,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn enter_call(&mut self, line: usize) -> Result<(), RuntimeError> {
+        self.check_tripped(line)?;
+        if let Some(max) = self.limits.call_depth {
+            if self.depth >= max {
+                return self.trip(
+                    ErrorKind::DepthLimit,
+                    format!("call depth limit of {} exceeded", max),
+                    line,
+                );
+            }
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub fn leave_call(&mut self) {
+        self.depth -= 1;
+    }
+
+    // rough accounting of memory handed out on the script's behalf.  it's a running total rather
+    // than what's live, so a script can't dodge it by freeing and reallocating
+    pub fn alloc(&mut self, bytes: usize, line: usize) -> Result<(), RuntimeError> {
+        self.check_tripped(line)?;
+        self.bytes += bytes;
+        if let Some(max) = self.limits.bytes {
+            if self.bytes > max {
+                return self.trip(
+                    ErrorKind::MemoryLimit,
+                    format!("memory limit of {} bytes exceeded", max),
+                    line,
+                );
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,477 @@
+use crate::ast::*;
+use crate::error::*;
+use crate::expr::*;
+use crate::interpreter::*;
+use crate::stmt::*;
+use crate::token::*;
+use crate::token_type::*;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rule {
+    UnusedVariable,
+    UnreadAssignment,
+    UnreachableCode,
+    ShadowedLocal,
+    SelfComparison,
+    WrongArgCount,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 6] = [
+        Rule::UnusedVariable,
+        Rule::UnreadAssignment,
+        Rule::UnreachableCode,
+        Rule::ShadowedLocal,
+        Rule::SelfComparison,
+        Rule::WrongArgCount,
+    ];
+
+    // what the rule is called on the command line and in reports
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::UnusedVariable => "unused-variable",
+            Rule::UnreadAssignment => "unread-assignment",
+            Rule::UnreachableCode => "unreachable-code",
+            Rule::ShadowedLocal => "shadowed-local",
+            Rule::SelfComparison => "self-comparison",
+            Rule::WrongArgCount => "wrong-arg-count",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL.iter().copied().find(|rule| rule.name() == name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Var,
+    Param,
+    Function,
+    Catch,
+    Loop,
+}
+
+#[derive(Debug)]
+struct Local<'a> {
+    name: &'a str,
+    line: usize,
+    kind: Kind,
+    read: bool,
+    assigned: bool,
+    // known number of params when the name holds a function nobody has reassigned (yet)
+    arity: Option<usize>,
+}
+
+// style checks on top of what the Checker does.  nothing here is an error, a script with lint
+// warnings runs the same as one without.  locals are tracked per scope the same way the
+// interpreter sees them, globals only for the functions they hold since any other part of the
+// program could be using them
+pub struct Linter<'a> {
+    ast: &'a Ast<'a>,
+    rules: Vec<Rule>,
+    scopes: Vec<Vec<Local<'a>>>,
+    globals: HashMap<String, Option<usize>>,
+    diags: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    pub fn new(ast: &'a Ast<'a>, rules: &[Rule]) -> Self {
+        Self {
+            ast,
+            rules: rules.to_vec(),
+            scopes: vec![],
+            globals: HashMap::new(),
+            diags: vec![],
+        }
+    }
+
+    pub fn lint(mut self) -> Vec<Diagnostic> {
+        let ast = self.ast;
+        for native in natives() {
+            self.globals.insert(native.name, Some(native.arity));
+        }
+        // hoist top-level functions so calls ahead of the declaration still get checked
+        for stmt in &ast.root {
+            if let Stmt::Function(fun) = ast.stmt(*stmt) {
+                let decl = ast.fun(*fun);
+                self.globals
+                    .insert(decl.name.lexeme.to_string(), Some(decl.params.len()));
+            }
+        }
+
+        self.lint_stmts(&ast.root);
+        // unused locals only turn up once their scope closes, put everything back in source order
+        self.diags.sort_by_key(|d| d.line);
+        self.diags
+    }
+
+    fn warn(&mut self, rule: Rule, line: usize, msg: &str) {
+        if self.rules.contains(&rule) {
+            self.diags.push(Diagnostic::warning(
+                line,
+                &format!("{} [{}]", msg, rule.name()),
+            ));
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(vec![]);
+    }
+
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap();
+        for local in scope {
+            // a leading underscore says "unused on purpose"
+            if local.kind != Kind::Var || local.read || local.name.starts_with('_') {
+                continue;
+            }
+            if local.assigned {
+                let msg = format!("'{}' is assigned but never read", local.name);
+                self.warn(Rule::UnreadAssignment, local.line, &msg);
+            } else {
+                let msg = format!("unused variable '{}'", local.name);
+                self.warn(Rule::UnusedVariable, local.line, &msg);
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &Token<'a>, kind: Kind, arity: Option<usize>) {
+        let scope = match self.scopes.last() {
+            Some(scope) => scope,
+            None => {
+                self.globals.insert(name.lexeme.to_string(), arity);
+                return;
+            }
+        };
+
+        if !scope.iter().any(|local| local.name == name.lexeme) {
+            let outer = self.scopes[..self.scopes.len() - 1]
+                .iter()
+                .rev()
+                .find_map(|scope| scope.iter().find(|local| local.name == name.lexeme));
+            if let Some(outer) = outer {
+                let msg = format!(
+                    "'{}' shadows a local declared on line {}",
+                    name.lexeme, outer.line
+                );
+                self.warn(Rule::ShadowedLocal, name.line, &msg);
+            }
+        }
+
+        self.scopes.last_mut().unwrap().push(Local {
+            name: name.lexeme,
+            line: name.line,
+            kind,
+            read: false,
+            assigned: false,
+            arity,
+        });
+    }
+
+    fn resolve(&mut self, name: &str) -> Option<&mut Local<'a>> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.iter_mut().rev().find(|local| local.name == name))
+    }
+
+    fn lint_stmts(&mut self, stmts: &[StmtId]) {
+        let ast = self.ast;
+        let mut exit = None;
+        for stmt in stmts {
+            if let Some(keyword) = exit.take() {
+                let msg = format!("unreachable code after {}", keyword);
+                self.warn(Rule::UnreachableCode, ast.line(*stmt), &msg);
+            }
+            self.lint_stmt(*stmt);
+            match ast.stmt(*stmt) {
+                Stmt::Return(..) => exit = Some("return"),
+                Stmt::Throw(..) => exit = Some("throw"),
+                _ => {}
+            }
+        }
+    }
+
+    fn lint_stmt(&mut self, id: StmtId) {
+        let ast = self.ast;
+        match ast.stmt(id) {
+            Stmt::Expr(expr) | Stmt::Print(expr) | Stmt::Throw(_, expr) => self.lint_expr(*expr),
+            Stmt::Var(name, _, init) => {
+                let mut arity = None;
+                if let Some(init) = init {
+                    self.lint_expr(*init);
+                    if let Expr::Function(fun) = ast.expr(*init) {
+                        arity = Some(ast.fun(*fun).params.len());
+                    }
+                }
+                self.declare(name, Kind::Var, arity);
+            }
+            Stmt::Block(stmts) => {
+                self.begin_scope();
+                self.lint_stmts(stmts);
+                self.end_scope();
+            }
+            Stmt::If(cond, then, els) => {
+                self.lint_expr(*cond);
+                self.lint_stmt(*then);
+                if let Some(els) = els {
+                    self.lint_stmt(*els);
+                }
+            }
+            Stmt::While(cond, body) => {
+                self.lint_expr(*cond);
+                self.lint_stmt(*body);
+            }
+            Stmt::Loop(body) => self.lint_stmt(*body),
+            Stmt::Function(fun) => {
+                let decl = ast.fun(*fun);
+                if !self.scopes.is_empty() {
+                    self.declare(&decl.name, Kind::Function, Some(decl.params.len()));
+                }
+                self.lint_function(decl);
+            }
+            Stmt::ForIn(name, iter, body) => {
+                self.lint_expr(*iter);
+                self.begin_scope();
+                self.declare(name, Kind::Loop, None);
+                self.lint_stmt(*body);
+                self.end_scope();
+            }
+            Stmt::Return(_, val) | Stmt::Yield(_, val) => {
+                if let Some(val) = val {
+                    self.lint_expr(*val);
+                }
+            }
+            Stmt::Try(body, catch, finally) => {
+                self.lint_stmt(*body);
+                if let Some((name, block)) = catch {
+                    self.begin_scope();
+                    self.declare(name, Kind::Catch, None);
+                    self.lint_stmt(*block);
+                    self.end_scope();
+                }
+                if let Some(block) = finally {
+                    self.lint_stmt(*block);
+                }
+            }
+        }
+    }
+
+    fn lint_function(&mut self, decl: &'a FunDecl<'a>) {
+        self.begin_scope();
+        for param in &decl.params {
+            self.declare(param, Kind::Param, None);
+        }
+        self.lint_stmts(&decl.body);
+        self.end_scope();
+    }
+
+    fn lint_expr(&mut self, id: ExprId) {
+        let ast = self.ast;
+        match ast.expr(id) {
+            Expr::Variable { name } => {
+                if let Some(local) = self.resolve(name.lexeme) {
+                    local.read = true;
+                }
+            }
+            Expr::Assign { name, value } => {
+                self.lint_expr(*value);
+                // whatever it holds now, we no longer know how many args it takes
+                match self.resolve(name.lexeme) {
+                    Some(local) => {
+                        local.assigned = true;
+                        local.arity = None;
+                    }
+                    None => {
+                        self.globals.insert(name.lexeme.to_string(), None);
+                    }
+                }
+            }
+            Expr::Binary { op, left, right } => {
+                self.lint_expr(*left);
+                self.lint_expr(*right);
+                if is_comparison(op) && self.same_expr(*left, *right) {
+                    let msg = format!("'{}' compares an expression to itself", op.lexeme);
+                    self.warn(Rule::SelfComparison, op.line, &msg);
+                }
+            }
+            Expr::Logical { left, right, .. } => {
+                self.lint_expr(*left);
+                self.lint_expr(*right);
+            }
+            Expr::Unary { right, .. } => self.lint_expr(*right),
+            Expr::Grouping { expr } => self.lint_expr(*expr),
+            Expr::Get { object, .. } => self.lint_expr(*object),
+            Expr::Call {
+                callee,
+                paren,
+                args,
+            } => {
+                self.lint_expr(*callee);
+                for arg in args {
+                    self.lint_expr(*arg);
+                }
+                if let Expr::Variable { name } = ast.expr(*callee) {
+                    self.check_arity(name, paren, args.len());
+                }
+            }
+            Expr::Function(fun) => self.lint_function(ast.fun(*fun)),
+            Expr::Literal { .. } | Expr::Constant { .. } => {}
+        }
+    }
+
+    fn check_arity(&mut self, name: &Token, paren: &Token, args: usize) {
+        let arity = match self.resolve(name.lexeme) {
+            Some(local) => local.arity,
+            None => self.globals.get(name.lexeme).copied().flatten(),
+        };
+        if let Some(arity) = arity {
+            if arity != args {
+                let msg = format!("{}() expects {} args but got {}", name.lexeme, arity, args);
+                self.warn(Rule::WrongArgCount, paren.line, &msg);
+            }
+        }
+    }
+
+    // structurally the same and free of side effects, so both sides always evaluate alike
+    fn same_expr(&self, a: ExprId, b: ExprId) -> bool {
+        let ast = self.ast;
+        match (ast.expr(a), ast.expr(b)) {
+            (Expr::Grouping { expr }, _) => self.same_expr(*expr, b),
+            (_, Expr::Grouping { expr }) => self.same_expr(a, *expr),
+            (Expr::Variable { name: x }, Expr::Variable { name: y }) => x.lexeme == y.lexeme,
+            (Expr::Literal { token: x }, Expr::Literal { token: y }) => x.lexeme == y.lexeme,
+            (
+                Expr::Get {
+                    object: x,
+                    name: xn,
+                },
+                Expr::Get {
+                    object: y,
+                    name: yn,
+                },
+            ) => xn.lexeme == yn.lexeme && self.same_expr(*x, *y),
+            (Expr::Unary { op: xo, right: x }, Expr::Unary { op: yo, right: y }) => {
+                xo.ttype == yo.ttype && self.same_expr(*x, *y)
+            }
+            (
+                Expr::Binary {
+                    op: xo,
+                    left: xl,
+                    right: xr,
+                },
+                Expr::Binary {
+                    op: yo,
+                    left: yl,
+                    right: yr,
+                },
+            ) => xo.ttype == yo.ttype && self.same_expr(*xl, *yl) && self.same_expr(*xr, *yr),
+            _ => false,
+        }
+    }
+}
+
+fn is_comparison(op: &Token) -> bool {
+    matches!(
+        op.ttype,
+        TokenType::EqualEqual
+            | TokenType::BangEqual
+            | TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::*;
+    use crate::scanner::*;
+
+    fn lint(src: &str, rules: &[Rule]) -> Vec<(usize, String)> {
+        let toks = Scanner::new(src).scan_tokens();
+        let ast = Parser::new(&toks).parse().unwrap();
+        Linter::new(&ast, rules)
+            .lint()
+            .into_iter()
+            .map(|d| {
+                assert_eq!(d.severity, Severity::Warning);
+                (d.line, d.msg)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn all_rules() {
+        let src = r#"var global = 1;
+fun add(a, b) {
+  var unused = 1;
+  var written;
+  written = 2;
+  var _ignored = 3;
+  return a + b;
+  print "never";
+}
+{
+  var x = 1;
+  {
+    var x = 2;
+    print x == x;
+  }
+}
+add(1);
+clock(1, 2);
+var f = fun (a) { return a; };
+f(1);
+f = add;
+f(1);
+"#;
+        let lines: Vec<(usize, String)> = vec![
+            (3, "unused variable 'unused' [unused-variable]"),
+            (
+                4,
+                "'written' is assigned but never read [unread-assignment]",
+            ),
+            (8, "unreachable code after return [unreachable-code]"),
+            (11, "unused variable 'x' [unused-variable]"),
+            (
+                13,
+                "'x' shadows a local declared on line 11 [shadowed-local]",
+            ),
+            (
+                14,
+                "'==' compares an expression to itself [self-comparison]",
+            ),
+            (17, "add() expects 2 args but got 1 [wrong-arg-count]"),
+            (18, "clock() expects 0 args but got 2 [wrong-arg-count]"),
+        ]
+        .into_iter()
+        .map(|(line, msg)| (line, msg.to_string()))
+        .collect();
+        assert_eq!(lint(src, &Rule::ALL), lines);
+    }
+
+    #[test]
+    fn toggles() {
+        let src = r#"fun f() {
+  var a = 1;
+  return;
+  f(1);
+}
+"#;
+        assert_eq!(lint(src, &Rule::ALL).len(), 3);
+        assert_eq!(
+            lint(src, &[Rule::WrongArgCount]),
+            vec![(
+                4,
+                "f() expects 0 args but got 1 [wrong-arg-count]".to_string()
+            )]
+        );
+        assert!(lint(src, &[]).is_empty());
+        assert_eq!(Rule::from_name("shadowed-local"), Some(Rule::ShadowedLocal));
+        assert_eq!(Rule::from_name("nope"), None);
+    }
+}
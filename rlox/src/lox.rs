@@ -1,7 +1,18 @@
 use std::io::{self, Read, Write};
 
+use crate::ast::*;
+use crate::ast_json;
+use crate::checker::*;
+use crate::codegen::*;
+use crate::coverage::*;
+use crate::error::*;
 use crate::interpreter::*;
+use crate::json::*;
+use crate::limits::*;
+use crate::lint::*;
+use crate::optimizer::*;
 use crate::parser::*;
+use crate::profiler::*;
 use crate::scanner::*;
 use crate::token::*;
 use crate::token_type::*;
@@ -11,47 +22,200 @@ use std::sync::atomic::{AtomicU32, Ordering};
 pub struct Lox {
     errs: u32,
     runtime_errs: u32,
+    strict: bool,
+    limits: Limits,
+    profile: bool,
+    // where to write collapsed stacks, if anywhere
+    profile_stacks: Option<String>,
+    coverage: bool,
+    // 0 runs the ast exactly as parsed, 1 folds constants and drops dead code first
+    opt_level: u8,
+    // path of the script being run, None for the prompt
+    script: Option<String>,
+    // handed to the script through args()
+    args: Vec<String>,
+    // the status to exit with no matter what the error counts say, set by exit() or when the
+    // script couldn't even be read
+    exit: Option<i32>,
 }
 
-static mut ERRS: [AtomicU32; 2] = [AtomicU32::new(0), AtomicU32::new(0)];
+static ERRS: [AtomicU32; 2] = [AtomicU32::new(0), AtomicU32::new(0)];
 const ERR_INTERPRET: usize = 0;
 const ERR_RUNTIME: usize = 1;
 
+// exit statuses, borrowed from sysexits.h like clox does
+const EX_DATAERR: i32 = 65;
+const EX_NOINPUT: i32 = 66;
+const EX_SOFTWARE: i32 = 70;
+
+// the whole of a script, or None after saying why it couldn't be read
+fn read_source(f: &str) -> Option<String> {
+    let mut buf = String::new();
+    match File::open(f).and_then(|mut file| file.read_to_string(&mut buf)) {
+        Ok(_) => Some(buf),
+        Err(e) => {
+            eprintln!("{}: {}", f, e);
+            None
+        }
+    }
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Lox {
     pub fn new() -> Self {
         Lox {
             errs: 0,
             runtime_errs: 0,
+            strict: false,
+            limits: Limits::default(),
+            profile: false,
+            profile_stacks: None,
+            coverage: false,
+            opt_level: 1,
+            script: None,
+            args: vec![],
+            exit: None,
+        }
+    }
+
+    // warn about top-level functions missing type annotations
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    // print a per-function report to stderr once the script finishes
+    pub fn set_profile(&mut self, profile: bool) {
+        self.profile = profile;
+    }
+
+    pub fn set_profile_stacks(&mut self, path: &str) {
+        self.profile = true;
+        self.profile_stacks = Some(path.to_string());
+    }
+
+    // write <script>.cov (annotated listing) and <script>.info (lcov) after running a file
+    pub fn set_coverage(&mut self, coverage: bool) {
+        self.coverage = coverage;
+    }
+
+    pub fn set_opt_level(&mut self, level: u8) {
+        self.opt_level = level;
+    }
+
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    // what the process should exit with after running: the code given to exit(), 65 if the
+    // script didn't get past the static checks, 70 if it failed at runtime, 0 otherwise
+    pub fn exit_code(&self) -> i32 {
+        match self.exit {
+            Some(code) => code,
+            None if self.errs > 0 => EX_DATAERR,
+            None if self.runtime_errs > 0 => EX_SOFTWARE,
+            None => 0,
         }
     }
 
     pub fn run(&mut self, s: &str) {
-        let sc = Scanner::new(s);
-        let toks = &sc.scan_tokens();
+        // the counter is global and the prompt never resets it, so compare against where it was
+        let errs = ERRS[ERR_INTERPRET].load(Ordering::SeqCst);
+        let toks = &Scanner::new(s).scan_tokens();
+        if ERRS[ERR_INTERPRET].load(Ordering::SeqCst) > errs {
+            self.errs += 1;
+            return;
+        }
 
-        let p = Parser::new(&toks);
-        let expr = p.parse();
-        if expr.is_err() {
+        match Parser::new(toks).parse() {
+            Ok(ast) => self.run_ast(ast, s),
+            Err(_) => self.errs += 1,
+        }
+    }
+
+    // everything after parsing.  `source` is only used for annotating coverage
+    fn run_ast(&mut self, mut ast: Ast, source: &str) {
+        // diagnostics are about the code as written, so check before the optimizer touches it
+        let diags = Checker::new(&ast, self.strict).check();
+        for diag in &diags {
+            Lox::diagnostic(diag);
+        }
+        if diags.iter().any(|d| d.severity == Severity::Error) {
+            self.errs += 1;
             return;
         }
-        //println!("{:?}", expr);
-        let expr = expr.unwrap();
 
-        let mut ir = Interpreter::new();
-        let val = ir.interpret(&expr);
+        if self.opt_level > 0 {
+            Optimizer::new(&mut ast).optimize();
+        }
+
+        let mut ir = Interpreter::new(&ast);
+        ir.set_limits(self.limits.clone());
+        ir.set_args(&self.args);
+        if self.profile {
+            ir.enable_profiling();
+        }
+        if self.coverage {
+            ir.enable_coverage();
+        }
+        let val = ir.interpret(&ast.root);
+
+        if let Some(profiler) = ir.take_profile() {
+            self.write_profile(&profiler);
+        }
+        if let (Some(coverage), Some(script)) = (ir.take_coverage(), &self.script) {
+            Self::write_coverage(&coverage, script, source);
+        }
 
-        // println!("ast: {}", AstPrinter::serialize_stmts(&expr));
         match val {
             Ok(_) => {}
-            Err(_e) => {} // println!("error: {:?}", e),
+            Err(RuntimeError {
+                kind: ErrorKind::Exit(code),
+                ..
+            }) => self.exit = Some(code),
+            Err(_) => self.runtime_errs += 1,
+        }
+    }
+
+    fn write_profile(&self, profiler: &Profiler) {
+        eprint!("{}", profiler.report());
+        if let Some(path) = &self.profile_stacks {
+            let res = File::create(path).and_then(|mut f| profiler.write_stacks(&mut f));
+            if let Err(e) = res {
+                eprintln!("failed to write {}: {}", path, e);
+            }
+        }
+    }
+
+    fn write_coverage(coverage: &Coverage, script: &str, source: &str) {
+        let listing = format!("{}.cov", script);
+        let info = format!("{}.info", script);
+        let res = File::create(&listing)
+            .and_then(|mut f| f.write_all(coverage.annotate(source).as_bytes()))
+            .and_then(|_| File::create(&info))
+            .and_then(|mut f| f.write_all(coverage.lcov(script).as_bytes()));
+        if let Err(e) = res {
+            eprintln!("failed to write coverage for {}: {}", script, e);
+            return;
         }
 
-        // if self.errs > 0 {
-        //     todo!();
-        // }
-        // if self.runtime_errs > 0 {
-        //     todo!();
-        // }
+        let (hit, total) = coverage.summary();
+        let pct = match total {
+            0 => 100.0,
+            _ => hit as f64 * 100.0 / total as f64,
+        };
+        eprintln!(
+            "coverage: {}/{} lines ({:.1}%), see {} and {}",
+            hit, total, pct, listing, info
+        );
     }
 
     pub fn run_prompt(&mut self) {
@@ -60,8 +224,13 @@ impl Lox {
             let _ = io::stdout().flush();
 
             let mut buf = String::new();
-            io::stdin().read_line(&mut buf).unwrap();
-            self.run(&buf);
+            match io::stdin().read_line(&mut buf) {
+                Ok(0) | Err(_) => return,
+                Ok(_) => self.run(&buf),
+            }
+            if self.exit.is_some() {
+                return;
+            }
 
             self.errs = 0;
             self.runtime_errs = 0;
@@ -69,29 +238,151 @@ impl Lox {
     }
 
     pub fn run_file(&mut self, f: &str) {
+        let buf = match read_source(f) {
+            Some(buf) => buf,
+            None => {
+                self.exit = Some(EX_NOINPUT);
+                return;
+            }
+        };
+        self.script = Some(f.to_string());
+        self.run(&buf);
+    }
+
+    // a whole script piped in, as opposed to the prompt reading it a line at a time
+    pub fn run_stdin(&mut self) {
         let mut buf = String::new();
-        File::open(f).unwrap().read_to_string(&mut buf).unwrap();
+        if let Err(e) = io::stdin().read_to_string(&mut buf) {
+            eprintln!("stdin: {}", e);
+            self.exit = Some(EX_NOINPUT);
+            return;
+        }
         self.run(&buf);
+    }
+
+    // parse a script and print its ast as json instead of running it
+    pub fn dump_ast_file(&mut self, f: &str) {
+        let buf = match read_source(f) {
+            Some(buf) => buf,
+            None => {
+                self.exit = Some(EX_NOINPUT);
+                return;
+            }
+        };
 
-        if self.errs > 0 {
-            todo!();
+        let toks = Scanner::new(&buf).scan_tokens();
+        match Parser::new(&toks).parse() {
+            Ok(ast) => print!("{}", ast_json::dump(&ast).pretty()),
+            Err(_) => self.errs += 1,
         }
     }
 
-    /// safety:  atomic add
-    pub fn error(line: usize, msg: &str) {
-        unsafe {
-            ERRS[ERR_INTERPRET].fetch_add(1, Ordering::SeqCst);
+    // translate a script to a standalone C program, written to `out` or stdout.  returns false if
+    // anything stopped it from being compiled
+    pub fn compile_file(&mut self, f: &str, out: Option<&str>) -> bool {
+        let buf = match read_source(f) {
+            Some(buf) => buf,
+            None => return false,
+        };
+
+        let toks = Scanner::new(&buf).scan_tokens();
+        let mut ast = match Parser::new(&toks).parse() {
+            Ok(ast) => ast,
+            Err(_) => return false,
+        };
+        let diags = Checker::new(&ast, self.strict).check();
+        for diag in &diags {
+            Lox::diagnostic(diag);
+        }
+        if diags.iter().any(|d| d.severity == Severity::Error) {
+            return false;
         }
+        if self.opt_level > 0 {
+            Optimizer::new(&mut ast).optimize();
+        }
+
+        let c = match CGen::new(&ast).generate(f) {
+            Ok(c) => c,
+            Err(diags) => {
+                for diag in &diags {
+                    Lox::diagnostic(diag);
+                }
+                return false;
+            }
+        };
+        let res = match out {
+            Some(path) => File::create(path).and_then(|mut file| file.write_all(c.as_bytes())),
+            None => io::stdout().write_all(c.as_bytes()),
+        };
+        if let Err(e) = res {
+            eprintln!("{}: {}", out.unwrap_or("stdout"), e);
+            return false;
+        }
+        true
+    }
+
+    // report lint findings for a script without running it.  returns how many problems turned
+    // up, parse errors included
+    pub fn lint_file(&mut self, f: &str, rules: &[Rule]) -> usize {
+        let buf = match read_source(f) {
+            Some(buf) => buf,
+            None => return 1,
+        };
+
+        let toks = Scanner::new(&buf).scan_tokens();
+        let ast = match Parser::new(&toks).parse() {
+            Ok(ast) => ast,
+            Err(_) => return ERRS[ERR_INTERPRET].load(Ordering::SeqCst).max(1) as usize,
+        };
+        let diags = Linter::new(&ast, rules).lint();
+        for diag in &diags {
+            Lox::diagnostic(diag);
+        }
+        diags.len()
+    }
+
+    // run an ast previously written out by dump_ast_file
+    pub fn run_ast_file(&mut self, f: &str) {
+        let buf = match read_source(f) {
+            Some(buf) => buf,
+            None => {
+                self.exit = Some(EX_NOINPUT);
+                return;
+            }
+        };
+
+        let doc = match Json::parse(&buf) {
+            Ok(doc) => doc,
+            Err(e) => {
+                eprintln!("{}: invalid json at byte {}: {}", f, e.offset, e.msg);
+                self.errs += 1;
+                return;
+            }
+        };
+        match ast_json::load(&doc) {
+            Ok(ast) => self.run_ast(ast, ""),
+            Err(e) => {
+                eprintln!("{}: invalid ast: {}", f, e.msg);
+                self.errs += 1;
+            }
+        }
+    }
+
+    pub fn error(line: usize, msg: &str) {
+        ERRS[ERR_INTERPRET].fetch_add(1, Ordering::SeqCst);
         Lox::report(line, "", msg);
     }
 
-    /// safety:  atomic add
-    pub fn runtime_error(msg: &str) {
-        unsafe {
-            ERRS[ERR_RUNTIME].fetch_add(1, Ordering::SeqCst);
+    pub fn runtime_error(err: &RuntimeError) {
+        ERRS[ERR_RUNTIME].fetch_add(1, Ordering::SeqCst);
+        eprintln!("{}", err);
+    }
+
+    pub fn diagnostic(diag: &Diagnostic) {
+        match diag.severity {
+            Severity::Error => Lox::error(diag.line, &diag.msg),
+            Severity::Warning => eprintln!("[line {}] warning: {}", diag.line, diag.msg),
         }
-        eprintln!("runtime error: {}", msg);
     }
 
     pub fn report(line: usize, loc: &str, msg: &str) {
@@ -108,12 +399,9 @@ impl Lox {
 }
 
 impl Drop for Lox {
-    /// safety:  atomic store
     fn drop(&mut self) {
-        unsafe {
-            ERRS[ERR_INTERPRET].store(0, Ordering::Relaxed);
-            ERRS[ERR_RUNTIME].store(0, Ordering::Relaxed);
-        }
+        ERRS[ERR_INTERPRET].store(0, Ordering::Relaxed);
+        ERRS[ERR_RUNTIME].store(0, Ordering::Relaxed);
     }
 }
 
@@ -124,7 +412,7 @@ mod test {
     fn lox_test(buf: &str) {
         println!("{}", buf);
         let mut l = Lox::new();
-        l.run(&buf);
+        l.run(buf);
         assert_no_errs();
     }
 
@@ -134,10 +422,8 @@ mod test {
         // downstream components like scanner/parser/etc now need references to
         // the top-level lox instance.
         // either that or inject in callback functors
-        unsafe {
-            for e in &ERRS {
-                assert!(e.load(Ordering::Relaxed) == 0);
-            }
+        for e in &ERRS {
+            assert!(e.load(Ordering::Relaxed) == 0);
         }
     }
 
@@ -1,32 +1,180 @@
-#![allow(dead_code)]
-
-use std::{env, process};
-
-mod ast_printer;
-mod environment;
-mod error;
-mod expr;
-mod function;
-mod interpreter;
-mod lox;
-mod parser;
-mod scanner;
-mod stmt;
-mod token;
-mod token_type;
-mod value;
-
-use crate::lox::Lox;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::time::Duration;
+use std::{env, process, thread};
+
+use rlox::limits::Limits;
+use rlox::lint::Rule;
+use rlox::lox::Lox;
+
+// scripts run on a thread of their own with room for Limits' default call depth, the main
+// thread's few megabytes run out after a few hundred lox calls in a debug build
+const INTERPRETER_STACK: usize = 256 << 20;
+
+enum Mode {
+    Run,
+    DumpAst,
+    LoadAst,
+    Lint,
+    Compile,
+}
+
+const USAGE: &str = "usage: rlox [-O0 | -O1] [--strict] [--profile[=stacks.folded]] \
+                     [--coverage] [--dump-ast=json | --load-ast=json] \
+                     [--max-steps=n] [--max-depth=n] [--timeout=ms] [--max-memory=bytes] \
+                     [--no-host-natives] [script | - | -e code] [args...]\n       \
+                     rlox lint [--disable=rule,...] script\n       \
+                     rlox compile [-O0 | -O1] --target c script [-o out.c]";
+
+fn ast_format(arg: &str) -> &str {
+    let format = &arg[arg.find('=').unwrap() + 1..];
+    if format != "json" {
+        eprintln!("unsupported ast format '{}'", format);
+        process::exit(-1);
+    }
+    format
+}
+
+fn lint_rules(arg: &str) -> Vec<Rule> {
+    let names = &arg[arg.find('=').unwrap() + 1..];
+    names
+        .split(',')
+        .map(|name| match Rule::from_name(name) {
+            Some(rule) => rule,
+            None => {
+                let known: Vec<&str> = Rule::ALL.iter().map(|r| r.name()).collect();
+                eprintln!(
+                    "unknown lint rule '{}', expected one of {}",
+                    name,
+                    known.join(", ")
+                );
+                process::exit(-1);
+            }
+        })
+        .collect()
+}
+
+fn limit<T: FromStr>(arg: &str) -> T {
+    let value = &arg[arg.find('=').unwrap() + 1..];
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("bad limit '{}'", arg);
+        process::exit(-1);
+    })
+}
 
 fn main() {
+    let lox = thread::Builder::new()
+        .stack_size(INTERPRETER_STACK)
+        .spawn(run)
+        .unwrap();
+    if lox.join().is_err() {
+        // the panic's already been reported
+        process::exit(101);
+    }
+}
+
+fn run() {
     let mut l = Lox::new();
+    let mut limits = Limits::default();
+    let mut mode = Mode::Run;
+    let mut rules = Rule::ALL.to_vec();
+
+    let mut out = None;
+    let mut target = None;
+    let mut code = None;
 
-    match env::args().len() {
-        1 => l.run_prompt(),
-        2 => l.run_file(&env::args().collect::<Vec<String>>()[1]),
-        _ => {
-            eprintln!("usage: rlox [script]");
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|a| a.as_str()) {
+        Some("lint") => mode = Mode::Lint,
+        Some("compile") => mode = Mode::Compile,
+        _ => {}
+    }
+    if !matches!(mode, Mode::Run) {
+        args.remove(0);
+    }
+
+    let mut scripts = vec![];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-O0" => l.set_opt_level(0),
+            "-O1" => l.set_opt_level(1),
+            "--strict" => l.set_strict(true),
+            "--profile" => l.set_profile(true),
+            "--coverage" => l.set_coverage(true),
+            "--no-host-natives" => limits.host_natives = false,
+            "--target" => target = args.next(),
+            "-o" => out = args.next(),
+            "-e" if matches!(mode, Mode::Run) => match args.next() {
+                Some(arg) => {
+                    code = Some(arg);
+                    break;
+                }
+                None => usage(),
+            },
+            _ if arg.starts_with("--max-steps=") => limits.steps = Some(limit(&arg)),
+            _ if arg.starts_with("--max-depth=") => limits.call_depth = Some(limit(&arg)),
+            _ if arg.starts_with("--timeout=") => {
+                limits.time = Some(Duration::from_millis(limit(&arg)))
+            }
+            _ if arg.starts_with("--max-memory=") => limits.bytes = Some(limit(&arg)),
+            _ if arg.starts_with("--profile=") => l.set_profile_stacks(&arg["--profile=".len()..]),
+            _ if arg.starts_with("--disable=") => {
+                let disabled = lint_rules(&arg);
+                rules.retain(|rule| !disabled.contains(rule));
+            }
+            _ if arg.starts_with("--dump-ast=") => {
+                ast_format(&arg);
+                mode = Mode::DumpAst;
+            }
+            _ if arg.starts_with("--load-ast=") => {
+                ast_format(&arg);
+                mode = Mode::LoadAst;
+            }
+            _ => {
+                scripts.push(arg);
+                // anything after the script to run is the script's own business
+                if matches!(mode, Mode::Run) {
+                    break;
+                }
+            }
+        }
+    }
+    l.set_args(args.collect());
+    l.set_limits(limits);
+
+    match (mode, scripts.len()) {
+        (Mode::Run, 0) => match &code {
+            Some(code) => l.run(code),
+            None => l.run_prompt(),
+        },
+        (Mode::Run, 1) if scripts[0] == "-" => l.run_stdin(),
+        (Mode::Run, 1) => l.run_file(&scripts[0]),
+        (Mode::DumpAst, 1) => l.dump_ast_file(&scripts[0]),
+        (Mode::LoadAst, 1) => l.run_ast_file(&scripts[0]),
+        (Mode::Lint, 1) => {
+            if l.lint_file(&scripts[0], &rules) > 0 {
+                process::exit(1);
+            }
+        }
+        // c is the only target there is so far, but make people say so
+        (Mode::Compile, 1) if target.as_deref() != Some("c") => {
+            eprintln!("unsupported target '{}'", target.unwrap_or_default());
             process::exit(-1);
         }
+        (Mode::Compile, 1) => {
+            if !l.compile_file(&scripts[0], out.as_deref()) {
+                process::exit(1);
+            }
+        }
+        _ => usage(),
     }
+
+    let _ = io::stdout().flush();
+    process::exit(l.exit_code());
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(-1);
 }
@@ -0,0 +1,227 @@
+use crate::bigint::*;
+use crate::token_type::*;
+use crate::value::*;
+use std::cmp::Ordering;
+
+// arithmetic across the three kinds of number.  the rules:
+//
+//   - int op int stays an integer, going to a bigint rather than overflowing and coming back
+//     down to an i64 once it fits again
+//   - anything with a float in it is done in floats
+//   - `/` always divides as floats, `\` and `%` round the quotient toward negative infinity
+//     (so the remainder takes the divisor's sign).  integer `\` or `%` by zero is an error,
+//     floats follow ieee like `/` does
+//   - comparisons and equality go by numeric value, 1 == 1.0
+
+// two numbers brought to a common kind
+enum Operands {
+    Ints(i64, i64),
+    Bigs(BigInt, BigInt),
+    Floats(f64, f64),
+}
+
+fn operands(l: &Value, r: &Value) -> Option<Operands> {
+    let big = |v: &Value| match v {
+        Value::Int(n) => Some(BigInt::from_i64(*n)),
+        Value::BigInt(n) => Some(n.clone()),
+        _ => None,
+    };
+    Some(match (l, r) {
+        (Value::Int(a), Value::Int(b)) => Operands::Ints(*a, *b),
+        (Value::Int(_) | Value::BigInt(_), Value::Int(_) | Value::BigInt(_)) => {
+            Operands::Bigs(big(l)?, big(r)?)
+        }
+        _ => Operands::Floats(to_float(l)?, to_float(r)?),
+    })
+}
+
+pub fn is_number(v: &Value) -> bool {
+    matches!(v, Value::Number(_) | Value::Int(_) | Value::BigInt(_))
+}
+
+pub fn to_float(v: &Value) -> Option<f64> {
+    match v {
+        Value::Number(n) => Some(*n),
+        Value::Int(n) => Some(*n as f64),
+        Value::BigInt(n) => Some(n.to_f64()),
+        _ => None,
+    }
+}
+
+// None if either side isn't a number or the operator isn't arithmetic, Err for integer division
+// by zero
+pub fn arith(op: TokenType, l: &Value, r: &Value) -> Option<Result<Value, String>> {
+    let operands = match (op, operands(l, r)?) {
+        (TokenType::Slash, Operands::Ints(a, b)) => Operands::Floats(a as f64, b as f64),
+        (TokenType::Slash, Operands::Bigs(a, b)) => Operands::Floats(a.to_f64(), b.to_f64()),
+        (_, operands) => operands,
+    };
+    Some(match operands {
+        Operands::Ints(a, b) => {
+            let res = match op {
+                TokenType::Plus => a.checked_add(b),
+                TokenType::Minus => a.checked_sub(b),
+                TokenType::Star => a.checked_mul(b),
+                TokenType::SlashSlash | TokenType::Percent if b == 0 => {
+                    return Some(Err("division by zero".to_string()))
+                }
+                TokenType::SlashSlash => a.checked_div_euclid(b).map(|q| {
+                    // euclid rounds toward negative infinity only for positive divisors
+                    if b < 0 && a.rem_euclid(b) != 0 {
+                        q - 1
+                    } else {
+                        q
+                    }
+                }),
+                TokenType::Percent => a.checked_rem(b).map(|m| {
+                    if m != 0 && (m < 0) != (b < 0) {
+                        m + b
+                    } else {
+                        m
+                    }
+                }),
+                _ => return None,
+            };
+            match res {
+                Some(n) => Ok(Value::Int(n)),
+                // only overflow lands here, do it again the long way
+                None => return big_arith(op, BigInt::from_i64(a), BigInt::from_i64(b)),
+            }
+        }
+        Operands::Bigs(a, b) => return big_arith(op, a, b),
+        Operands::Floats(a, b) => Ok(Value::Number(match op {
+            TokenType::Plus => a + b,
+            TokenType::Minus => a - b,
+            TokenType::Star => a * b,
+            TokenType::Slash => a / b,
+            TokenType::SlashSlash => (a / b).floor(),
+            TokenType::Percent => {
+                let m = a % b;
+                if m != 0.0 && (m < 0.0) != (b < 0.0) {
+                    m + b
+                } else {
+                    m
+                }
+            }
+            _ => return None,
+        })),
+    })
+}
+
+fn big_arith(op: TokenType, a: BigInt, b: BigInt) -> Option<Result<Value, String>> {
+    let res = match op {
+        TokenType::Plus => a.add(&b),
+        TokenType::Minus => a.sub(&b),
+        TokenType::Star => a.mul(&b),
+        TokenType::SlashSlash | TokenType::Percent => match a.div_mod_floor(&b) {
+            Some((q, _)) if op == TokenType::SlashSlash => q,
+            Some((_, r)) => r,
+            None => return Some(Err("division by zero".to_string())),
+        },
+        _ => return None,
+    };
+    Some(Ok(Value::from_bigint(res)))
+}
+
+// an upper bound on the bytes a bigint result takes, known before doing the arithmetic so the
+// memory limit can stop a huge multiply before it starts rather than after.  None unless it's
+// bigint arithmetic
+pub fn big_result_size(op: TokenType, l: &Value, r: &Value) -> Option<usize> {
+    let size = |v: &Value| match v {
+        Value::BigInt(n) => Some(n.size()),
+        Value::Int(_) => Some(8),
+        _ => None,
+    };
+    if !matches!(l, Value::BigInt(_)) && !matches!(r, Value::BigInt(_)) {
+        return None;
+    }
+    let (l, r) = (size(l)?, size(r)?);
+    match op {
+        TokenType::Star => Some(l + r),
+        TokenType::Plus | TokenType::Minus | TokenType::SlashSlash | TokenType::Percent => {
+            Some(l.max(r) + 4)
+        }
+        _ => None,
+    }
+}
+
+// None if they aren't both numbers, or one of them is NaN
+pub fn compare(l: &Value, r: &Value) -> Option<Ordering> {
+    match operands(l, r)? {
+        Operands::Ints(a, b) => Some(a.cmp(&b)),
+        Operands::Bigs(a, b) => Some(a.cmp(&b)),
+        Operands::Floats(a, b) => a.partial_cmp(&b),
+    }
+}
+
+pub fn negate(v: &Value) -> Option<Value> {
+    match v {
+        Value::Number(n) => Some(Value::Number(-n)),
+        Value::Int(n) => Some(match n.checked_neg() {
+            Some(n) => Value::Int(n),
+            None => Value::from_bigint(BigInt::from_i64(*n).neg()),
+        }),
+        Value::BigInt(n) => Some(Value::from_bigint(n.neg())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn int(n: i64) -> Value {
+        Value::Int(n)
+    }
+
+    fn calc(op: TokenType, l: Value, r: Value) -> String {
+        match arith(op, &l, &r).unwrap() {
+            Ok(v) => v.to_string(),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn mixed_arithmetic() {
+        assert_eq!(calc(TokenType::Plus, int(1), int(2)), "3");
+        assert_eq!(calc(TokenType::Plus, int(1), Value::Number(2.0)), "3.0");
+        assert_eq!(calc(TokenType::Slash, int(10), int(4)), "2.5");
+        assert_eq!(calc(TokenType::Slash, int(4), int(2)), "2.0");
+        assert_eq!(calc(TokenType::SlashSlash, int(-7), int(2)), "-4");
+        assert_eq!(calc(TokenType::SlashSlash, int(7), int(-2)), "-4");
+        assert_eq!(calc(TokenType::SlashSlash, int(-7), int(-2)), "3");
+        assert_eq!(calc(TokenType::Percent, int(-7), int(2)), "1");
+        assert_eq!(calc(TokenType::Percent, int(7), int(-2)), "-1");
+        assert_eq!(calc(TokenType::Percent, Value::Number(-7.5), int(2)), "0.5");
+        assert_eq!(
+            calc(TokenType::SlashSlash, Value::Number(7.5), int(2)),
+            "3.0"
+        );
+        assert_eq!(calc(TokenType::Percent, int(1), int(0)), "division by zero");
+        assert_eq!(calc(TokenType::Slash, int(1), int(0)), "inf");
+
+        // overflow goes big and comes back down
+        let max = int(i64::MAX);
+        let big = arith(TokenType::Plus, &max, &int(1)).unwrap().unwrap();
+        assert_eq!(big.to_string(), "9223372036854775808");
+        assert!(matches!(big, Value::BigInt(_)));
+        assert_eq!(
+            arith(TokenType::Minus, &big, &int(1)).unwrap(),
+            Ok(max.clone())
+        );
+        assert_eq!(
+            calc(TokenType::SlashSlash, int(i64::MIN), int(-1)),
+            "9223372036854775808"
+        );
+        assert_eq!(calc(TokenType::Percent, int(i64::MIN), int(-1)), "0");
+        assert_eq!(
+            negate(&int(i64::MIN)).unwrap().to_string(),
+            "9223372036854775808"
+        );
+
+        assert_eq!(compare(&int(1), &Value::Number(1.5)), Some(Ordering::Less));
+        assert_eq!(compare(&big, &max), Some(Ordering::Greater));
+        assert_eq!(compare(&int(1), &Value::Number(f64::NAN)), None);
+        assert!(arith(TokenType::Plus, &int(1), &Value::Nil).is_none());
+    }
+}
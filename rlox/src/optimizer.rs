@@ -0,0 +1,352 @@
+use crate::ast::*;
+use crate::bigint::*;
+use crate::expr::*;
+use crate::interpreter::*;
+use crate::stmt::*;
+use crate::token_type::*;
+use crate::value::*;
+
+// -O1: folds expressions whose operands are all known ahead of time and throws away code that can
+// never run.  nodes get rewritten in place so ids handed out before (coverage lines, profiles)
+// stay meaningful.  anything that would fail at runtime is left alone, so the error still comes
+// out of the interpreter on the line it always did
+pub struct Optimizer<'a, 'src> {
+    ast: &'a mut Ast<'src>,
+}
+
+impl<'a, 'src> Optimizer<'a, 'src> {
+    pub fn new(ast: &'a mut Ast<'src>) -> Self {
+        Self { ast }
+    }
+
+    pub fn optimize(mut self) {
+        let root = self.ast.root.clone();
+        self.ast.root = self.fold_stmts(&root);
+    }
+
+    // the value of an expression, if it doesn't take running anything to know it
+    fn constant(&self, id: ExprId) -> Option<Constant> {
+        match self.ast.expr(id) {
+            Expr::Constant { value, .. } => Some(value.clone()),
+            Expr::Grouping { expr } => self.constant(*expr),
+            Expr::Literal { token } => match token.ttype {
+                TokenType::String(s) => Some(Constant::String(s.into())),
+                TokenType::Number(n) => Some(Constant::Number(n)),
+                TokenType::Integer(n) => Some(Constant::Int(n)),
+                TokenType::BigInteger => BigInt::parse(token.lexeme)
+                    .and_then(|n| Constant::from_value(&Value::from_bigint(n))),
+                TokenType::True => Some(Constant::Bool(true)),
+                TokenType::False => Some(Constant::Bool(false)),
+                TokenType::Nil => Some(Constant::Nil),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn fold_expr(&mut self, id: ExprId) {
+        let folded = match self.ast.expr(id).clone() {
+            Expr::Grouping { expr } => {
+                self.fold_expr(expr);
+                self.constant(expr).map(|value| (value, self.line(expr)))
+            }
+            Expr::Assign { value, .. } => {
+                self.fold_expr(value);
+                None
+            }
+            Expr::Binary { op, left, right } => {
+                self.fold_expr(left);
+                self.fold_expr(right);
+                match (self.constant(left), self.constant(right)) {
+                    (Some(l), Some(r)) => Interpreter::binary_op(&op, l.to_value(), r.to_value())
+                        .ok()
+                        .and_then(|v| Constant::from_value(&v))
+                        .map(|value| (value, op.line)),
+                    _ => None,
+                }
+            }
+            Expr::Unary { op, right } => {
+                self.fold_expr(right);
+                self.constant(right).and_then(|r| {
+                    Interpreter::unary_op(&op, r.to_value())
+                        .ok()
+                        .and_then(|v| Constant::from_value(&v))
+                        .map(|value| (value, op.line))
+                })
+            }
+            Expr::Logical { op, left, right } => {
+                self.fold_expr(left);
+                self.fold_expr(right);
+                if let Some(l) = self.constant(left) {
+                    let truthy = Interpreter::is_truthy(&l.to_value());
+                    // same short-circuit rules as eval_logical: the result is either side as-is
+                    let keep = match (op.ttype, truthy) {
+                        (TokenType::Or, true) | (TokenType::And, false) => left,
+                        _ => right,
+                    };
+                    *self.ast.expr_mut(id) = self.ast.expr(keep).clone();
+                }
+                None
+            }
+            Expr::Call { callee, args, .. } => {
+                self.fold_expr(callee);
+                for arg in args {
+                    self.fold_expr(arg);
+                }
+                None
+            }
+            Expr::Get { object, .. } => {
+                self.fold_expr(object);
+                None
+            }
+            Expr::Function(fun) => {
+                self.fold_fun(fun);
+                None
+            }
+            Expr::Literal { .. } | Expr::Variable { .. } | Expr::Constant { .. } => None,
+        };
+
+        if let Some((value, line)) = folded {
+            *self.ast.expr_mut(id) = Expr::Constant { value, line };
+        }
+    }
+
+    // best guess at where an expression sits, for constants pulled out of groupings
+    fn line(&self, id: ExprId) -> usize {
+        match self.ast.expr(id) {
+            Expr::Constant { line, .. } => *line,
+            Expr::Literal { token } => token.line,
+            Expr::Grouping { expr } => self.line(*expr),
+            _ => 0,
+        }
+    }
+
+    fn fold_fun(&mut self, fun: FunId) {
+        let body = self.ast.fun(fun).body.clone();
+        self.ast.fun_mut(fun).body = self.fold_stmts(&body);
+    }
+
+    // folds a statement list, dropping anything that does nothing and everything after a return
+    // or throw
+    fn fold_stmts(&mut self, stmts: &[StmtId]) -> Vec<StmtId> {
+        let mut out = vec![];
+        for &id in stmts {
+            if !self.fold_stmt(id) {
+                continue;
+            }
+            out.push(id);
+            if matches!(self.ast.stmt(id), Stmt::Return(..) | Stmt::Throw(..)) {
+                break;
+            }
+        }
+        out
+    }
+
+    // for spots that need some statement there even if it was folded away
+    fn fold_body(&mut self, id: StmtId) {
+        if !self.fold_stmt(id) {
+            *self.ast.stmt_mut(id) = Stmt::Block(vec![]);
+        }
+    }
+
+    // false if the statement can be dropped entirely
+    fn fold_stmt(&mut self, id: StmtId) -> bool {
+        match self.ast.stmt(id).clone() {
+            Stmt::Expr(expr) => {
+                self.fold_expr(expr);
+                self.constant(expr).is_none()
+            }
+            Stmt::Print(expr) | Stmt::Throw(_, expr) | Stmt::Return(_, Some(expr)) => {
+                self.fold_expr(expr);
+                true
+            }
+            Stmt::Var(_, _, init) => {
+                if let Some(init) = init {
+                    self.fold_expr(init);
+                }
+                true
+            }
+            Stmt::Block(stmts) => {
+                let stmts = self.fold_stmts(&stmts);
+                let keep = !stmts.is_empty();
+                *self.ast.stmt_mut(id) = Stmt::Block(stmts);
+                keep
+            }
+            Stmt::If(cond, then, els) => {
+                self.fold_expr(cond);
+                match self.constant(cond) {
+                    Some(c) => {
+                        let taken = match Interpreter::is_truthy(&c.to_value()) {
+                            true => Some(then),
+                            false => els,
+                        };
+                        match taken {
+                            Some(branch) if self.fold_stmt(branch) => {
+                                *self.ast.stmt_mut(id) = self.ast.stmt(branch).clone();
+                                true
+                            }
+                            _ => false,
+                        }
+                    }
+                    None => {
+                        self.fold_body(then);
+                        if let Some(els) = els {
+                            self.fold_body(els);
+                        }
+                        true
+                    }
+                }
+            }
+            Stmt::While(cond, body) => {
+                self.fold_expr(cond);
+                let taken = self
+                    .constant(cond)
+                    .map(|c| Interpreter::is_truthy(&c.to_value()));
+                if taken == Some(false) {
+                    return false;
+                }
+                self.fold_body(body);
+                if taken == Some(true) {
+                    *self.ast.stmt_mut(id) = Stmt::Loop(body);
+                }
+                true
+            }
+            Stmt::Loop(body) => {
+                self.fold_body(body);
+                true
+            }
+            Stmt::Function(fun) => {
+                self.fold_fun(fun);
+                true
+            }
+            Stmt::Try(body, catch, finally) => {
+                self.fold_body(body);
+                if let Some((_, block)) = catch {
+                    self.fold_body(block);
+                }
+                if let Some(finally) = finally {
+                    self.fold_body(finally);
+                }
+                true
+            }
+            Stmt::Yield(_, val) => {
+                if let Some(val) = val {
+                    self.fold_expr(val);
+                }
+                true
+            }
+            Stmt::ForIn(_, iter, body) => {
+                self.fold_expr(iter);
+                self.fold_body(body);
+                true
+            }
+            Stmt::Return(_, None) => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ast_printer::*;
+    use crate::parser::*;
+    use crate::scanner::*;
+    use crate::test_util::*;
+
+    fn run(ast: &Ast) -> (String, ExecuteResult) {
+        run_with(ast, |_| {})
+    }
+
+    fn optimized<'src>(ast: &Ast<'src>) -> Ast<'src> {
+        let mut ast = ast.clone();
+        Optimizer::new(&mut ast).optimize();
+        ast
+    }
+
+    // what each top-level print statement ends up printing, as an s-expression
+    fn printed(ast: &Ast) -> Vec<String> {
+        let mut out = vec![];
+        for id in &ast.root {
+            if let Stmt::Print(expr) = ast.stmt(*id) {
+                out.push(AstPrinter::serialize(ast, *expr));
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn folds_constants() {
+        let src = r#"print 1 + 2 * 3;
+print "a" + "b" + "c";
+print -(4 - 6);
+print nil or "x";
+print 2 > 1 and 3;
+var y = 5;
+print (1 + 1) * y;
+"#;
+        let toks = Scanner::new(src).scan_tokens();
+        let ast = optimized(&Parser::new(&toks).parse().unwrap());
+        assert_eq!(
+            printed(&ast),
+            vec!["7", "\"abc\"", "2", "\"x\"", "3", "(* 2 y)"]
+        );
+        assert_eq!(run(&ast).0, "7\nabc\n2\nx\n3\n10\n");
+    }
+
+    #[test]
+    fn keeps_runtime_errors() {
+        let src = "var x = 1;\n\nprint \"a\" - 1;\n";
+        let toks = Scanner::new(src).scan_tokens();
+        let ast = Parser::new(&toks).parse().unwrap();
+        let before = run(&ast).1.unwrap_err();
+        let after = run(&optimized(&ast)).1.unwrap_err();
+        assert_eq!(after.line, 3);
+        assert_eq!((before.msg, before.line), (after.msg, after.line));
+    }
+
+    #[test]
+    fn drops_dead_code() {
+        let src = r#"if (false) print "never";
+if (1 > 2) print "no"; else print "yes";
+while (false) print "nope";
+"unused";
+fun f() {
+  return 1;
+  print "after return";
+}
+for (;;) {
+  break_out();
+}
+"#;
+        let toks = Scanner::new(src).scan_tokens();
+        let ast = optimized(&Parser::new(&toks).parse().unwrap());
+
+        assert_eq!(ast.root.len(), 3);
+        assert_eq!(printed(&ast), vec!["\"yes\""]);
+        match ast.stmt(ast.root[1]) {
+            Stmt::Function(fun) => assert_eq!(ast.fun(*fun).body.len(), 1),
+            s => panic!("expected function, got {:?}", s),
+        }
+        assert!(matches!(ast.stmt(ast.root[2]), Stmt::Loop(_)));
+    }
+
+    #[test]
+    fn same_output() {
+        let src = r#"var total = 0;
+for (var i = 0; i < 10; i = i + 1) {
+  if (i - 2 * 2 > 0 or false) total = total + i * (1 + 1);
+}
+fun add(a, b) { return a + b + ("" + ""); }
+print total;
+print add("x", "y" + "z");
+print 1 == 1;
+"#;
+        let toks = Scanner::new(src).scan_tokens();
+        let ast = Parser::new(&toks).parse().unwrap();
+        let before = run(&ast);
+        let after = run(&optimized(&ast));
+        assert!(before.1.is_ok());
+        assert_eq!(before.0, after.0);
+        assert_eq!(after.0, "70\nxyz\ntrue\n");
+    }
+}
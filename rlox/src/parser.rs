@@ -1,9 +1,11 @@
+use crate::ast::*;
 use crate::expr::*;
 use crate::lox::*;
+use crate::r#type::*;
 use crate::stmt::*;
 use crate::token::*;
 use crate::token_type::*;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
 #[derive(Debug)]
 pub struct ParseError {
@@ -18,45 +20,87 @@ impl ParseError {
     }
 }
 
-pub type ParseResult = Result<Vec<Stmt>, ParseError>;
+pub type ParseResult<'src> = Result<Ast<'src>, ParseError>;
 
-type StmtResult = Result<Stmt, ParseError>;
-type ExprResult = Result<Expr, ParseError>;
+type StmtResult = Result<StmtId, ParseError>;
+type ExprResult = Result<ExprId, ParseError>;
 
 type ConsumeResult = Result<(), ParseError>;
 
+// how deep expressions and statements can nest before parsing gives up.  everything downstream
+// (checker, optimizer, interpreter) recurses over the tree too, so this is really what keeps
+// something like ten thousand open parens from overflowing the rust stack
+const MAX_NESTING: usize = 200;
+
+// flat chains like `a + b + c` or `else if` after `else if` don't count as nesting, nobody
+// writing one thinks of it that way.  the tree they build is just as deep though, so they're
+// held to a looser limit of their own, one a tree gets through every pass with on an 8M stack
+// in a debug build.  ast_json holds a loaded tree to it as well
+pub const MAX_TREE_DEPTH: usize = 1000;
+
+// stands in for the real EOF if the token list ever runs short, so peek never goes off the end
+static EOF: Token<'static> = Token {
+    ttype: TokenType::EOF,
+    lexeme: "",
+    line: 0,
+};
+
+// walks the scanned tokens in place.  tokens are Copy and only borrow the source, so building
+// nodes out of them never touches the allocator beyond the arena itself
 #[derive(Debug)]
-pub struct Parser {
-    tokens: Vec<Token>,
-    current: RefCell<usize>,
+pub struct Parser<'t, 'src> {
+    tokens: &'t [Token<'src>],
+    current: Cell<usize>,
+    // every node lands in here as soon as it's built, parents only ever hold ids
+    ast: RefCell<Ast<'src>>,
+    // one entry per function being parsed, flipped once a `yield` turns up in its body
+    yields: RefCell<Vec<bool>>,
+    // current nesting depth, checked against MAX_NESTING
+    depth: Cell<usize>,
+    // the same plus every link of the chains around us, checked against MAX_TREE_DEPTH
+    tree_depth: Cell<usize>,
 }
 
-impl Parser {
-    pub fn new(tokens: &[Token]) -> Self {
+impl<'t, 'src> Parser<'t, 'src> {
+    pub fn new(tokens: &'t [Token<'src>]) -> Self {
         Parser {
-            tokens: tokens.to_vec(),
-            current: RefCell::new(0),
+            tokens,
+            current: Cell::new(0),
+            ast: RefCell::new(Ast::new()),
+            yields: RefCell::new(vec![]),
+            depth: Cell::new(0),
+            tree_depth: Cell::new(0),
         }
     }
 
-    pub fn parse(&self) -> ParseResult {
+    pub fn parse(self) -> ParseResult<'src> {
         let mut stmts = vec![];
         while !self.is_at_end() {
             match self.declaration() {
                 Ok(stmt) => stmts.push(stmt),
                 Err(e) => {
-                    self.error(&self.previous(), &e.msg);
+                    self.error(self.previous(), &e.msg);
                     return Err(e);
                 }
             }
         }
 
-        Ok(stmts)
+        let mut ast = self.ast.into_inner();
+        ast.root = stmts;
+        Ok(ast)
+    }
+
+    fn expr(&self, expr: Expr<'src>) -> ExprId {
+        self.ast.borrow_mut().push_expr(expr)
+    }
+
+    fn stmt(&self, stmt: Stmt<'src>, line: usize) -> StmtId {
+        self.ast.borrow_mut().push_stmt(stmt, line)
     }
 
     fn is_match(&self, tts: &[TokenType]) -> bool {
         for tt in tts {
-            if self.check(tt.clone()) {
+            if self.check(*tt) {
                 self.advance();
                 return true;
             }
@@ -78,10 +122,18 @@ impl Parser {
             return false;
         }
 
-        let cur = self.peek().ttype;
+        std::mem::discriminant(&self.peek().ttype) == std::mem::discriminant(&tt)
+    }
 
-        let out = std::mem::discriminant(&cur) == std::mem::discriminant(&tt);
-        out
+    fn check_next(&self, tt: TokenType) -> bool {
+        self.check_ahead(1, tt)
+    }
+
+    fn check_ahead(&self, n: usize, tt: TokenType) -> bool {
+        match self.tokens.get(self.current.get() + n) {
+            Some(next) => std::mem::discriminant(&next.ttype) == std::mem::discriminant(&tt),
+            None => false,
+        }
     }
 
     fn advance(&self) {
@@ -90,12 +142,14 @@ impl Parser {
         }
     }
 
-    fn peek(&self) -> Token {
-        self.tokens[*self.current.borrow()].clone()
+    fn peek(&self) -> &'t Token<'src> {
+        self.tokens.get(self.current.get()).unwrap_or(&EOF)
     }
 
-    fn previous(&self) -> Token {
-        self.tokens[*self.current.borrow() - 1].clone()
+    // the first token if nothing has been consumed yet
+    fn previous(&self) -> &'t Token<'src> {
+        let cur = self.current.get().saturating_sub(1);
+        self.tokens.get(cur).unwrap_or(&EOF)
     }
 
     fn error(&self, t: &Token, msg: &str) -> ParseError {
@@ -104,34 +158,63 @@ impl Parser {
     }
 
     fn bump_current(&self, c: usize) {
-        self.current.replace_with(|&mut cur| cur + c);
+        self.current.set(self.current.get() + c);
     }
 
     fn is_at_end(&self) -> bool {
-        assert!(*self.current.borrow() < self.tokens.len());
         self.peek().ttype == TokenType::EOF
     }
 
+    // run one level of a recursive rule, bailing out once things nest too deep
+    fn nested<T>(&self, rule: impl FnOnce() -> Result<T, ParseError>) -> Result<T, ParseError> {
+        let (depth, tree_depth) = (self.depth.get(), self.tree_depth.get());
+        if depth >= MAX_NESTING {
+            return Err(ParseError::new("too much nesting"));
+        }
+        self.depth.set(depth + 1);
+        let res = self.deeper().and_then(|_| rule());
+        self.depth.set(depth);
+        self.tree_depth.set(tree_depth);
+        res
+    }
+
+    // one more link in a chain.  the loops building left-leaning chains like `a + b + c` or
+    // `f()()` call this on every pass since the tree gets deeper even though the parser's stack
+    // doesn't; whichever nested() they're inside puts the depth back afterwards
+    fn deeper(&self) -> Result<(), ParseError> {
+        if self.tree_depth.get() >= MAX_TREE_DEPTH {
+            return Err(ParseError::new("too much nesting"));
+        }
+        self.tree_depth.set(self.tree_depth.get() + 1);
+        Ok(())
+    }
+
     fn declaration(&self) -> StmtResult {
         if self.is_match(&[TokenType::Var]) {
-            return self.var_declaration();
+            return self.nested(|| self.var_declaration());
+        }
+        // a `fun` not followed by a name starts an anonymous function expression instead
+        if self.check(TokenType::Func) && self.check_next(TokenType::Identifier("")) {
+            self.advance();
+            return self.function("function");
         }
 
         match self.statement() {
-            Ok(stmt) => return Ok(stmt),
+            Ok(stmt) => Ok(stmt),
             Err(err) => {
                 self.synchronize();
-                return Err(err);
+                Err(err)
             }
         }
     }
 
     fn var_declaration(&self) -> StmtResult {
-        self.consume(TokenType::Identifier(String::new()), "expect variable name")?;
-        let tok = self.previous();
+        self.consume(TokenType::Identifier(""), "expect variable name")?;
+        let tok = *self.previous();
+        let ty = self.annotation()?;
 
         if self.is_match(&[TokenType::Semicolon]) {
-            return Ok(Stmt::new_var(&tok));
+            return Ok(self.stmt(Stmt::Var(tok, ty, None), tok.line));
         }
 
         if !self.is_match(&[TokenType::Equal]) {
@@ -143,10 +226,87 @@ impl Parser {
             TokenType::Semicolon,
             "expect ';' after variable declaration",
         )?;
-        return Ok(Stmt::new_var_init(&tok, &initializer));
+        Ok(self.stmt(Stmt::Var(tok, ty, Some(initializer)), tok.line))
+    }
+
+    // optional `: Type` following a variable, parameter or parameter list
+    fn annotation(&self) -> Result<Option<Type>, ParseError> {
+        if !self.is_match(&[TokenType::Colon]) {
+            return Ok(None);
+        }
+
+        self.consume(TokenType::Identifier(""), "expect type after ':'")?;
+        let name = *self.previous();
+        match Type::from_name(name.lexeme) {
+            Some(ty) => Ok(Some(ty)),
+            None => Err(ParseError::new(&format!("unknown type '{}'", name.lexeme))),
+        }
+    }
+
+    // fun name(a, b) { ... }
+    fn function(&self, kind: &str) -> StmtResult {
+        self.consume(TokenType::Identifier(""), &format!("expect {} name", kind))?;
+        let name = *self.previous();
+
+        self.consume(
+            TokenType::LeftParen,
+            &format!("expect '(' after {} name", kind),
+        )?;
+        let fun = self.finish_function(name, kind)?;
+        Ok(self.stmt(Stmt::Function(fun), name.line))
+    }
+
+    // fun (a, b) { ... }
+    fn lambda(&self) -> ExprResult {
+        let keyword = *self.previous();
+        self.consume(TokenType::LeftParen, "expect '(' after fun")?;
+        let fun = self.finish_function(keyword, "function")?;
+        Ok(self.expr(Expr::Function(fun)))
+    }
+
+    // everything from the parameter list on, the opening '(' is expected to be consumed already
+    fn finish_function(&self, name: Token<'src>, kind: &str) -> Result<FunId, ParseError> {
+        let mut params = vec![];
+        let mut sig = Signature::default();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() >= 255 {
+                    self.error(self.peek(), "too many params");
+                }
+                self.consume(TokenType::Identifier(""), "expect parameter name")?;
+                params.push(*self.previous());
+                sig.params.push(self.annotation()?);
+                if !self.is_match(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "expect ')' after parameters")?;
+        sig.ret = self.annotation()?;
+
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("expect '{{' before {} body", kind),
+        )?;
+        self.yields.borrow_mut().push(false);
+        // a body is a level down like any other block, declarations don't go through statement()
+        let body = self.nested(|| self.block_stmts());
+        let generator = self.yields.borrow_mut().pop().unwrap();
+        let decl = FunDecl {
+            name,
+            params,
+            body: body?,
+            sig,
+            generator,
+        };
+        Ok(self.ast.borrow_mut().push_fun(decl))
     }
 
     fn statement(&self) -> StmtResult {
+        self.nested(|| self.any_statement())
+    }
+
+    fn any_statement(&self) -> StmtResult {
         if self.is_match(&[TokenType::If]) {
             return self.if_stmt();
         }
@@ -159,13 +319,26 @@ impl Parser {
         if self.is_match(&[TokenType::For]) {
             return self.for_stmt();
         }
+        if self.is_match(&[TokenType::Return]) {
+            return self.return_stmt();
+        }
+        if self.is_match(&[TokenType::Throw]) {
+            return self.throw_stmt();
+        }
+        if self.is_match(&[TokenType::Yield]) {
+            return self.yield_stmt();
+        }
+        if self.is_match(&[TokenType::Try]) {
+            return self.try_stmt();
+        }
         if self.is_match(&[TokenType::LeftBrace]) {
             return self.block();
         }
-        return self.expr_stmt();
+        self.expr_stmt()
     }
 
     fn if_stmt(&self) -> StmtResult {
+        let line = self.previous().line;
         self.consume(TokenType::LeftParen, "expect '(' after if")?;
         let cond = self.expression()?;
         self.consume(TokenType::RightParen, "expect ')' after condition")?;
@@ -173,10 +346,16 @@ impl Parser {
         let then = self.statement()?;
         let mut els = None;
         if self.is_match(&[TokenType::Else]) {
-            els = Some(self.statement()?);
+            // `else if` is another link in the chain rather than a level down
+            els = Some(if self.check(TokenType::If) {
+                self.deeper()?;
+                self.any_statement()?
+            } else {
+                self.statement()?
+            });
         }
 
-        Ok(Stmt::new_if(&cond, &then, &els))
+        Ok(self.stmt(Stmt::If(cond, then, els), line))
     }
 
     fn for_stmt(&self) -> StmtResult {
@@ -190,8 +369,21 @@ impl Parser {
         // for (var i=0; i<10; i += 1) {
         //   print i;
         // }
+        let line = self.previous().line;
         self.consume(TokenType::LeftParen, "expect '(' after for")?;
-        let init: Option<Stmt>;
+        if self.check(TokenType::Var) && self.check_ahead(2, TokenType::In) {
+            self.advance();
+        }
+        if self.check(TokenType::Identifier("")) && self.check_next(TokenType::In) {
+            return self.for_in_stmt(line);
+        }
+        // the block, while and block it turns into, and the statement around the increment, are
+        // all levels of the tree too
+        for _ in 0..3 {
+            self.deeper()?;
+        }
+
+        let init: Option<StmtId>;
         if self.is_match(&[TokenType::Semicolon]) {
             init = None;
         } else if self.is_match(&[TokenType::Var]) {
@@ -200,7 +392,7 @@ impl Parser {
             init = Some(self.expr_stmt()?);
         }
 
-        let mut cond: Option<Expr>;
+        let mut cond: Option<ExprId>;
         if self.check(TokenType::Semicolon) {
             cond = None;
         } else {
@@ -208,73 +400,153 @@ impl Parser {
         }
         self.consume(TokenType::Semicolon, "expect ';' after loop condition")?;
 
-        let incr: Option<Expr>;
-        if self.check(TokenType::RightParen) {
-            incr = None;
+        let incr = if self.check(TokenType::RightParen) {
+            None
         } else {
-            incr = Some(self.expression()?);
-        }
+            Some(self.expression()?)
+        };
         self.consume(TokenType::RightParen, "expect ')' after loop increment")?;
 
         // desugar (resugar?) the above components into a while statement
         let mut body = self.statement()?;
         if let Some(incr_exp) = incr {
-            let mut stmts = vec![];
-            stmts.push(body);
-            stmts.push(Stmt::new_expr(&incr_exp));
-            body = Stmt::new_block(&stmts);
+            let incr_stmt = self.stmt(Stmt::Expr(incr_exp), line);
+            body = self.stmt(Stmt::Block(vec![body, incr_stmt]), line);
         }
 
         // forever/spin cond if none present
         if cond.is_none() {
-            cond = Some(Expr::new_literal(Token::new(TokenType::True, "true", 0)));
+            cond = Some(self.expr(Expr::Literal {
+                token: Token::new(TokenType::True, "true", line),
+            }));
         }
 
-        body = Stmt::new_while(&cond.unwrap(), &body);
+        body = self.stmt(Stmt::While(cond.unwrap(), body), line);
 
         // toss incr in front of the while body
         if let Some(init_expr) = init {
-            let mut stmts = vec![];
-            stmts.push(init_expr);
-            stmts.push(body);
-            body = Stmt::new_block(&stmts);
+            body = self.stmt(Stmt::Block(vec![init_expr, body]), line);
         }
         Ok(body)
     }
 
+    // for (x in gen()) body, the `var` in front of x is optional and already skipped
+    fn for_in_stmt(&self, line: usize) -> StmtResult {
+        self.advance();
+        let name = *self.previous();
+        self.advance();
+        let iter = self.expression()?;
+        self.consume(TokenType::RightParen, "expect ')' after for-in generator")?;
+        let body = self.statement()?;
+        Ok(self.stmt(Stmt::ForIn(name, iter, body), line))
+    }
+
     fn print_stmt(&self) -> StmtResult {
+        let line = self.previous().line;
         let val = self.expression()?;
         self.consume(TokenType::Semicolon, "expect ';' after print statement")?;
-        return Ok(Stmt::new_print(&val));
+        Ok(self.stmt(Stmt::Print(val), line))
     }
 
     fn while_stmt(&self) -> StmtResult {
+        let line = self.previous().line;
         self.consume(TokenType::LeftParen, "expect '(' after while")?;
         let cond = self.expression()?;
         self.consume(TokenType::RightParen, "expect ')' after condition")?;
         let body = self.statement()?;
 
-        Ok(Stmt::new_while(&cond, &body))
+        Ok(self.stmt(Stmt::While(cond, body), line))
+    }
+
+    fn return_stmt(&self) -> StmtResult {
+        let keyword = *self.previous();
+        let mut val = None;
+        if !self.check(TokenType::Semicolon) {
+            val = Some(self.expression()?);
+        }
+        self.consume(TokenType::Semicolon, "expect ';' after return value")?;
+        Ok(self.stmt(Stmt::Return(keyword, val), keyword.line))
     }
 
+    fn throw_stmt(&self) -> StmtResult {
+        let keyword = *self.previous();
+        let val = self.expression()?;
+        self.consume(TokenType::Semicolon, "expect ';' after throw value")?;
+        Ok(self.stmt(Stmt::Throw(keyword, val), keyword.line))
+    }
+
+    fn yield_stmt(&self) -> StmtResult {
+        let keyword = *self.previous();
+        match self.yields.borrow_mut().last_mut() {
+            Some(yields) => *yields = true,
+            None => return Err(ParseError::new("can't yield outside a function")),
+        }
+        let mut val = None;
+        if !self.check(TokenType::Semicolon) {
+            val = Some(self.expression()?);
+        }
+        self.consume(TokenType::Semicolon, "expect ';' after yield value")?;
+        Ok(self.stmt(Stmt::Yield(keyword, val), keyword.line))
+    }
+
+    // try { ... } catch (e) { ... } finally { ... }
+    // either of catch or finally may be left off, but not both
+    fn try_stmt(&self) -> StmtResult {
+        let line = self.previous().line;
+        self.consume(TokenType::LeftBrace, "expect '{' after try")?;
+        let body = self.block()?;
+
+        let mut catch = None;
+        if self.is_match(&[TokenType::Catch]) {
+            self.consume(TokenType::LeftParen, "expect '(' after catch")?;
+            self.consume(TokenType::Identifier(""), "expect exception variable name")?;
+            let name = *self.previous();
+            self.consume(TokenType::RightParen, "expect ')' after exception variable")?;
+            self.consume(TokenType::LeftBrace, "expect '{' after catch")?;
+            catch = Some((name, self.block()?));
+        }
+
+        let mut finally = None;
+        if self.is_match(&[TokenType::Finally]) {
+            self.consume(TokenType::LeftBrace, "expect '{' after finally")?;
+            finally = Some(self.block()?);
+        }
+
+        if catch.is_none() && finally.is_none() {
+            return Err(ParseError::new(
+                "expect 'catch' or 'finally' after try block",
+            ));
+        }
+        Ok(self.stmt(Stmt::Try(body, catch, finally), line))
+    }
+
+    // the opening '{' is expected to be consumed already
     fn block(&self) -> StmtResult {
+        let line = self.previous().line;
+        let stmts = self.block_stmts()?;
+        Ok(self.stmt(Stmt::Block(stmts), line))
+    }
+
+    // the opening '{' is expected to be consumed already
+    fn block_stmts(&self) -> Result<Vec<StmtId>, ParseError> {
         let mut stmts = vec![];
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
             stmts.push(self.declaration()?);
         }
 
         self.consume(TokenType::RightBrace, "expect '}' after block")?;
-        Ok(Stmt::new_block(&stmts))
+        Ok(stmts)
     }
 
     fn expr_stmt(&self) -> StmtResult {
+        let line = self.peek().line;
         let expr = self.expression()?;
         self.consume(TokenType::Semicolon, "expect ';' after statement")?;
-        Ok(Stmt::new_expr(&expr))
+        Ok(self.stmt(Stmt::Expr(expr), line))
     }
 
     fn expression(&self) -> ExprResult {
-        self.assignment()
+        self.nested(|| self.assignment())
     }
 
     fn assignment(&self) -> ExprResult {
@@ -282,14 +554,17 @@ impl Parser {
 
         if self.is_match(&[TokenType::Equal]) {
             let equals = self.previous();
-            let val = self.assignment()?;
-
-            if expr.etype == ExprType::Variable {
-                let name = expr.token;
-                return Ok(Expr::new_assign(name, val));
+            let val = self.nested(|| self.assignment())?;
+
+            let target = match self.ast.borrow().expr(expr) {
+                Expr::Variable { name } => Some(*name),
+                _ => None,
+            };
+            if let Some(name) = target {
+                return Ok(self.expr(Expr::Assign { name, value: val }));
             }
 
-            self.error(&equals, "invalid assignment target");
+            self.error(equals, "invalid assignment target");
         }
 
         Ok(expr)
@@ -299,9 +574,14 @@ impl Parser {
         let mut expr = self.and()?;
 
         while self.is_match(&[TokenType::Or]) {
-            let op = self.previous();
+            self.deeper()?;
+            let op = *self.previous();
             let right = self.and()?;
-            expr = Expr::new_or(expr, op, right);
+            expr = self.expr(Expr::Logical {
+                op,
+                left: expr,
+                right,
+            });
         }
         Ok(expr)
     }
@@ -310,9 +590,14 @@ impl Parser {
         let mut expr = self.equality()?;
 
         while self.is_match(&[TokenType::And]) {
-            let op = self.previous();
+            self.deeper()?;
+            let op = *self.previous();
             let right = self.equality()?;
-            expr = Expr::new_and(expr, op, right);
+            expr = self.expr(Expr::Logical {
+                op,
+                left: expr,
+                right,
+            });
         }
         Ok(expr)
     }
@@ -321,9 +606,14 @@ impl Parser {
         let mut expr = self.comparison()?;
 
         while self.is_match(&[TokenType::BangEqual, TokenType::EqualEqual]) {
-            let operator = self.previous();
-            let right = self.comparison()?;
-            expr = Expr::new_binary(operator, expr, right);
+            self.deeper()?;
+            let operator = *self.previous();
+            let right = self.nested(|| self.comparison())?;
+            expr = self.expr(Expr::Binary {
+                op: operator,
+                left: expr,
+                right,
+            });
         }
 
         Ok(expr)
@@ -339,9 +629,13 @@ impl Parser {
             TokenType::LessEqual,
             TokenType::Less,
         ]) {
-            let operator = self.previous();
-            let right = self.comparison()?;
-            expr = Expr::new_binary(operator, expr, right);
+            let operator = *self.previous();
+            let right = self.nested(|| self.comparison())?;
+            expr = self.expr(Expr::Binary {
+                op: operator,
+                left: expr,
+                right,
+            });
         }
 
         Ok(expr)
@@ -352,9 +646,14 @@ impl Parser {
         // println!("term: {:?}", expr);
 
         while self.is_match(&[TokenType::Minus, TokenType::Plus]) {
-            let operator = self.previous();
-            let right = self.factor()?;
-            expr = Expr::new_binary(operator, expr, right);
+            self.deeper()?;
+            let operator = *self.previous();
+            let right = self.nested(|| self.factor())?;
+            expr = self.expr(Expr::Binary {
+                op: operator,
+                left: expr,
+                right,
+            });
         }
 
         Ok(expr)
@@ -364,10 +663,19 @@ impl Parser {
         let mut expr = self.unary()?;
         // println!("factor: {:?}", expr);
 
-        while self.is_match(&[TokenType::Slash, TokenType::Star]) {
-            let operator = self.previous();
-            let right = self.factor()?;
-            expr = Expr::new_binary(operator, expr, right);
+        while self.is_match(&[
+            TokenType::Slash,
+            TokenType::SlashSlash,
+            TokenType::Star,
+            TokenType::Percent,
+        ]) {
+            let operator = *self.previous();
+            let right = self.nested(|| self.factor())?;
+            expr = self.expr(Expr::Binary {
+                op: operator,
+                left: expr,
+                right,
+            });
             // println!("add factor: {:?}", expr);
         }
 
@@ -375,10 +683,13 @@ impl Parser {
     }
 
     fn unary(&self) -> ExprResult {
-        while self.is_match(&[TokenType::Bang, TokenType::Minus]) {
-            let operator = self.previous();
-            let right = self.unary()?;
-            return Ok(Expr::new_unary(operator, right));
+        if self.is_match(&[TokenType::Bang, TokenType::Minus]) {
+            let operator = *self.previous();
+            let right = self.nested(|| self.unary())?;
+            return Ok(self.expr(Expr::Unary {
+                op: operator,
+                right,
+            }));
         }
 
         self.call()
@@ -388,27 +699,35 @@ impl Parser {
         let mut expr = self.primary()?;
 
         loop {
-            if !self.is_match(&[TokenType::LeftParen]) {
+            if self.check(TokenType::LeftParen) || self.check(TokenType::Dot) {
+                self.deeper()?;
+            }
+            if self.is_match(&[TokenType::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.is_match(&[TokenType::Dot]) {
+                self.consume(TokenType::Identifier(""), "expect property name after '.'")?;
+                expr = self.expr(Expr::Get {
+                    object: expr,
+                    name: *self.previous(),
+                });
+            } else {
                 break;
             }
-            expr = self.finish_call(&expr)?;
         }
 
         Ok(expr)
     }
 
     // someFunc(1, 2, "x")
-    fn finish_call(&self, callee: &Expr) -> ExprResult {
+    fn finish_call(&self, callee: ExprId) -> ExprResult {
         let mut args = vec![];
 
         if !self.check(TokenType::RightParen) {
             let mut overflow = false;
             while {
-                if args.len() > 255 {
-                    if !overflow {
-                        overflow = true;
-                        self.error(&self.peek(), "too many args");
-                    }
+                if args.len() > 255 && !overflow {
+                    overflow = true;
+                    self.error(self.peek(), "too many args");
                 }
                 args.push(self.expression()?);
                 self.is_match(&[TokenType::Comma])
@@ -416,35 +735,58 @@ impl Parser {
         }
 
         self.consume(TokenType::RightParen, "expect ')' after args")?;
-        let paren = self.previous();
-        Ok(Expr::new_call(callee, paren, args))
+        let paren = *self.previous();
+        Ok(self.expr(Expr::Call {
+            callee,
+            paren,
+            args,
+        }))
     }
 
     fn primary(&self) -> ExprResult {
         if self.is_match(&[TokenType::False]) {
-            return Ok(Expr::new_literal(self.previous()));
+            return Ok(self.expr(Expr::Literal {
+                token: *self.previous(),
+            }));
         }
         if self.is_match(&[TokenType::True]) {
-            return Ok(Expr::new_literal(self.previous()));
+            return Ok(self.expr(Expr::Literal {
+                token: *self.previous(),
+            }));
         }
         if self.is_match(&[TokenType::Nil]) {
-            return Ok(Expr::new_literal(self.previous()));
+            return Ok(self.expr(Expr::Literal {
+                token: *self.previous(),
+            }));
         }
         // possibly a more idiomatic way to do this exists.  for now just hack
         // in arbitrary values as the matching logic only cares about the
         // variant types themselves, not the contained values
-        if self.is_match(&[TokenType::Number(0.0), TokenType::String("".to_string())]) {
-            return Ok(Expr::new_literal(self.previous()));
+        if self.is_match(&[
+            TokenType::Number(0.0),
+            TokenType::Integer(0),
+            TokenType::BigInteger,
+            TokenType::String(""),
+        ]) {
+            return Ok(self.expr(Expr::Literal {
+                token: *self.previous(),
+            }));
+        }
+
+        if self.is_match(&[TokenType::Func]) {
+            return self.lambda();
         }
 
         if self.is_match(&[TokenType::LeftParen]) {
             let expr = self.expression()?;
             self.consume(TokenType::RightParen, "expect ')' after expression.")?;
-            return Ok(Expr::new_grouping(expr));
+            return Ok(self.expr(Expr::Grouping { expr }));
         }
 
-        if self.is_match(&[TokenType::Identifier(String::new())]) {
-            return Ok(Expr::new_var(self.previous()));
+        if self.is_match(&[TokenType::Identifier("")]) {
+            return Ok(self.expr(Expr::Variable {
+                name: *self.previous(),
+            }));
         }
 
         Err(ParseError::new(
@@ -468,7 +810,10 @@ impl Parser {
                 | TokenType::If
                 | TokenType::While
                 | TokenType::Print
-                | TokenType::Return => return,
+                | TokenType::Return
+                | TokenType::Throw
+                | TokenType::Yield
+                | TokenType::Try => return,
                 _ => {}
             }
 
@@ -484,9 +829,9 @@ mod test {
 
     #[test]
     fn test_parser() {
-        let p = Parser::new(&[
+        let toks = [
             Token::new(TokenType::Var, "var", 1),
-            Token::new(TokenType::Identifier("x".to_string()), "x", 1),
+            Token::new(TokenType::Identifier("x"), "x", 1),
             Token::new(TokenType::Equal, "=", 1),
             Token::new(TokenType::Number(1.0), "1", 1),
             Token::new(TokenType::Plus, "+", 1),
@@ -495,8 +840,15 @@ mod test {
             Token::new(TokenType::Number(4.0), "4", 1),
             Token::new(TokenType::Semicolon, ";", 1),
             Token::new(TokenType::EOF, "", 1),
-        ]);
-        let stmts = p.parse().unwrap();
-        println!("{:?}", AstPrinter::serialize_stmts(&stmts));
+        ];
+        let ast = Parser::new(&toks).parse().unwrap();
+        assert_eq!(ast.root.len(), 1);
+        match ast.stmt(ast.root[0]) {
+            Stmt::Var(name, None, Some(init)) => {
+                assert_eq!(name.lexeme, "x");
+                assert_eq!(AstPrinter::serialize(&ast, *init), "(- (+ 1 9) 4)");
+            }
+            stmt => panic!("expected var, got {:?}", stmt),
+        }
     }
 }
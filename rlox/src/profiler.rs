@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+// how many of the hottest lines make it into the report
+const TOP_LINES: usize = 10;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FuncStats {
+    pub native: bool,
+    pub calls: u64,
+    // time from entry to exit, only counted for the outermost activation so recursion doesn't
+    // count the same stretch of time over and over
+    pub inclusive: Duration,
+    // inclusive minus whatever was spent in callees
+    pub exclusive: Duration,
+}
+
+#[derive(Debug)]
+struct Frame {
+    name: String,
+    start: Instant,
+    // inclusive time of direct callees
+    children: Duration,
+}
+
+// records calls and executed lines while the interpreter runs.  the top-level script counts as a
+// function of its own so its time shows up in the report and at the root of every stack
+#[derive(Debug)]
+pub struct Profiler {
+    stack: Vec<Frame>,
+    pub funcs: HashMap<String, FuncStats>,
+    // statement executions per source line
+    pub lines: HashMap<usize, u64>,
+    // exclusive time per call stack, keyed like "script;outer;inner"
+    pub stacks: HashMap<String, Duration>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        let mut p = Self {
+            stack: vec![],
+            funcs: HashMap::new(),
+            lines: HashMap::new(),
+            stacks: HashMap::new(),
+        };
+        p.enter("script", false);
+        p
+    }
+
+    pub fn line(&mut self, line: usize) {
+        *self.lines.entry(line).or_default() += 1;
+    }
+
+    pub fn enter(&mut self, name: &str, native: bool) {
+        let stats = self.funcs.entry(name.to_string()).or_default();
+        stats.native = native;
+        stats.calls += 1;
+        self.stack.push(Frame {
+            name: name.to_string(),
+            start: Instant::now(),
+            children: Duration::ZERO,
+        });
+    }
+
+    pub fn leave(&mut self) {
+        let path = self.path();
+        let frame = match self.stack.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let elapsed = frame.start.elapsed();
+        let exclusive = elapsed.saturating_sub(frame.children);
+
+        let recursing = self.stack.iter().any(|f| f.name == frame.name);
+        let stats = self.funcs.get_mut(&frame.name).unwrap();
+        if !recursing {
+            stats.inclusive += elapsed;
+        }
+        stats.exclusive += exclusive;
+        *self.stacks.entry(path).or_default() += exclusive;
+
+        if let Some(parent) = self.stack.last_mut() {
+            parent.children += elapsed;
+        }
+    }
+
+    // close out the script frame along with anything an error unwound through
+    pub fn finish(&mut self) {
+        while !self.stack.is_empty() {
+            self.leave();
+        }
+    }
+
+    fn path(&self) -> String {
+        let names: Vec<&str> = self.stack.iter().map(|f| f.name.as_str()).collect();
+        names.join(";")
+    }
+
+    pub fn report(&self) -> String {
+        let mut funcs: Vec<(&String, &FuncStats)> = self.funcs.iter().collect();
+        funcs.sort_by(|a, b| b.1.exclusive.cmp(&a.1.exclusive).then(a.0.cmp(b.0)));
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{:<24} {:>10} {:>14} {:>14}",
+            "function", "calls", "inclusive", "exclusive"
+        );
+        for (name, stats) in funcs {
+            let label = match stats.native {
+                true => format!("{} (native)", name),
+                false => name.to_string(),
+            };
+            let _ = writeln!(
+                out,
+                "{:<24} {:>10} {:>14} {:>14}",
+                label,
+                stats.calls,
+                format!("{:.3?}", stats.inclusive),
+                format!("{:.3?}", stats.exclusive)
+            );
+        }
+
+        let mut lines: Vec<(&usize, &u64)> = self.lines.iter().collect();
+        lines.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        let _ = writeln!(out, "\n{:<24} {:>10}", "line", "hits");
+        for (line, hits) in lines.iter().take(TOP_LINES) {
+            let _ = writeln!(out, "{:<24} {:>10}", line, hits);
+        }
+        out
+    }
+
+    // one "a;b;c <microseconds>" line per stack, the format flamegraph.pl and inferno read
+    pub fn write_stacks(&self, w: &mut dyn Write) -> io::Result<()> {
+        let mut stacks: Vec<(&String, &Duration)> = self.stacks.iter().collect();
+        stacks.sort();
+        for (path, time) in stacks {
+            writeln!(w, "{} {}", path, time.as_micros())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn counts_and_stacks() {
+        let mut p = Profiler::new();
+        p.line(1);
+        p.enter("outer", false);
+        p.line(2);
+        for _ in 0..3 {
+            p.enter("inner", false);
+            p.line(3);
+            p.enter("inner", false);
+            p.leave();
+            p.leave();
+        }
+        p.enter("clock", true);
+        p.leave();
+        p.leave();
+        p.finish();
+
+        assert_eq!(p.funcs["script"].calls, 1);
+        assert_eq!(p.funcs["outer"].calls, 1);
+        assert_eq!(p.funcs["inner"].calls, 6);
+        assert!(p.funcs["clock"].native);
+        assert_eq!(p.lines[&3], 3);
+
+        let mut keys: Vec<&String> = p.stacks.keys().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                "script",
+                "script;outer",
+                "script;outer;clock",
+                "script;outer;inner",
+                "script;outer;inner;inner"
+            ]
+        );
+
+        // nothing runs outside of outer, so it accounts for all of the script's time
+        let outer = &p.funcs["outer"];
+        assert!(outer.inclusive <= p.funcs["script"].inclusive);
+        assert!(outer.exclusive <= outer.inclusive);
+        assert!(p.funcs["inner"].inclusive <= outer.inclusive);
+
+        let report = p.report();
+        assert!(report.contains("clock (native)"));
+        let mut buf = vec![];
+        p.write_stacks(&mut buf).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap().lines().count(), 5);
+    }
+}
@@ -0,0 +1,1053 @@
+/* runtime for programs built with `rlox compile --target c`.  gets pasted in front of the
+ * generated code so the output is a single file that only needs libc and libm:
+ *
+ *   rlox compile --target c script.lox -o script.c && cc script.c -o script -lm
+ *
+ * every live value sits either in a global or in a slot on rt_stack.  generated code stores each
+ * intermediate result in a slot of its own, so the collector can find everything by scanning
+ * those two places and never has to know about the C stack.  build with -DRT_GC_STRESS to
+ * collect on every allocation */
+
+#include <limits.h>
+#include <math.h>
+#include <setjmp.h>
+#include <stdarg.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <time.h>
+
+/* not every program needs every operator */
+#pragma GCC diagnostic ignored "-Wunused-function"
+
+/* T_NUM is a float, T_INT an integer.  an integer that outgrows a long long carries on as an
+ * O_BIG, and comes back down to a T_INT once it fits again */
+typedef enum { T_UNDEF, T_NIL, T_BOOL, T_NUM, T_INT, T_OBJ } Tag;
+typedef enum { O_STR, O_FUN, O_ERR, O_CELL, O_BIG } ObjKind;
+
+typedef struct Obj {
+    ObjKind kind;
+    unsigned char marked;
+    size_t size;
+    struct Obj *next;
+} Obj;
+
+typedef struct {
+    Tag tag;
+    union {
+        int b;
+        double n;
+        long long i;
+        Obj *o;
+    } as;
+} Value;
+
+typedef struct {
+    Obj obj;
+    size_t len;
+    char chars[];
+} ObjString;
+
+typedef struct ObjFun ObjFun;
+typedef Value (*LoxFn)(ObjFun *self, Value *args);
+
+/* lox functions and natives alike.  captured holds the cells of the locals a closure uses from
+ * the functions around it, shared with them the same way the interpreter does it */
+struct ObjFun {
+    Obj obj;
+    const char *name;
+    int arity;
+    int native;
+    LoxFn fn;
+    int ncaptured;
+    Value captured[];
+};
+
+/* what a catch clause gets for errors raised by the runtime itself */
+typedef struct {
+    Obj obj;
+    Value msg;
+    int line;
+} ObjError;
+
+/* a local that some closure captures lives in one of these instead of straight in its slot, so
+ * the function it belongs to and every closure using it see the same variable.  only ever held
+ * in slots and in ObjFun::captured, scripts never get their hands on one */
+typedef struct {
+    Obj obj;
+    Value value;
+} ObjCell;
+
+/* integers past a long long, laid out like bigint.rs: sign plus magnitude in base 10^9 limbs,
+ * least significant first, with no high zero limbs.  never zero, that's always a T_INT */
+typedef struct {
+    Obj obj;
+    int neg;
+    int len;
+    unsigned limbs[];
+} ObjBig;
+
+#define RT_BASE 1000000000u
+#define RT_BASE_DIGITS 9
+
+typedef struct Handler {
+    jmp_buf jmp;
+    Value *sp;
+    int depth;
+    struct Handler *prev;
+    Value err;
+} Handler;
+
+#define RT_STACK_MAX (1 << 20)
+
+/* calls nested any deeper are a runtime error rather than a crash, the same limit and error the
+ * interpreter has by default */
+#define RT_MAX_DEPTH 1000
+
+static Value rt_stack[RT_STACK_MAX];
+static Value *rt_sp = rt_stack;
+static Value *rt_globals;
+static const char **rt_global_names;
+static int rt_nglobals;
+static Handler *rt_handler;
+static int rt_depth;
+
+static Obj *rt_objects;
+static size_t rt_allocated;
+static size_t rt_next_gc = 1 << 20;
+
+static Value rt_nil(void) { Value v; v.tag = T_NIL; v.as.n = 0; return v; }
+static Value rt_bool(int b) { Value v; v.tag = T_BOOL; v.as.b = b; return v; }
+static Value rt_num(double n) { Value v; v.tag = T_NUM; v.as.n = n; return v; }
+static Value rt_int(long long i) { Value v; v.tag = T_INT; v.as.i = i; return v; }
+static Value rt_obj(Obj *o) { Value v; v.tag = T_OBJ; v.as.o = o; return v; }
+
+static int rt_is(Value v, ObjKind kind) { return v.tag == T_OBJ && v.as.o->kind == kind; }
+#define RT_STR(v) ((ObjString *)(v).as.o)
+#define RT_FUN(v) ((ObjFun *)(v).as.o)
+#define RT_ERR(v) ((ObjError *)(v).as.o)
+#define RT_CELL(v) ((ObjCell *)(v).as.o)
+#define RT_BIG(v) ((ObjBig *)(v).as.o)
+
+static void rt_throw(Value v, int line);
+static void rt_error(int line, const char *fmt, ...);
+
+/* ---- gc ---- */
+
+static void rt_mark(Value v) {
+    if (v.tag != T_OBJ || v.as.o->marked)
+        return;
+    v.as.o->marked = 1;
+    switch (v.as.o->kind) {
+    case O_STR:
+        break;
+    case O_FUN:
+        for (int i = 0; i < RT_FUN(v)->ncaptured; i++)
+            rt_mark(RT_FUN(v)->captured[i]);
+        break;
+    case O_ERR:
+        rt_mark(RT_ERR(v)->msg);
+        break;
+    case O_CELL:
+        rt_mark(RT_CELL(v)->value);
+        break;
+    case O_BIG:
+        break;
+    }
+}
+
+static void rt_collect(void) {
+    for (Value *v = rt_stack; v < rt_sp; v++)
+        rt_mark(*v);
+    for (int i = 0; i < rt_nglobals; i++)
+        rt_mark(rt_globals[i]);
+
+    Obj **link = &rt_objects;
+    while (*link) {
+        Obj *o = *link;
+        if (o->marked) {
+            o->marked = 0;
+            link = &o->next;
+        } else {
+            *link = o->next;
+            rt_allocated -= o->size;
+            free(o);
+        }
+    }
+    rt_next_gc = rt_allocated * 2 > (1 << 20) ? rt_allocated * 2 : (1 << 20);
+}
+
+static Obj *rt_alloc(size_t size, ObjKind kind) {
+#ifdef RT_GC_STRESS
+    rt_collect();
+#else
+    if (rt_allocated + size > rt_next_gc)
+        rt_collect();
+#endif
+    Obj *o = malloc(size);
+    if (!o) {
+        fprintf(stderr, "out of memory\n");
+        exit(70);
+    }
+    o->kind = kind;
+    o->marked = 0;
+    o->size = size;
+    o->next = rt_objects;
+    rt_objects = o;
+    rt_allocated += size;
+    return o;
+}
+
+/* ---- frames ---- */
+
+static Value *rt_enter(int slots) {
+    /* leave one slot spare for rt_error to build the error in */
+    if (rt_sp + slots >= rt_stack + RT_STACK_MAX)
+        rt_error(0, "stack overflow");
+    Value *fp = rt_sp;
+    for (int i = 0; i < slots; i++)
+        fp[i] = rt_nil();
+    rt_sp += slots;
+    return fp;
+}
+
+#define rt_leave(fp) (rt_sp = (fp))
+
+/* for natives that need a line to blame */
+static int rt_call_line;
+
+/* a function's frame, one call deeper.  like in the interpreter, running out of depth can't be
+ * caught: with the handlers out of the way the error goes straight to the top and exits */
+static Value *rt_enter_fn(int slots) {
+    if (rt_depth >= RT_MAX_DEPTH) {
+        rt_handler = NULL;
+        rt_error(rt_call_line, "call depth limit of %d exceeded", RT_MAX_DEPTH);
+    }
+    rt_depth++;
+    return rt_enter(slots);
+}
+
+#define rt_leave_fn(fp) (rt_depth--, rt_leave(fp))
+
+/* the call in `return f(x)`, waiting for rt_call to make it.  codegen only lowers calls with few
+ * enough args to have a function on the other end, see CGen::tail_call */
+#define RT_MAX_ARGS 255
+
+static struct {
+    int pending;
+    Value callee;
+    Value args[RT_MAX_ARGS];
+    int argc;
+    int line;
+} rt_tail_call;
+
+/* leaves the calling function's frame and hands the call back to rt_call, which makes it in that
+ * frame's place.  nothing allocates before rt_call picks it up, so the collector doesn't need to
+ * know about rt_tail_call */
+static Value rt_tail(Value callee, Value *args, int argc, int line, Value *fp) {
+    rt_tail_call.pending = 1;
+    rt_tail_call.callee = callee;
+    for (int i = 0; i < argc; i++)
+        rt_tail_call.args[i] = args[i];
+    rt_tail_call.argc = argc;
+    rt_tail_call.line = line;
+    rt_leave_fn(fp);
+    return rt_nil();
+}
+
+/* ---- values ---- */
+
+static Value rt_str(const char *chars, size_t len) {
+    ObjString *s = (ObjString *)rt_alloc(sizeof(ObjString) + len + 1, O_STR);
+    s->len = len;
+    memcpy(s->chars, chars, len);
+    s->chars[len] = '\0';
+    return rt_obj(&s->obj);
+}
+
+static Value rt_closure(LoxFn fn, const char *name, int arity, int ncaptured) {
+    ObjFun *f = (ObjFun *)rt_alloc(sizeof(ObjFun) + ncaptured * sizeof(Value), O_FUN);
+    f->name = name;
+    f->arity = arity;
+    f->native = 0;
+    f->fn = fn;
+    f->ncaptured = ncaptured;
+    for (int i = 0; i < ncaptured; i++)
+        f->captured[i] = rt_nil();
+    return rt_obj(&f->obj);
+}
+
+static void rt_capture(Value fun, int i, Value v) { RT_FUN(fun)->captured[i] = v; }
+
+static Value rt_cell(Value v) {
+    Value cell = rt_obj(rt_alloc(sizeof(ObjCell), O_CELL));
+    RT_CELL(cell)->value = v;
+    return cell;
+}
+
+static Value rt_native(LoxFn fn, const char *name, int arity) {
+    Value f = rt_closure(fn, name, arity, 0);
+    RT_FUN(f)->native = 1;
+    return f;
+}
+
+/* ---- bigints ---- */
+
+/* the arithmetic itself works on these, with the limbs on the malloc heap rather than the
+ * collector's, so nothing moves or goes away while it runs.  only the result becomes an ObjBig */
+typedef struct {
+    int neg;
+    int len;
+    unsigned *limbs;
+} Big;
+
+static unsigned *rt_limbs(int n) {
+    unsigned *limbs = calloc(n > 0 ? n : 1, sizeof(unsigned));
+    if (!limbs) {
+        fprintf(stderr, "out of memory\n");
+        exit(70);
+    }
+    return limbs;
+}
+
+static int rt_is_int(Value v) { return v.tag == T_INT || rt_is(v, O_BIG); }
+
+static void big_trim(Big *b) {
+    while (b->len > 0 && b->limbs[b->len - 1] == 0)
+        b->len--;
+    if (b->len == 0)
+        b->neg = 0;
+}
+
+static Big big_of(Value v) {
+    Big b;
+    if (v.tag == T_INT) {
+        /* negated as unsigned so LLONG_MIN doesn't overflow */
+        unsigned long long u = (unsigned long long)v.as.i;
+        if (v.as.i < 0)
+            u = -u;
+        b.neg = v.as.i < 0;
+        b.len = 0;
+        b.limbs = rt_limbs(3);
+        for (; u > 0; u /= RT_BASE)
+            b.limbs[b.len++] = u % RT_BASE;
+        return b;
+    }
+    b.neg = RT_BIG(v)->neg;
+    b.len = RT_BIG(v)->len;
+    b.limbs = rt_limbs(b.len);
+    memcpy(b.limbs, RT_BIG(v)->limbs, b.len * sizeof(unsigned));
+    return b;
+}
+
+/* back to a value, and back down to a plain int if it fits, like Value::from_bigint.  takes the
+ * limbs off b's hands */
+static Value big_value(Big b) {
+    big_trim(&b);
+    unsigned long long u = 0;
+    int fits = 1;
+    for (int i = b.len - 1; i >= 0 && fits; i--) {
+        if (u > (ULLONG_MAX - b.limbs[i]) / RT_BASE)
+            fits = 0;
+        else
+            u = u * RT_BASE + b.limbs[i];
+    }
+    if (fits && !b.neg && u <= LLONG_MAX) {
+        free(b.limbs);
+        return rt_int((long long)u);
+    }
+    if (fits && b.neg && u <= (unsigned long long)LLONG_MAX + 1) {
+        free(b.limbs);
+        return rt_int(-(long long)(u - 1) - 1);
+    }
+    ObjBig *o = (ObjBig *)rt_alloc(sizeof(ObjBig) + b.len * sizeof(unsigned), O_BIG);
+    o->neg = b.neg;
+    o->len = b.len;
+    memcpy(o->limbs, b.limbs, b.len * sizeof(unsigned));
+    free(b.limbs);
+    return rt_obj(&o->obj);
+}
+
+/* an integer literal too big for a long long, digits with an optional leading '-' */
+static Value rt_big_lit(const char *s) {
+    Big b;
+    b.neg = *s == '-';
+    if (b.neg)
+        s++;
+    int n = (int)strlen(s);
+    b.len = 0;
+    b.limbs = rt_limbs(n / RT_BASE_DIGITS + 1);
+    for (int end = n; end > 0; end -= RT_BASE_DIGITS) {
+        int start = end > RT_BASE_DIGITS ? end - RT_BASE_DIGITS : 0;
+        unsigned limb = 0;
+        for (int i = start; i < end; i++)
+            limb = limb * 10 + (unsigned)(s[i] - '0');
+        b.limbs[b.len++] = limb;
+    }
+    return big_value(b);
+}
+
+static int big_cmp_mag(const Big *a, const Big *b) {
+    if (a->len != b->len)
+        return a->len < b->len ? -1 : 1;
+    for (int i = a->len - 1; i >= 0; i--)
+        if (a->limbs[i] != b->limbs[i])
+            return a->limbs[i] < b->limbs[i] ? -1 : 1;
+    return 0;
+}
+
+static int big_cmp(const Big *a, const Big *b) {
+    if (a->neg != b->neg)
+        return a->neg ? -1 : 1;
+    return a->neg ? big_cmp_mag(b, a) : big_cmp_mag(a, b);
+}
+
+static Big big_add_mag(const Big *a, const Big *b) {
+    int n = a->len > b->len ? a->len : b->len;
+    Big r = {0, 0, rt_limbs(n + 1)};
+    unsigned long long carry = 0;
+    for (int i = 0; i < n; i++) {
+        unsigned long long cur = carry;
+        cur += i < a->len ? a->limbs[i] : 0;
+        cur += i < b->len ? b->limbs[i] : 0;
+        r.limbs[r.len++] = cur % RT_BASE;
+        carry = cur / RT_BASE;
+    }
+    if (carry)
+        r.limbs[r.len++] = (unsigned)carry;
+    return r;
+}
+
+/* a - b, with a at least as big as b */
+static Big big_sub_mag(const Big *a, const Big *b) {
+    Big r = {0, a->len, rt_limbs(a->len)};
+    long long borrow = 0;
+    for (int i = 0; i < a->len; i++) {
+        long long cur = (long long)a->limbs[i] - (i < b->len ? b->limbs[i] : 0) - borrow;
+        borrow = cur < 0;
+        r.limbs[i] = (unsigned)(cur < 0 ? cur + RT_BASE : cur);
+    }
+    big_trim(&r);
+    return r;
+}
+
+static Big big_add(const Big *a, const Big *b) {
+    Big r;
+    if (a->neg == b->neg) {
+        r = big_add_mag(a, b);
+        r.neg = a->neg;
+    } else if (big_cmp_mag(a, b) < 0) {
+        r = big_sub_mag(b, a);
+        r.neg = b->neg;
+    } else {
+        r = big_sub_mag(a, b);
+        r.neg = a->neg;
+    }
+    big_trim(&r);
+    return r;
+}
+
+static Big big_sub(const Big *a, const Big *b) {
+    Big neg = *b;
+    neg.neg = !b->neg;
+    return big_add(a, &neg);
+}
+
+static Big big_mul(const Big *a, const Big *b) {
+    int n = a->len + b->len;
+    unsigned long long *out = calloc(n > 0 ? n : 1, sizeof(unsigned long long));
+    if (!out) {
+        fprintf(stderr, "out of memory\n");
+        exit(70);
+    }
+    for (int i = 0; i < a->len; i++) {
+        unsigned long long carry = 0;
+        for (int j = 0; j < b->len; j++) {
+            unsigned long long cur = out[i + j] + carry;
+            cur += (unsigned long long)a->limbs[i] * b->limbs[j];
+            out[i + j] = cur % RT_BASE;
+            carry = cur / RT_BASE;
+        }
+        out[i + b->len] += carry;
+    }
+    Big r = {a->neg != b->neg, n, rt_limbs(n)};
+    for (int i = 0; i < n; i++)
+        r.limbs[i] = (unsigned)out[i];
+    free(out);
+    big_trim(&r);
+    return r;
+}
+
+/* a times a single limb-sized m, one limb longer than a */
+static void big_mul_small(const unsigned *a, int n, unsigned long long m, unsigned *out) {
+    unsigned long long carry = 0;
+    for (int i = 0; i < n; i++) {
+        unsigned long long cur = a[i] * m + carry;
+        out[i] = cur % RT_BASE;
+        carry = cur / RT_BASE;
+    }
+    out[n] = (unsigned)carry;
+}
+
+/* truncating division of magnitudes, knuth's algorithm D the same way bigint.rs's div_mag does
+ * it: scale both so the divisor's top limb is at least half the base, then guess each quotient
+ * limb from the top two and walk it down until it fits */
+static void big_div_mag(const Big *a, const Big *b, Big *q, Big *r) {
+    if (big_cmp_mag(a, b) < 0) {
+        *q = (Big){0, 0, rt_limbs(0)};
+        *r = (Big){0, a->len, rt_limbs(a->len)};
+        memcpy(r->limbs, a->limbs, a->len * sizeof(unsigned));
+        return;
+    }
+
+    unsigned long long scale = RT_BASE / ((unsigned long long)b->limbs[b->len - 1] + 1);
+    int rlen = a->len + 1;
+    unsigned *rem = rt_limbs(rlen);
+    unsigned *div = rt_limbs(b->len + 1);
+    big_mul_small(a->limbs, a->len, scale, rem);
+    big_mul_small(b->limbs, b->len, scale, div);
+    int n = b->len;
+    unsigned long long top = div[n - 1];
+    unsigned *prod = rt_limbs(n + 1);
+
+    *q = (Big){0, rlen - n, rt_limbs(rlen - n)};
+    for (int j = rlen - n - 1; j >= 0; j--) {
+        unsigned long long hi = (unsigned long long)rem[j + n] * RT_BASE + rem[j + n - 1];
+        unsigned long long guess = hi / top < RT_BASE - 1 ? hi / top : RT_BASE - 1;
+        for (;;) {
+            big_mul_small(div, n, guess, prod);
+            Big window = {0, n + 1, rem + j}, p = {0, n + 1, prod};
+            while (window.len > 0 && window.limbs[window.len - 1] == 0)
+                window.len--;
+            while (p.len > 0 && p.limbs[p.len - 1] == 0)
+                p.len--;
+            if (big_cmp_mag(&window, &p) >= 0) {
+                long long borrow = 0;
+                for (int i = 0; i <= n; i++) {
+                    long long cur = (long long)rem[j + i] - prod[i] - borrow;
+                    borrow = cur < 0;
+                    rem[j + i] = (unsigned)(cur < 0 ? cur + RT_BASE : cur);
+                }
+                break;
+            }
+            guess--;
+        }
+        q->limbs[j] = (unsigned)guess;
+    }
+
+    /* undo the scaling on what's left over */
+    *r = (Big){0, n, rt_limbs(n)};
+    unsigned long long carry = 0;
+    for (int i = n - 1; i >= 0; i--) {
+        unsigned long long cur = carry * RT_BASE + rem[i];
+        r->limbs[i] = (unsigned)(cur / scale);
+        carry = cur % scale;
+    }
+    free(rem);
+    free(div);
+    free(prod);
+    big_trim(q);
+    big_trim(r);
+}
+
+/* quotient rounded toward negative infinity and the remainder that goes with it, like
+ * BigInt::div_mod_floor.  b isn't zero */
+static void big_div_mod_floor(const Big *a, const Big *b, Big *q, Big *r) {
+    big_div_mag(a, b, q, r);
+    q->neg = a->neg != b->neg;
+    r->neg = a->neg;
+    big_trim(q);
+    big_trim(r);
+    if (r->len > 0 && r->neg != b->neg) {
+        Big one = {0, 1, (unsigned[]){1}};
+        Big q1 = big_sub(q, &one), r1 = big_add(r, b);
+        free(q->limbs);
+        free(r->limbs);
+        *q = q1;
+        *r = r1;
+    }
+}
+
+/* integer arithmetic that overflowed a long long or already had a bigint in it, done the long way
+ * like number.rs's big_arith.  op is one of + - * / %, / meaning floor division */
+static Value rt_big_arith(char op, Value a, Value b, int line) {
+    Big x = big_of(a), y = big_of(b), r = {0, 0, NULL}, other = {0, 0, NULL};
+    switch (op) {
+    case '+':
+        r = big_add(&x, &y);
+        break;
+    case '-':
+        r = big_sub(&x, &y);
+        break;
+    case '*':
+        r = big_mul(&x, &y);
+        break;
+    default:
+        if (y.len == 0) {
+            free(x.limbs);
+            free(y.limbs);
+            rt_error(line, "division by zero");
+        }
+        if (op == '%')
+            big_div_mod_floor(&x, &y, &other, &r);
+        else
+            big_div_mod_floor(&x, &y, &r, &other);
+        free(other.limbs);
+        break;
+    }
+    free(x.limbs);
+    free(y.limbs);
+    return big_value(r);
+}
+
+static int rt_big_compare(Value a, Value b) {
+    Big x = big_of(a), y = big_of(b);
+    int c = big_cmp(&x, &y);
+    free(x.limbs);
+    free(y.limbs);
+    return c;
+}
+
+static Value rt_big_neg(Value v) {
+    Big b = big_of(v);
+    b.neg = !b.neg;
+    return big_value(b);
+}
+
+/* decimal digits into out, which has room for 9 per limb plus a sign and the terminator */
+static void rt_big_format(const ObjBig *b, char *out) {
+    if (b->neg)
+        *out++ = '-';
+    out += sprintf(out, "%u", b->limbs[b->len - 1]);
+    for (int i = b->len - 2; i >= 0; i--)
+        out += sprintf(out, "%09u", b->limbs[i]);
+}
+
+static char *rt_big_string(const ObjBig *b) {
+    char *s = malloc(b->len * RT_BASE_DIGITS + 2);
+    if (!s) {
+        fprintf(stderr, "out of memory\n");
+        exit(70);
+    }
+    rt_big_format(b, s);
+    return s;
+}
+
+/* through the digits, which gets correctly rounded the same as BigInt::to_f64 */
+static double rt_big_float(Value v) {
+    char *s = rt_big_string(RT_BIG(v));
+    double d = strtod(s, NULL);
+    free(s);
+    return d;
+}
+
+static const char *rt_type_name(Value v) {
+    switch (v.tag) {
+    case T_UNDEF:
+    case T_NIL:
+        return "nil";
+    case T_BOOL:
+        return "boolean";
+    case T_NUM:
+    case T_INT:
+        return "number";
+    case T_OBJ:
+        break;
+    }
+    switch (v.as.o->kind) {
+    case O_STR:
+        return "string";
+    case O_FUN:
+        return RT_FUN(v)->native ? "native function" : "function";
+    case O_ERR:
+        return "error";
+    case O_BIG:
+        return "number";
+    case O_CELL:
+        break;
+    }
+    return "?";
+}
+
+static int rt_truthy(Value v) {
+    if (v.tag == T_NIL)
+        return 0;
+    if (v.tag == T_BOOL)
+        return v.as.b;
+    return 1;
+}
+
+/* same digits rust's f64 Display picks: the shortest that reads back exactly, never with an
+ * exponent.  plus a ".0" on whole numbers, like value::format_float */
+static void rt_format_number(double n, char *out) {
+    if (isnan(n)) {
+        strcpy(out, "NaN");
+        return;
+    }
+    if (isinf(n)) {
+        strcpy(out, n > 0 ? "inf" : "-inf");
+        return;
+    }
+    if (n == 0) {
+        strcpy(out, signbit(n) ? "-0.0" : "0.0");
+        return;
+    }
+
+    char buf[40];
+    for (int p = 1; p <= 17; p++) {
+        snprintf(buf, sizeof buf, "%.*e", p - 1, n);
+        if (strtod(buf, NULL) == n)
+            break;
+    }
+
+    char digits[20];
+    int nd = 0;
+    const char *s = buf;
+    char *o = out;
+    if (*s == '-') {
+        *o++ = '-';
+        s++;
+    }
+    for (; *s && *s != 'e'; s++)
+        if (*s != '.')
+            digits[nd++] = *s;
+    int point = atoi(s + 1) + 1;
+
+    if (point <= 0) {
+        *o++ = '0';
+        *o++ = '.';
+        for (int i = 0; i < -point; i++)
+            *o++ = '0';
+        for (int i = 0; i < nd; i++)
+            *o++ = digits[i];
+    } else {
+        for (int i = 0; i < point; i++)
+            *o++ = i < nd ? digits[i] : '0';
+        *o++ = '.';
+        if (nd > point) {
+            for (int i = point; i < nd; i++)
+                *o++ = digits[i];
+        } else {
+            *o++ = '0';
+        }
+    }
+    *o = '\0';
+}
+
+static void rt_write(FILE *f, Value v) {
+    char num[400];
+    switch (v.tag) {
+    case T_UNDEF:
+    case T_NIL:
+        fputs("(nil)", f);
+        return;
+    case T_BOOL:
+        fputs(v.as.b ? "true" : "false", f);
+        return;
+    case T_NUM:
+        rt_format_number(v.as.n, num);
+        fputs(num, f);
+        return;
+    case T_INT:
+        fprintf(f, "%lld", v.as.i);
+        return;
+    case T_OBJ:
+        break;
+    }
+    switch (v.as.o->kind) {
+    case O_STR:
+        fwrite(RT_STR(v)->chars, 1, RT_STR(v)->len, f);
+        break;
+    case O_FUN:
+        fputs(RT_FUN(v)->native ? "native fn" : "fn", f);
+        break;
+    case O_ERR:
+        fputs("error: ", f);
+        rt_write(f, RT_ERR(v)->msg);
+        break;
+    case O_BIG: {
+        char *digits = rt_big_string(RT_BIG(v));
+        fputs(digits, f);
+        free(digits);
+        break;
+    }
+    case O_CELL:
+        break;
+    }
+}
+
+static void rt_print(Value v) {
+    rt_write(stdout, v);
+    fputc('\n', stdout);
+}
+
+/* ---- errors ---- */
+
+static void rt_throw(Value v, int line) {
+    Handler *h = rt_handler;
+    if (!h) {
+        fflush(stdout);
+        if (rt_is(v, O_ERR)) {
+            fputs("runtime error: ", stderr);
+            rt_write(stderr, RT_ERR(v)->msg);
+        } else {
+            fputs("uncaught exception: ", stderr);
+            rt_write(stderr, v);
+        }
+        fprintf(stderr, "\n[line %d]\n", line);
+        exit(70);
+    }
+    rt_handler = h->prev;
+    h->err = v;
+    rt_sp = h->sp;
+    rt_depth = h->depth;
+    longjmp(h->jmp, 1);
+}
+
+static void rt_error(int line, const char *fmt, ...) {
+    char msg[512];
+    va_list args;
+    va_start(args, fmt);
+    vsnprintf(msg, sizeof msg, fmt, args);
+    va_end(args);
+
+    Value err = rt_obj(rt_alloc(sizeof(ObjError), O_ERR));
+    RT_ERR(err)->msg = rt_nil();
+    RT_ERR(err)->line = line;
+    /* keep the half built error reachable while its message gets allocated */
+    *rt_sp++ = err;
+    RT_ERR(err)->msg = rt_str(msg, strlen(msg));
+    rt_sp--;
+    rt_throw(err, line);
+}
+
+#define RT_TRY(h) ((h).sp = rt_sp, (h).depth = rt_depth, (h).prev = rt_handler, rt_handler = &(h), setjmp((h).jmp) == 0)
+
+/* ---- variables ---- */
+
+static Value rt_global(int i, int line) {
+    if (rt_globals[i].tag == T_UNDEF)
+        rt_error(line, "undefined variable '%s'", rt_global_names[i]);
+    return rt_globals[i];
+}
+
+static void rt_set_global(int i, Value v, int line) {
+    if (rt_globals[i].tag == T_UNDEF)
+        rt_error(line, "undefined variable '%s'", rt_global_names[i]);
+    rt_globals[i] = v;
+}
+
+/* ---- operators, following Interpreter::binary_op and friends ---- */
+
+static int rt_is_num(Value v) { return v.tag == T_NUM || rt_is_int(v); }
+
+static double rt_float(Value v) {
+    if (v.tag == T_INT)
+        return (double)v.as.i;
+    if (rt_is(v, O_BIG))
+        return rt_big_float(v);
+    return v.as.n;
+}
+
+static void rt_numbers(const char *op, Value a, Value b, int line) {
+    if (!rt_is_num(a) || !rt_is_num(b))
+        rt_error(line, "operands of '%s' must be numbers, got %s and %s", op, rt_type_name(a),
+                 rt_type_name(b));
+}
+
+/* mixing rules from number.rs: two ints stay an int, going big rather than overflowing, and
+ * anything else is done in floats */
+#define RT_ARITH(name, opstr, builtin, op)                                                      \
+    static Value name(Value a, Value b, int line) {                                            \
+        rt_numbers(opstr, a, b, line);                                                         \
+        if (a.tag == T_INT && b.tag == T_INT) {                                                \
+            long long r;                                                                       \
+            if (builtin(a.as.i, b.as.i, &r))                                                   \
+                return rt_big_arith(opstr[0], a, b, line);                                     \
+            return rt_int(r);                                                                  \
+        }                                                                                      \
+        if (rt_is_int(a) && rt_is_int(b))                                                      \
+            return rt_big_arith(opstr[0], a, b, line);                                         \
+        return rt_num(rt_float(a) op rt_float(b));                                             \
+    }
+
+static Value rt_add(Value a, Value b, int line) {
+    if (a.tag == T_INT && b.tag == T_INT) {
+        long long r;
+        if (__builtin_add_overflow(a.as.i, b.as.i, &r))
+            return rt_big_arith('+', a, b, line);
+        return rt_int(r);
+    }
+    if (rt_is_int(a) && rt_is_int(b))
+        return rt_big_arith('+', a, b, line);
+    if (rt_is_num(a) && rt_is_num(b))
+        return rt_num(rt_float(a) + rt_float(b));
+    if (rt_is(a, O_STR) && rt_is(b, O_STR)) {
+        size_t len = RT_STR(a)->len + RT_STR(b)->len;
+        ObjString *s = (ObjString *)rt_alloc(sizeof(ObjString) + len + 1, O_STR);
+        s->len = len;
+        memcpy(s->chars, RT_STR(a)->chars, RT_STR(a)->len);
+        memcpy(s->chars + RT_STR(a)->len, RT_STR(b)->chars, RT_STR(b)->len);
+        s->chars[len] = '\0';
+        return rt_obj(&s->obj);
+    }
+    rt_error(line, "operands of '+' must be two numbers or two strings, got %s and %s",
+             rt_type_name(a), rt_type_name(b));
+    return rt_nil();
+}
+
+RT_ARITH(rt_sub, "-", __builtin_sub_overflow, -)
+RT_ARITH(rt_mul, "*", __builtin_mul_overflow, *)
+
+static Value rt_div(Value a, Value b, int line) {
+    rt_numbers("/", a, b, line);
+    return rt_num(rt_float(a) / rt_float(b));
+}
+
+/* // and % round toward negative infinity, so the remainder has the divisor's sign */
+static Value rt_floor_div_mod(Value a, Value b, int line, const char *op, int want_mod) {
+    rt_numbers(op, a, b, line);
+    if (a.tag == T_INT && b.tag == T_INT) {
+        long long x = a.as.i, y = b.as.i;
+        if (y == 0)
+            rt_error(line, "division by zero");
+        if (x == LLONG_MIN && y == -1)
+            return want_mod ? rt_int(0) : rt_big_arith('/', a, b, line);
+        long long q = x / y, m = x % y;
+        if (m != 0 && (m < 0) != (y < 0)) {
+            q -= 1;
+            m += y;
+        }
+        return rt_int(want_mod ? m : q);
+    }
+    if (rt_is_int(a) && rt_is_int(b))
+        return rt_big_arith(want_mod ? '%' : '/', a, b, line);
+    double x = rt_float(a), y = rt_float(b);
+    if (!want_mod)
+        return rt_num(floor(x / y));
+    double m = fmod(x, y);
+    if (m != 0 && (m < 0) != (y < 0))
+        m += y;
+    return rt_num(m);
+}
+
+static Value rt_idiv(Value a, Value b, int line) { return rt_floor_div_mod(a, b, line, "//", 0); }
+static Value rt_mod(Value a, Value b, int line) { return rt_floor_div_mod(a, b, line, "%", 1); }
+
+/* -2 means unordered (NaN) */
+static int rt_compare(Value a, Value b) {
+    if (a.tag == T_INT && b.tag == T_INT)
+        return (a.as.i > b.as.i) - (a.as.i < b.as.i);
+    if (rt_is_int(a) && rt_is_int(b))
+        return rt_big_compare(a, b);
+    double x = rt_float(a), y = rt_float(b);
+    if (isnan(x) || isnan(y))
+        return -2;
+    return (x > y) - (x < y);
+}
+
+static Value rt_gt(Value a, Value b, int line) { rt_numbers(">", a, b, line); return rt_bool(rt_compare(a, b) == 1); }
+static Value rt_ge(Value a, Value b, int line) { rt_numbers(">=", a, b, line); int c = rt_compare(a, b); return rt_bool(c == 1 || c == 0); }
+static Value rt_lt(Value a, Value b, int line) { rt_numbers("<", a, b, line); return rt_bool(rt_compare(a, b) == -1); }
+static Value rt_le(Value a, Value b, int line) { rt_numbers("<=", a, b, line); int c = rt_compare(a, b); return rt_bool(c == -1 || c == 0); }
+
+/* only nil, numbers and strings ever compare equal, same as Interpreter::is_equal */
+static int rt_equal(Value a, Value b) {
+    if (a.tag == T_NIL && b.tag == T_NIL)
+        return 1;
+    if (rt_is_num(a) && rt_is_num(b))
+        return rt_compare(a, b) == 0;
+    if (rt_is(a, O_STR) && rt_is(b, O_STR))
+        return RT_STR(a)->len == RT_STR(b)->len &&
+               memcmp(RT_STR(a)->chars, RT_STR(b)->chars, RT_STR(a)->len) == 0;
+    return 0;
+}
+
+static Value rt_eq(Value a, Value b) { return rt_bool(rt_equal(a, b)); }
+static Value rt_ne(Value a, Value b) { return rt_bool(!rt_equal(a, b)); }
+
+static Value rt_neg(Value v, int line) {
+    if (!rt_is_num(v))
+        rt_error(line, "operand of '-' must be a number, got %s", rt_type_name(v));
+    if (v.tag == T_INT && v.as.i != LLONG_MIN)
+        return rt_int(-v.as.i);
+    if (rt_is_int(v))
+        return rt_big_neg(v);
+    return rt_num(-v.as.n);
+}
+
+/* Interpreter::unary_op only takes numbers for '!' and hands back their truthiness */
+static Value rt_not(Value v, int line) {
+    if (!rt_is_num(v))
+        rt_error(line, "operand of '!' must be a number, got %s", rt_type_name(v));
+    return rt_bool(1);
+}
+
+static Value rt_get(Value v, const char *name, int line) {
+    if (rt_is(v, O_ERR)) {
+        if (strcmp(name, "message") == 0)
+            return RT_ERR(v)->msg;
+        if (strcmp(name, "line") == 0)
+            return rt_int(RT_ERR(v)->line);
+    }
+    rt_error(line, "undefined property '%s' on %s", name, rt_type_name(v));
+    return rt_nil();
+}
+
+/* a function that ended in `return f(x)` left its frame before f got called (see rt_tail).  make
+ * that call from here, so recursing this way doesn't go any deeper, same as the interpreter */
+static Value rt_call(Value callee, Value *args, int argc, int line) {
+    Value *base = rt_sp;
+    for (;;) {
+        rt_call_line = line;
+        if (!rt_is(callee, O_FUN))
+            rt_error(line, "can only call functions, not %s", rt_type_name(callee));
+        ObjFun *f = RT_FUN(callee);
+        if (f->arity != argc)
+            rt_error(line, "%s() expects %d args but got %d", f->name, f->arity, argc);
+        Value ret = f->fn(f, args);
+        rt_leave(base);
+        if (!rt_tail_call.pending)
+            return ret;
+
+        /* the next callee and its args go in a frame of ours */
+        rt_tail_call.pending = 0;
+        argc = rt_tail_call.argc;
+        line = rt_tail_call.line;
+        Value *frame = rt_enter(argc + 1);
+        frame[0] = callee = rt_tail_call.callee;
+        for (int i = 0; i < argc; i++)
+            frame[i + 1] = rt_tail_call.args[i];
+        args = frame + 1;
+    }
+}
+
+/* ---- natives ---- */
+
+static Value rt_clock(ObjFun *self, Value *args) {
+    (void)self;
+    (void)args;
+    return rt_num((double)time(NULL));
+}
+
+/* unlike the interpreter this leaves on the spot, without running any finally blocks */
+static Value rt_exit(ObjFun *self, Value *args) {
+    (void)self;
+    if (args[0].tag != T_INT || args[0].as.i < 0 || args[0].as.i > 255)
+        rt_error(rt_call_line, "exit() expects a code from 0 to 255");
+    fflush(stdout);
+    exit((int)args[0].as.i);
+}
+
+static void rt_init(Value *globals, const char **names, int n) {
+    rt_globals = globals;
+    rt_global_names = names;
+    rt_nglobals = n;
+    for (int i = 0; i < n; i++)
+        globals[i].tag = T_UNDEF;
+    for (int i = 0; i < n; i++)
+        if (strcmp(names[i], "clock") == 0)
+            globals[i] = rt_native(rt_clock, "clock", 0);
+        else if (strcmp(names[i], "exit") == 0)
+            globals[i] = rt_native(rt_exit, "exit", 1);
+}
@@ -1,30 +1,29 @@
 use crate::lox::*;
 use crate::token::*;
 use crate::token_type::*;
-use std::cell::RefCell;
 
 // rust has builtins for these but they allow for lots of stuff in the ut8 range
 // that I don't want to deal with here.  just roll my own
 fn is_digit(c: char) -> bool {
-    c >= '0' && c <= '9'
+    c.is_ascii_digit()
 }
 
 fn is_alpha(c: char) -> bool {
-    c >= 'a' && c <= 'z' || c >= 'A' && c <= 'Z' || c == '_'
+    c.is_ascii_lowercase() || c.is_ascii_uppercase() || c == '_'
 }
 
 fn is_alphanum(c: char) -> bool {
     is_digit(c) || is_alpha(c)
 }
 
-fn keyword(s: &str) -> Option<TokenType> {
+fn keyword(s: &str) -> Option<TokenType<'static>> {
     match s {
         "and" => Some(TokenType::And),
         "class" => Some(TokenType::Class),
         "else" => Some(TokenType::Else),
         "false" => Some(TokenType::False),
         "for" => Some(TokenType::For),
-        "fun" => Some(TokenType::Fun),
+        "fun" => Some(TokenType::Func),
         "if" => Some(TokenType::If),
         "nil" => Some(TokenType::Nil),
         "or" => Some(TokenType::Or),
@@ -35,97 +34,183 @@ fn keyword(s: &str) -> Option<TokenType> {
         "true" => Some(TokenType::True),
         "var" => Some(TokenType::Var),
         "while" => Some(TokenType::While),
+        "throw" => Some(TokenType::Throw),
+        "try" => Some(TokenType::Try),
+        "catch" => Some(TokenType::Catch),
+        "finally" => Some(TokenType::Finally),
+        "yield" => Some(TokenType::Yield),
+        "in" => Some(TokenType::In),
         _ => None,
     }
 }
 
+// hands out tokens one at a time as an iterator, always finishing with a single EOF.  scan_tokens
+// is there for when the whole list is wanted up front
 #[derive(Debug)]
-pub struct Scanner {
-    source: String,
-    tokens: Vec<Token>,
+pub struct Scanner<'src> {
+    source: &'src str,
     start: usize,
-    current: RefCell<usize>,
+    current: usize,
     line: usize,
+    done: bool,
+    // whether the last token could end an operand, which is what makes `//` floor division
+    // rather than the start of a comment
+    operand: bool,
+    // one entry per open paren, true for the ones around an if/while/for/catch header or a
+    // parameter list.  closing those ends a header rather than an operand, so `if (x) // note`
+    // and `fun f(a) // doc` still comment
+    headers: Vec<bool>,
+    // just closed a parameter list, so a `: Type` after it is a return annotation
+    signature: bool,
+    last: Option<TokenType<'src>>,
+    before: Option<TokenType<'src>>,
 }
 
-impl Scanner {
-    pub fn new(s: &str) -> Self {
+impl<'src> Iterator for Scanner<'src> {
+    type Item = Token<'src>;
+
+    fn next(&mut self) -> Option<Token<'src>> {
+        while !self.is_at_end() {
+            // at the beginning of the next lexeme
+            self.start = self.current;
+            if let Some(tok) = self.scan_token() {
+                self.track(tok.ttype);
+                return Some(tok);
+            }
+        }
+
+        if self.done {
+            return None;
+        }
+        self.done = true;
+        Some(Token::new(TokenType::EOF, "", self.line))
+    }
+}
+
+impl<'src> Scanner<'src> {
+    pub fn new(source: &'src str) -> Self {
+        // a `#!` line up top is for the shell, not us.  its newline still gets counted
+        let current = match source.starts_with("#!") {
+            true => source.find('\n').unwrap_or(source.len()),
+            false => 0,
+        };
         Scanner {
-            source: s.to_string(),
-            tokens: vec![],
+            source,
             start: 0,
-            current: RefCell::new(0),
+            current,
             line: 1,
+            done: false,
+            operand: false,
+            headers: vec![],
+            signature: false,
+            last: None,
+            before: None,
+        }
+    }
+
+    // keeps up what `//` needs to know about the tokens before it
+    fn track(&mut self, ttype: TokenType<'src>) {
+        let annotation = self.signature && self.last == Some(TokenType::Colon);
+        self.operand = match ttype {
+            TokenType::Identifier(_) => !annotation,
+            TokenType::String(_)
+            | TokenType::Number(_)
+            | TokenType::Integer(_)
+            | TokenType::BigInteger
+            | TokenType::True
+            | TokenType::False
+            | TokenType::Nil
+            | TokenType::This => true,
+            TokenType::RightParen => !self.headers.pop().unwrap_or(false),
+            _ => false,
+        };
+        if ttype == TokenType::LeftParen {
+            let header = match self.last {
+                Some(TokenType::If | TokenType::While | TokenType::For | TokenType::Catch) => true,
+                Some(TokenType::Func) => true,
+                Some(TokenType::Identifier(_)) => self.before == Some(TokenType::Func),
+                _ => false,
+            };
+            self.headers.push(header);
         }
+        self.signature = match ttype {
+            TokenType::RightParen => !self.operand,
+            TokenType::Colon => self.signature,
+            _ => false,
+        };
+        self.before = self.last;
+        self.last = Some(ttype);
     }
 
     fn is_at_end(&self) -> bool {
-        *self.current.borrow() >= self.source.len()
+        self.current >= self.source.len()
     }
 
-    pub fn scan_tokens(mut self) -> Vec<Token> {
-        while !self.is_at_end() {
-            // at the beginning of the next lexeme
-            self.start = *self.current.borrow();
-            self.scan_token();
-        }
-        self.tokens.push(Token::new(TokenType::EOF, "", self.line));
-        self.tokens
+    pub fn scan_tokens(self) -> Vec<Token<'src>> {
+        self.collect()
     }
 
-    fn scan_token(&mut self) {
+    // None for anything that doesn't produce a token, like whitespace and comments
+    fn scan_token(&mut self) -> Option<Token<'src>> {
         let c = self.advance();
         match c {
-            '(' => self.add_token(TokenType::LeftParen),
-            ')' => self.add_token(TokenType::RightParen),
-            '{' => self.add_token(TokenType::LeftBrace),
-            '}' => self.add_token(TokenType::RightBrace),
-            ',' => self.add_token(TokenType::Comma),
-            '.' => self.add_token(TokenType::Dot),
-            '-' => self.add_token(TokenType::Minus),
-            '+' => self.add_token(TokenType::Plus),
-            ';' => self.add_token(TokenType::Semicolon),
-            '*' => self.add_token(TokenType::Star),
+            '(' => Some(self.add_token(TokenType::LeftParen)),
+            ')' => Some(self.add_token(TokenType::RightParen)),
+            '{' => Some(self.add_token(TokenType::LeftBrace)),
+            '}' => Some(self.add_token(TokenType::RightBrace)),
+            ',' => Some(self.add_token(TokenType::Comma)),
+            ':' => Some(self.add_token(TokenType::Colon)),
+            '.' => Some(self.add_token(TokenType::Dot)),
+            '-' => Some(self.add_token(TokenType::Minus)),
+            '+' => Some(self.add_token(TokenType::Plus)),
+            ';' => Some(self.add_token(TokenType::Semicolon)),
+            '*' => Some(self.add_token(TokenType::Star)),
+            '%' => Some(self.add_token(TokenType::Percent)),
 
             '!' => {
                 if self.is_match('=') {
-                    self.add_token(TokenType::BangEqual);
+                    Some(self.add_token(TokenType::BangEqual))
                 } else {
-                    self.add_token(TokenType::Bang);
+                    Some(self.add_token(TokenType::Bang))
                 }
             }
 
             '>' => {
                 if self.is_match('=') {
-                    self.add_token(TokenType::GreaterEqual);
+                    Some(self.add_token(TokenType::GreaterEqual))
                 } else {
-                    self.add_token(TokenType::Greater);
+                    Some(self.add_token(TokenType::Greater))
                 }
             }
 
             '<' => {
                 if self.is_match('=') {
-                    self.add_token(TokenType::LessEqual);
+                    Some(self.add_token(TokenType::LessEqual))
                 } else {
-                    self.add_token(TokenType::Less);
+                    Some(self.add_token(TokenType::Less))
                 }
             }
 
             '=' => {
                 if self.is_match('=') {
-                    self.add_token(TokenType::EqualEqual);
+                    Some(self.add_token(TokenType::EqualEqual))
                 } else {
-                    self.add_token(TokenType::Equal);
+                    Some(self.add_token(TokenType::Equal))
                 }
             }
 
             '/' => {
-                if self.is_match('/') {
+                // `a // b` divides.  anywhere an operand can't have just ended, it's a comment,
+                // and so is one straight after a header like `if (x)` or `fun f(a)`
+                if self.operand && self.is_match('/') {
+                    Some(self.add_token(TokenType::SlashSlash))
+                } else if self.is_match('/') {
                     while !self.is_at_end() && self.peek() != '\n' {
                         self.advance();
                     }
+                    None
                 } else {
-                    self.add_token(TokenType::Slash);
+                    Some(self.add_token(TokenType::Slash))
                 }
             }
 
@@ -139,24 +224,22 @@ impl Scanner {
 
                 if self.is_at_end() {
                     Lox::error(self.line, "unterminated string");
-                    return;
+                    return None;
                 }
 
                 // consume the closing "
                 self.advance();
 
                 // grab the contents in between the "'s
-                let b = &self.source.as_bytes()[self.start + 1..*self.current.borrow() - 1];
-                let s = String::from_utf8(b.to_vec()).unwrap();
-                self.add_token(TokenType::String(s));
+                let s = &self.source[self.start + 1..self.current - 1];
+                Some(self.add_token(TokenType::String(s)))
             }
 
-            ' ' => {}
-            '\r' => {}
-            '\t' => {}
+            ' ' | '\r' | '\t' => None,
 
             '\n' => {
                 self.line += 1;
+                None
             }
 
             n => {
@@ -167,83 +250,188 @@ impl Scanner {
 
                     // make sure there's another digit behind the ., then
                     // consume the . itself
+                    let mut float = false;
                     if self.peek() == '.' && is_digit(self.peek_next()) {
+                        float = true;
                         self.advance();
+                        while is_digit(self.peek()) {
+                            self.advance();
+                        }
                     }
 
-                    while is_digit(self.peek()) {
-                        self.advance();
+                    // same for an exponent, which needs digits after the e and its sign
+                    if self.peek() == 'e' || self.peek() == 'E' {
+                        let sign = matches!(self.peek_next(), '+' | '-') as usize;
+                        if is_digit(self.peek_at(1 + sign)) {
+                            float = true;
+                            self.current += 1 + sign;
+                            while is_digit(self.peek()) {
+                                self.advance();
+                            }
+                        }
                     }
 
-                    let val = self.source[self.start..*self.current.borrow()]
-                        .parse::<f64>()
-                        .unwrap();
-                    self.add_token(TokenType::Number(val));
+                    let lexeme = self.current_str();
+                    if float {
+                        // anything the loops above accept is a valid float, huge exponents
+                        // included (they come out as inf)
+                        let val = lexeme.parse::<f64>().unwrap_or(f64::NAN);
+                        return Some(self.add_token(TokenType::Number(val)));
+                    }
+                    match lexeme.parse::<i64>() {
+                        Ok(val) => Some(self.add_token(TokenType::Integer(val))),
+                        Err(_) => Some(self.add_token(TokenType::BigInteger)),
+                    }
                 } else if is_alpha(n) {
                     while is_alphanum(self.peek()) {
                         self.advance();
                     }
-                    let cur = &self.current_string();
-                    let tt = keyword(cur);
-                    match tt {
-                        Some(t) => self.add_token(t),
-                        None => self.add_token(TokenType::Identifier(cur.to_string())),
+                    let cur = self.current_str();
+                    match keyword(cur) {
+                        Some(t) => Some(self.add_token(t)),
+                        None => Some(self.add_token(TokenType::Identifier(cur))),
                     }
                 } else {
-                    Lox::error(self.line, &format!("unexpected '{}'", n));
-                    todo!();
+                    // n is only the first byte of anything outside ascii, step over the rest of
+                    // the character so later slices stay on char boundaries
+                    while !self.source.is_char_boundary(self.current) {
+                        self.current += 1;
+                    }
+                    Lox::error(self.line, &format!("unexpected '{}'", self.current_str()));
+                    None
                 }
             }
         }
     }
 
-    fn advance(&self) -> char {
-        self.bump_current(1);
-        return self.current_char();
+    fn advance(&mut self) -> char {
+        self.current += 1;
+        self.current_char()
     }
 
-    fn bump_current(&self, c: usize) {
-        self.current.replace_with(|&mut cur| cur + c);
-    }
-
-    fn is_match(&self, expect: char) -> bool {
+    fn is_match(&mut self, expect: char) -> bool {
         if self.peek() == expect {
-            self.bump_current(1);
+            self.current += 1;
             return true;
         }
         false
     }
 
     fn current_char(&self) -> char {
-        // lazy assume ascii and just index by byte position
-        // will totally explode or do something stupid in the (unlikely) case we
-        // have like emoji in the source code
-        let c = self.source.as_bytes()[*self.current.borrow() - 1] as char;
-        c
+        // lazy assume ascii and just index by byte position.  anything else only shows up in
+        // strings and comments, which slice on ascii quotes and newlines, or gets reported as
+        // unexpected
+        self.source.as_bytes()[self.current - 1] as char
     }
 
-    fn current_string(&self) -> String {
-        let b = &self.source.as_bytes()[self.start..*self.current.borrow()];
-        String::from_utf8(b.to_vec()).unwrap()
+    fn current_str(&self) -> &'src str {
+        &self.source[self.start..self.current]
     }
 
     fn peek(&self) -> char {
         if self.is_at_end() {
             return '\0';
         }
-        self.source.as_bytes()[*self.current.borrow()] as char
+        self.source.as_bytes()[self.current] as char
     }
 
     fn peek_next(&self) -> char {
-        if *self.current.borrow() + 1 >= self.source.len() {
+        self.peek_at(1)
+    }
+
+    fn peek_at(&self, n: usize) -> char {
+        if self.current + n >= self.source.len() {
             return '\0';
         }
-        self.source.as_bytes()[*self.current.borrow() + 1] as char
+        self.source.as_bytes()[self.current + n] as char
+    }
+
+    fn add_token(&self, tt: TokenType<'src>) -> Token<'src> {
+        Token::new(tt, self.current_str(), self.line)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn streams_borrowed_tokens() {
+        let src = "var s = \"hi\"; // done\nprint s;";
+        let mut sc = Scanner::new(src);
+
+        let tok = sc.next().unwrap();
+        assert_eq!(tok.ttype, TokenType::Var);
+        assert_eq!(sc.next().unwrap().ttype, TokenType::Identifier("s"));
+        sc.next();
+        let s = sc.next().unwrap();
+        assert_eq!(s.ttype, TokenType::String("hi"));
+        // lexemes are slices of the source, not copies
+        assert!(std::ptr::eq(s.lexeme.as_ptr(), src[8..].as_ptr()));
+
+        let rest: Vec<_> = sc.map(|t| t.ttype).collect();
+        assert_eq!(
+            rest,
+            vec![
+                TokenType::Semicolon,
+                TokenType::Print,
+                TokenType::Identifier("s"),
+                TokenType::Semicolon,
+                TokenType::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers_and_floor_division() {
+        let src = "1 2.5 3e2 4E-1 5e 9223372036854775808 7 // 2 % 3; // comment\nf(x) // y";
+        let types: Vec<_> = Scanner::new(src).map(|t| t.ttype).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Integer(1),
+                TokenType::Number(2.5),
+                TokenType::Number(300.0),
+                TokenType::Number(0.4),
+                TokenType::Integer(5),
+                TokenType::Identifier("e"),
+                TokenType::BigInteger,
+                TokenType::Integer(7),
+                TokenType::SlashSlash,
+                TokenType::Integer(2),
+                TokenType::Percent,
+                TokenType::Integer(3),
+                TokenType::Semicolon,
+                TokenType::Identifier("f"),
+                TokenType::LeftParen,
+                TokenType::Identifier("x"),
+                TokenType::RightParen,
+                TokenType::SlashSlash,
+                TokenType::Identifier("y"),
+                TokenType::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn comments_after_headers() {
+        let src = "if (x) // note\nwhile (f(x)) // note\nfun g(a): Number // doc\n\
+                   var h = fun (b) // doc\ncatch (e) // note";
+        let types: Vec<_> = Scanner::new(src).map(|t| t.ttype).collect();
+        assert!(!types.contains(&TokenType::SlashSlash), "{:?}", types);
+        assert!(!types.contains(&TokenType::Identifier("note")));
+        assert!(!types.contains(&TokenType::Identifier("doc")));
     }
 
-    fn add_token(&mut self, tt: TokenType) {
-        let text = &self.source[self.start..*self.current.borrow()];
-        let token = Token::new(tt, text, self.line);
-        self.tokens.push(token);
+    #[test]
+    fn skips_shebang() {
+        let toks: Vec<_> = Scanner::new(
+            "#!/usr/bin/env rlox
+print 1;",
+        )
+        .collect();
+        assert_eq!(toks[0].ttype, TokenType::Print);
+        assert_eq!(toks[0].line, 2);
+        assert_eq!(Scanner::new("#!rlox").next().unwrap().ttype, TokenType::EOF);
     }
 }
@@ -1,74 +1,51 @@
-use crate::expr::*;
+use crate::ast::*;
+use crate::r#type::*;
 use crate::token::*;
-use std::fmt;
+use crate::token_type::*;
 
 #[derive(Clone, Debug, PartialEq)]
-pub enum Stmt {
-    Expr(Expr),
-    Print(Expr),
-    If(Expr, Box<Stmt>, Box<Option<Stmt>>),
-    Block(Vec<Stmt>),
-    Var(Token, Option<Expr>),
-    While(Expr, Box<Stmt>),
+pub enum Stmt<'src> {
+    Expr(ExprId),
+    Print(ExprId),
+    If(ExprId, StmtId, Option<StmtId>),
+    Block(Vec<StmtId>),
+    Var(Token<'src>, Option<Type>, Option<ExprId>),
+    While(ExprId, StmtId),
+    // loops until something unwinds out of it, what `while (true)` turns into after optimizing
+    Loop(StmtId),
+    // declarations live in their own table so function values can refer to them by id
+    Function(FunId),
+    Return(Token<'src>, Option<ExprId>),
+    Throw(Token<'src>, ExprId),
+    Try(StmtId, Option<(Token<'src>, StmtId)>, Option<StmtId>),
+    // hands a value out of a generator and suspends it until the next one is asked for
+    Yield(Token<'src>, Option<ExprId>),
+    // for (x in gen) body, with x bound fresh for every value the generator produces
+    ForIn(Token<'src>, ExprId, StmtId),
 }
 
-impl Stmt {
-    pub fn new_expr(expr: &Expr) -> Stmt {
-        Stmt::Expr(expr.clone())
-    }
-
-    pub fn new_print(expr: &Expr) -> Stmt {
-        Stmt::Print(expr.clone())
-    }
-
-    pub fn new_if(expr: &Expr, then: &Stmt, els: &Option<Stmt>) -> Stmt {
-        Stmt::If(expr.clone(), Box::new(then.clone()), Box::new(els.clone()))
-    }
-
-    pub fn new_var(name: &Token) -> Stmt {
-        Stmt::Var(name.clone(), None)
-    }
-
-    pub fn new_var_init(name: &Token, initializer: &Expr) -> Stmt {
-        Stmt::Var(name.clone(), Some(initializer.clone()))
-    }
-
-    pub fn new_block(stmts: &Vec<Stmt>) -> Stmt {
-        Stmt::Block(stmts.clone())
-    }
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunDecl<'src> {
+    // anonymous functions get the `fun` keyword here in place of a name
+    pub name: Token<'src>,
+    pub params: Vec<Token<'src>>,
+    pub body: Vec<StmtId>,
+    pub sig: Signature,
+    // has a `yield` somewhere in its own body.  calling it makes a generator instead of running it
+    pub generator: bool,
+}
 
-    pub fn new_while(cond: &Expr, body: &Stmt) -> Stmt {
-        Stmt::While(cond.clone(), Box::new(body.clone()))
+impl<'src> FunDecl<'src> {
+    pub fn is_anonymous(&self) -> bool {
+        self.name.ttype == TokenType::Func
     }
-}
 
-impl fmt::Display for Stmt {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Stmt::Expr(expr) => write!(f, "\nexpr:{:?}", expr),
-            Stmt::Print(expr) => write!(f, "\nprint expr:{:?}", expr),
-            Stmt::If(expr, _then, _else) => write!(f, "\nif expr:{:?}", expr),
-            Stmt::Block(stmts) => {
-                write!(f, "\nblock")?;
-                for stmt in stmts {
-                    write!(f, "\n{:?}", stmt)?;
-                }
-                Ok(())
-                // let mut rc;
-            }
-            Stmt::While(cond, block) => {
-                write!(f, "\nwhile")?;
-                write!(f, "\ncond {:?}", cond)?;
-                write!(f, "\nblock {:?}", block)?;
-                Ok(())
-            }
-            Stmt::Var(token, oexpr) => {
-                if let Some(expr) = oexpr {
-                    write!(f, "\nvar:{:?} expr:{:?}", token.lexeme, expr)
-                } else {
-                    write!(f, "\nvar:{:?} expr:none", token.lexeme)
-                }
-            }
+    // what shows up in stack traces and error messages
+    pub fn label(&self) -> &'src str {
+        if self.is_anonymous() {
+            "<anonymous>"
+        } else {
+            self.name.lexeme
         }
     }
 }
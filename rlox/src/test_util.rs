@@ -0,0 +1,31 @@
+// helpers shared by the unit tests
+use crate::ast::*;
+use crate::interpreter::*;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+// print output lands here instead of stdout
+#[derive(Clone, Default)]
+struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// runs the whole program and hands back what it printed along with how it ended.  `setup` gets
+// the interpreter first, for limits, args and the like
+pub fn run_with(ast: &Ast, setup: impl FnOnce(&mut Interpreter)) -> (String, ExecuteResult) {
+    let cap = Capture::default();
+    let mut ir = Interpreter::with_output(ast, Box::new(cap.clone()));
+    setup(&mut ir);
+    let res = ir.run(&ast.root);
+    let out = String::from_utf8(cap.0.borrow().clone()).unwrap();
+    (out, res)
+}
@@ -1,18 +1,20 @@
 use crate::token_type::TokenType;
 
-#[derive(Clone, Debug, PartialEq)]
-pub struct Token {
-    pub ttype: TokenType,
-    pub lexeme: String,
+// lexemes point straight into the source text, so tokens are cheap to copy around and the source
+// has to outlive everything built from them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Token<'src> {
+    pub ttype: TokenType<'src>,
+    pub lexeme: &'src str,
     pub line: usize,
 }
 
-impl Token {
-    pub fn new(ttype: TokenType, lexeme: &str, line: usize) -> Self {
+impl<'src> Token<'src> {
+    pub fn new(ttype: TokenType<'src>, lexeme: &'src str, line: usize) -> Self {
         Token {
-            ttype: ttype,
-            lexeme: lexeme.to_string(),
-            line: line,
+            ttype,
+            lexeme,
+            line,
         }
     }
 }
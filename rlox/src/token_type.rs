@@ -1,16 +1,20 @@
-#[derive(Clone, Debug, PartialEq)]
-pub enum TokenType {
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenType<'src> {
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
     Comma,
+    Colon,
     Dot,
     Minus,
     Plus,
     Semicolon,
     Slash,
+    SlashSlash,
     Star,
+    Percent,
 
     Bang,
     BangEqual,
@@ -37,10 +41,100 @@ pub enum TokenType {
     True,
     Var,
     While,
+    Throw,
+    Try,
+    Catch,
+    Finally,
+    Yield,
+    In,
 
-    Identifier(String),
-    String(String),
+    Identifier(&'src str),
+    String(&'src str),
+    // floats, anything with a '.' or an exponent
     Number(f64),
+    Integer(i64),
+    // an integer literal too big for i64.  the digits are in the lexeme
+    BigInteger,
 
     EOF,
 }
+
+// names as they appear in ast dumps.  payloads aren't listed, they come back out of the lexeme
+const NAMES: &[(TokenType<'static>, &str)] = &[
+    (TokenType::LeftParen, "LeftParen"),
+    (TokenType::RightParen, "RightParen"),
+    (TokenType::LeftBrace, "LeftBrace"),
+    (TokenType::RightBrace, "RightBrace"),
+    (TokenType::Comma, "Comma"),
+    (TokenType::Colon, "Colon"),
+    (TokenType::Dot, "Dot"),
+    (TokenType::Minus, "Minus"),
+    (TokenType::Plus, "Plus"),
+    (TokenType::Semicolon, "Semicolon"),
+    (TokenType::Slash, "Slash"),
+    (TokenType::SlashSlash, "SlashSlash"),
+    (TokenType::Star, "Star"),
+    (TokenType::Percent, "Percent"),
+    (TokenType::Bang, "Bang"),
+    (TokenType::BangEqual, "BangEqual"),
+    (TokenType::Equal, "Equal"),
+    (TokenType::EqualEqual, "EqualEqual"),
+    (TokenType::Greater, "Greater"),
+    (TokenType::GreaterEqual, "GreaterEqual"),
+    (TokenType::Less, "Less"),
+    (TokenType::LessEqual, "LessEqual"),
+    (TokenType::And, "And"),
+    (TokenType::Class, "Class"),
+    (TokenType::Else, "Else"),
+    (TokenType::False, "False"),
+    (TokenType::Func, "Func"),
+    (TokenType::For, "For"),
+    (TokenType::If, "If"),
+    (TokenType::Nil, "Nil"),
+    (TokenType::Or, "Or"),
+    (TokenType::Print, "Print"),
+    (TokenType::Return, "Return"),
+    (TokenType::Super, "Super"),
+    (TokenType::This, "This"),
+    (TokenType::True, "True"),
+    (TokenType::Var, "Var"),
+    (TokenType::While, "While"),
+    (TokenType::Throw, "Throw"),
+    (TokenType::Try, "Try"),
+    (TokenType::Catch, "Catch"),
+    (TokenType::Finally, "Finally"),
+    (TokenType::Yield, "Yield"),
+    (TokenType::In, "In"),
+    (TokenType::BigInteger, "BigInteger"),
+    (TokenType::EOF, "EOF"),
+];
+
+impl<'src> TokenType<'src> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenType::Identifier(_) => "Identifier",
+            TokenType::String(_) => "String",
+            TokenType::Number(_) => "Number",
+            TokenType::Integer(_) => "Integer",
+            tt => NAMES.iter().find(|(t, _)| t == tt).unwrap().1,
+        }
+    }
+
+    // rebuild a token type from its name and the lexeme it was scanned from
+    pub fn from_name(name: &str, lexeme: &'src str) -> Option<TokenType<'src>> {
+        match name {
+            "Identifier" => Some(TokenType::Identifier(lexeme)),
+            "String" => {
+                let inner = lexeme.strip_prefix('"')?.strip_suffix('"')?;
+                Some(TokenType::String(inner))
+            }
+            "Number" => lexeme.parse().ok().map(TokenType::Number),
+            "Integer" => lexeme.parse().ok().map(TokenType::Integer),
+            "BigInteger" if !lexeme.is_empty() && lexeme.bytes().all(|b| b.is_ascii_digit()) => {
+                Some(TokenType::BigInteger)
+            }
+            "BigInteger" => None,
+            _ => NAMES.iter().find(|(_, n)| *n == name).map(|(t, _)| *t),
+        }
+    }
+}
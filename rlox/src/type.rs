@@ -1,3 +1,7 @@
+use std::fmt;
+
+// static types for the optional annotations.  Object is the "don't know / don't care" type and is
+// compatible with everything in either direction
 #[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Object,
@@ -5,15 +9,42 @@ pub enum Type {
     Boolean,
     Number,
     String,
-};
+    Function,
+}
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum Value {
-    // Object,
-    // Nil,
-    Boolean(bool),
-    Number(f64),
-    String(String),
-};
+impl Type {
+    pub fn from_name(name: &str) -> Option<Type> {
+        match name {
+            "Object" => Some(Type::Object),
+            "Nil" => Some(Type::Nil),
+            "Boolean" => Some(Type::Boolean),
+            "Number" => Some(Type::Number),
+            "String" => Some(Type::String),
+            "Function" => Some(Type::Function),
+            _ => None,
+        }
+    }
+
+    pub fn accepts(&self, other: &Type) -> bool {
+        *self == Type::Object || *other == Type::Object || self == other
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+// annotations attached to a function declaration, one slot per param plus the return type
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Signature {
+    pub params: Vec<Option<Type>>,
+    pub ret: Option<Type>,
+}
 
+impl Signature {
+    pub fn is_annotated(&self) -> bool {
+        self.ret.is_some() && self.params.iter().all(|p| p.is_some())
+    }
 }
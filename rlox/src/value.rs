@@ -1,5 +1,8 @@
+use crate::bigint::*;
 use crate::function::*;
+use crate::generator::*;
 use std::fmt;
+use std::rc::Rc;
 
 // todo: PartialEq is only required for test comparisons at the moment
 // however, PartialEq is kind of viral because every variant must also derive it
@@ -11,21 +14,82 @@ use std::fmt;
 pub enum Value {
     Nil,
     Bool(bool),
+    // floats.  Int and BigInt are the integers, kept as an i64 for as long as they fit
     Number(f64),
-    String(String),
-    Function(LoxFunction),
+    Int(i64),
+    BigInt(BigInt),
+    // strings and functions never change once made, so copies share them.  cloning a value is
+    // never more than a refcount bump, which matters since every variable read is a clone
+    String(Rc<str>),
+    Function(Rc<LoxFunction>),
+    Native(Rc<NativeFunction>),
+    Generator(GeneratorRef),
+    Error(ErrorObject),
 }
 
-impl fmt::Display for Value {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let out;
+// what a catch clause sees when the interpreter itself raised the error
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorObject {
+    pub msg: String,
+    pub line: usize,
+}
+
+impl ErrorObject {
+    pub fn new(msg: &str, line: usize) -> Self {
+        Self {
+            msg: msg.to_string(),
+            line,
+        }
+    }
+}
+
+impl Value {
+    // integers go back to plain Int as soon as they fit again
+    pub fn from_bigint(n: BigInt) -> Value {
+        match n.to_i64() {
+            Some(i) => Value::Int(i),
+            None => Value::BigInt(n),
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
         match self {
-            Value::Nil => out = format!("(nil)"),
-            Value::Bool(val) => out = format!("{}", val),
-            Value::Number(val) => out = format!("{}", val),
-            Value::String(val) => out = format!("{}", val),
-            Value::Function(_func) => out = format!("fn"),
+            Value::Nil => "nil",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) | Value::Int(_) | Value::BigInt(_) => "number",
+            Value::String(_) => "string",
+            Value::Function(_) => "function",
+            Value::Native(_) => "native function",
+            Value::Generator(_) => "generator",
+            Value::Error(_) => "error",
         }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let out = match self {
+            Value::Nil => "(nil)".to_string(),
+            Value::Bool(val) => format!("{}", val),
+            Value::Number(val) => format_float(*val),
+            Value::Int(val) => val.to_string(),
+            Value::BigInt(val) => val.to_string(),
+            Value::String(val) => val.to_string(),
+            Value::Function(_func) => "fn".to_string(),
+            Value::Native(_func) => "native fn".to_string(),
+            Value::Generator(gen) => gen.to_string(),
+            Value::Error(err) => format!("error: {}", err.msg),
+        };
         write!(f, "{}", out)
     }
 }
+
+// floats always print with a decimal point, so 2.0 doesn't pass for the integer 2
+pub fn format_float(n: f64) -> String {
+    let s = n.to_string();
+    if n.is_finite() && !s.contains('.') {
+        format!("{}.0", s)
+    } else {
+        s
+    }
+}
@@ -0,0 +1,120 @@
+// the rlox binary as a shell sees it: where scripts come from, what they're handed and what
+// status comes back
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+const RLOX: &str = env!("CARGO_BIN_EXE_rlox");
+
+fn rlox(args: &[&str]) -> Output {
+    Command::new(RLOX).args(args).output().unwrap()
+}
+
+fn rlox_stdin(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(RLOX)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(out: &Output) -> &str {
+    std::str::from_utf8(&out.stdout).unwrap()
+}
+
+#[test]
+fn inline_code() {
+    let out = rlox(&["-e", "print 1 + 2;"]);
+    assert_eq!(stdout(&out), "3\n");
+    assert_eq!(out.status.code(), Some(0));
+
+    // everything after the code belongs to the script, flags included
+    let out = rlox(&["-e", "for (a in args()) print a;", "-e", "x y"]);
+    assert_eq!(stdout(&out), "-e\nx y\n");
+}
+
+#[test]
+fn script_from_stdin() {
+    let out = rlox_stdin(&["-", "a", "b"], "for (a in args()) print a;\nexit(3);");
+    assert_eq!(stdout(&out), "a\nb\n");
+    assert_eq!(out.status.code(), Some(3));
+}
+
+#[test]
+fn exit_codes() {
+    assert_eq!(rlox(&["-e", "exit(0);"]).status.code(), Some(0));
+    assert_eq!(rlox(&["-e", "print 1 +;"]).status.code(), Some(65));
+    assert_eq!(rlox(&["-e", "print 1 @ 2;"]).status.code(), Some(65));
+    assert_eq!(rlox(&["-e", "throw 1;"]).status.code(), Some(70));
+    assert_eq!(rlox(&["-e", "exit(-1);"]).status.code(), Some(70));
+
+    let out = rlox(&["no/such/script.lox"]);
+    assert_eq!(out.status.code(), Some(66));
+    assert!(String::from_utf8_lossy(&out.stderr).starts_with("no/such/script.lox: "));
+}
+
+#[test]
+fn limits() {
+    // runaway recursion is a lox error, not a crash
+    let deep = "fun f(n) { if (n == 0) return 0; return 1 + f(n - 1); } print f(100000);";
+    let out = rlox(&["-e", deep]);
+    assert_eq!(out.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("call depth"));
+    assert_eq!(rlox(&["-e", "print 1;"]).status.code(), Some(0));
+
+    let out = rlox(&[
+        "--max-depth=10",
+        "-e",
+        "fun f(n) { if (n == 0) return 0; return 1 + f(n - 1); } print f(20);",
+    ]);
+    assert_eq!(out.status.code(), Some(70));
+    let out = rlox(&["--max-steps=100", "-e", "while (true) {}"]);
+    assert_eq!(out.status.code(), Some(70));
+    let out = rlox(&["--timeout=50", "-e", "while (true) {}"]);
+    assert_eq!(out.status.code(), Some(70));
+    let out = rlox(&[
+        "--max-memory=1000",
+        "-e",
+        "var s = \"x\"; while (true) s = s + s;",
+    ]);
+    assert_eq!(out.status.code(), Some(70));
+    assert_eq!(
+        rlox(&["--max-depth=lots", "-e", "print 1;"]).status.code(),
+        Some(255)
+    );
+
+    let out = rlox(&["--no-host-natives", "-e", "print clock();"]);
+    assert_eq!(out.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("clock() is not available"));
+    // only the natives that reach outside go
+    let out = rlox(&["--no-host-natives", "-e", "exit(3);"]);
+    assert_eq!(out.status.code(), Some(3));
+}
+
+#[cfg(unix)]
+#[test]
+fn shebang() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let script = Path::new(env!("CARGO_TARGET_TMPDIR")).join("shebang.lox");
+    fs::write(
+        &script,
+        format!("#!{}\nfor (a in args()) print a;\nexit(5);\n", RLOX),
+    )
+    .unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let out = Command::new(&script).arg("hi").output().unwrap();
+    assert_eq!(stdout(&out), "hi\n");
+    assert_eq!(out.status.code(), Some(5));
+}
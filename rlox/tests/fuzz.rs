@@ -0,0 +1,527 @@
+// throws junk at the front end and interpreter and fails on any panic.  inputs come from a few
+// places: raw bytes, soups of real tokens, small programs built from the grammar, and those same
+// programs with a few bytes knocked around.  everything runs under tight Limits so loops and
+// runaway recursion end in a limit error rather than a hang.
+//
+// it's deterministic by default.  RLOX_FUZZ_CASES=n runs n cases of each kind and
+// RLOX_FUZZ_SEED=n starts somewhere else, for longer runs:
+//
+//   RLOX_FUZZ_CASES=100000 RLOX_FUZZ_SEED=$RANDOM cargo test --release --test fuzz
+use std::env;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+use std::time::Duration;
+
+use rlox::ast_json;
+use rlox::checker::Checker;
+use rlox::error::Severity;
+use rlox::interpreter::Interpreter;
+use rlox::json::Json;
+use rlox::limits::Limits;
+use rlox::optimizer::Optimizer;
+use rlox::parser::Parser;
+use rlox::scanner::Scanner;
+use rlox::token_type::TokenType;
+
+const DEFAULT_CASES: u64 = 300;
+const DEFAULT_SEED: u64 = 0x5eed;
+
+// the rlox binary gives scripts a far bigger stack, but the front end's limits are meant to hold up
+// on a plain 8M main thread too, so that's what the harness gets rather than the smaller one test
+// threads get
+const STACK: usize = 8 << 20;
+
+// xorshift64*, plenty for picking junk
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+fn env_u64(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+fn limits() -> Limits {
+    Limits {
+        steps: Some(20_000),
+        call_depth: Some(32),
+        time: Some(Duration::from_secs(1)),
+        // small enough that squaring a bigint up to it stays quick in a debug build
+        bytes: Some(1 << 16),
+        host_natives: false,
+    }
+}
+
+// the same trip a script takes through `rlox script`, with the ast also round tripped through
+// json since that's another way in.  anything the parser builds has to load back
+fn run(src: &str, optimize: bool) {
+    let toks = Scanner::new(src).scan_tokens();
+    let mut ast = match Parser::new(&toks).parse() {
+        Ok(ast) => ast,
+        Err(_) => return,
+    };
+    let doc = Json::parse(&ast_json::dump(&ast).pretty()).expect("dumped ast isn't valid json");
+    if let Err(e) = ast_json::load(&doc) {
+        panic!("dumped ast doesn't load: {}", e.msg);
+    }
+
+    let diags = Checker::new(&ast, false).check();
+    if diags.iter().any(|d| d.severity == Severity::Error) {
+        return;
+    }
+    if optimize {
+        Optimizer::new(&mut ast).optimize();
+    }
+
+    let mut ir = Interpreter::with_output(&ast, Box::new(io::sink()));
+    ir.set_limits(limits());
+    let _ = ir.interpret(&ast.root);
+}
+
+// run every case from `gen`, failing with enough to reproduce on the first panic
+fn fuzz(kind: &'static str, gen: fn(&mut Rng) -> String) {
+    let cases = env_u64("RLOX_FUZZ_CASES", DEFAULT_CASES);
+    let seed = env_u64("RLOX_FUZZ_SEED", DEFAULT_SEED);
+
+    let failure = thread::Builder::new()
+        .stack_size(STACK)
+        .spawn(move || {
+            let mut rng = Rng::new(seed);
+            for case in 0..cases {
+                let src = gen(&mut rng);
+                let optimize = case % 2 == 0;
+                if panic::catch_unwind(AssertUnwindSafe(|| run(&src, optimize))).is_err() {
+                    return Some((case, src));
+                }
+            }
+            None
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+
+    if let Some((case, src)) = failure {
+        panic!(
+            "{} case {} panicked (RLOX_FUZZ_SEED={}):\n{:?}",
+            kind, case, seed, src
+        );
+    }
+}
+
+// a made-up script's worth of bytes, leaning on lox's own punctuation so it isn't all rejected
+// at the first character
+fn random_bytes(rng: &mut Rng) -> String {
+    const ALPHABET: &[u8] = b"(){}[],.;:+-*/%!=<>\"' \n\t0123456789eE_abcfnrtvxyz@#$\\";
+    let len = rng.below(200);
+    let bytes: Vec<u8> = (0..len)
+        .map(|_| match rng.below(8) {
+            0 => rng.next() as u8,
+            _ => ALPHABET[rng.below(ALPHABET.len())],
+        })
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+const TOKENS: &[&str] = &[
+    "(",
+    ")",
+    "{",
+    "}",
+    ",",
+    ".",
+    ";",
+    ":",
+    "+",
+    "-",
+    "*",
+    "/",
+    "//",
+    "%",
+    "!",
+    "!=",
+    "=",
+    "==",
+    "<",
+    "<=",
+    ">",
+    ">=",
+    "and",
+    "or",
+    "class",
+    "else",
+    "false",
+    "true",
+    "nil",
+    "for",
+    "fun",
+    "if",
+    "print",
+    "return",
+    "var",
+    "while",
+    "throw",
+    "try",
+    "catch",
+    "finally",
+    "yield",
+    "in",
+    "x",
+    "y",
+    "f",
+    "e",
+    "0",
+    "1",
+    "2.5",
+    "1e400",
+    "99999999999999999999",
+    "\"s\"",
+    "\"",
+    "clock",
+    "next",
+    "done",
+];
+
+// real tokens in no particular order
+fn token_soup(rng: &mut Rng) -> String {
+    let len = rng.below(60);
+    let toks: Vec<&str> = (0..len).map(|_| rng.pick(TOKENS)).collect();
+    toks.join(" ")
+}
+
+const NAMES: &[&str] = &["a", "b", "c", "f", "g"];
+
+// builds programs that mostly parse, so the checker and interpreter get exercised and not just
+// the error paths.  depth keeps any one program from getting out of hand
+struct Gen<'r> {
+    rng: &'r mut Rng,
+    depth: usize,
+}
+
+impl<'r> Gen<'r> {
+    fn program(&mut self) -> String {
+        let mut out = String::new();
+        for _ in 0..1 + self.rng.below(6) {
+            out += &self.stmt();
+            out.push('\n');
+        }
+        out
+    }
+
+    fn block(&mut self) -> String {
+        let mut out = String::from("{ ");
+        for _ in 0..self.rng.below(4) {
+            out += &self.stmt();
+            out.push(' ');
+        }
+        out + "}"
+    }
+
+    fn stmt(&mut self) -> String {
+        self.depth += 1;
+        let choice = if self.depth > 4 {
+            0
+        } else {
+            self.rng.below(12)
+        };
+        let s = match choice {
+            0 => format!("print {};", self.expr()),
+            1 => format!("var {} = {};", self.name(), self.expr()),
+            2 => format!("{} = {};", self.name(), self.expr()),
+            3 => format!("if ({}) {} else {}", self.expr(), self.stmt(), self.stmt()),
+            4 => format!("while ({}) {}", self.expr(), self.block()),
+            5 => format!(
+                "for (var i = 0; i < {}; i = i + 1) {}",
+                self.expr(),
+                self.block()
+            ),
+            6 => format!("fun {}({}) {}", self.name(), self.params(), self.block()),
+            7 => format!("return {};", self.expr()),
+            8 => format!(
+                "try {} catch ({}) {} finally {}",
+                self.block(),
+                self.name(),
+                self.block(),
+                self.block()
+            ),
+            9 => format!("throw {};", self.expr()),
+            10 => format!(
+                "fun {}() {{ yield {}; yield {}; }}",
+                self.name(),
+                self.expr(),
+                self.expr()
+            ),
+            _ => format!("for ({} in {}) {}", self.name(), self.expr(), self.block()),
+        };
+        self.depth -= 1;
+        s
+    }
+
+    fn params(&mut self) -> String {
+        let params: Vec<&str> = (0..self.rng.below(3))
+            .map(|_| self.rng.pick(NAMES))
+            .collect();
+        params.join(", ")
+    }
+
+    fn name(&mut self) -> &'static str {
+        self.rng.pick(NAMES)
+    }
+
+    fn expr(&mut self) -> String {
+        self.depth += 1;
+        let choice = if self.depth > 6 {
+            self.rng.below(3)
+        } else {
+            self.rng.below(9)
+        };
+        let e = match choice {
+            0 => self
+                .rng
+                .pick(&[
+                    "0",
+                    "1",
+                    "-1",
+                    "2.5",
+                    "0.0",
+                    "1e308",
+                    "9223372036854775807",
+                    "123456789012345678901234567890",
+                    "nil",
+                    "true",
+                    "false",
+                    "\"s\"",
+                    "\"\"",
+                ])
+                .to_string(),
+            1 => self.name().to_string(),
+            2 => "clock".to_string(),
+            3 => {
+                let op = self.rng.pick(&[
+                    "+", "-", "*", "/", "//", "%", "<", "<=", ">", ">=", "==", "!=", "and", "or",
+                ]);
+                format!("({} {} {})", self.expr(), op, self.expr())
+            }
+            4 => format!("{}{}", self.rng.pick(&["-", "!"]), self.expr()),
+            5 => {
+                let args: Vec<String> = (0..self.rng.below(3)).map(|_| self.expr()).collect();
+                format!("{}({})", self.expr(), args.join(", "))
+            }
+            6 => format!("fun ({}) {}", self.params(), self.block()),
+            7 => format!("{}{}", self.expr(), self.rng.pick(&[".done", ".next()"])),
+            _ => format!("({} = {})", self.name(), self.expr()),
+        };
+        self.depth -= 1;
+        e
+    }
+}
+
+fn grammar(rng: &mut Rng) -> String {
+    Gen { rng, depth: 0 }.program()
+}
+
+// a grammar program with a handful of bytes deleted, duplicated or swapped for junk
+fn mutated(rng: &mut Rng) -> String {
+    let mut bytes = grammar(rng).into_bytes();
+    for _ in 0..1 + rng.below(4) {
+        if bytes.is_empty() {
+            break;
+        }
+        let at = rng.below(bytes.len());
+        match rng.below(3) {
+            0 => {
+                bytes.remove(at);
+            }
+            1 => bytes.insert(at, bytes[at]),
+            _ => bytes[at] = rng.next() as u8,
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[test]
+fn random_input() {
+    fuzz("bytes", random_bytes);
+}
+
+#[test]
+fn token_soups() {
+    fuzz("tokens", token_soup);
+}
+
+#[test]
+fn grammar_programs() {
+    fuzz("grammar", grammar);
+}
+
+#[test]
+fn mutated_programs() {
+    fuzz("mutated", mutated);
+}
+
+// inputs that used to panic.  these live out here rather than next to the scanner and parser
+// since reporting errors bumps the global counters the lib tests check
+#[test]
+fn known_crashes() {
+    for src in [
+        "@",
+        "x = 1 # 2;",
+        "print \"\u{e9}\"; \u{e9}\u{1f600}",
+        ")",
+        "1e400",
+        "fun",
+    ] {
+        let res = panic::catch_unwind(|| run(src, true));
+        assert!(res.is_ok(), "panicked on {:?}", src);
+    }
+
+    // junk is reported and skipped, whole characters at a time
+    let toks: Vec<_> = Scanner::new("a \u{1f600}b").map(|t| t.ttype).collect();
+    assert_eq!(
+        toks,
+        vec![
+            TokenType::Identifier("a"),
+            TokenType::Identifier("b"),
+            TokenType::EOF
+        ]
+    );
+
+    // a token list with no EOF on the end
+    let toks = Scanner::new("print 1").scan_tokens();
+    assert!(Parser::new(&toks[..2]).parse().is_err());
+    assert!(Parser::new(&[]).parse().is_ok());
+}
+
+// the things random generation is unlikely to stumble on: nesting far past anything sensible
+#[test]
+fn deep_nesting() {
+    let n = 100_000;
+    let mut cases = vec![
+        "(".repeat(n),
+        format!("print {}1{};", "(".repeat(n), ")".repeat(n)),
+        format!("print {}1;", "-".repeat(n)),
+        format!("print {}1;", "!".repeat(n)),
+        format!("print {}1;", "1 * ".repeat(n)),
+        format!("print {}1;", "1 < ".repeat(n)),
+        format!("print {}1;", "1 + ".repeat(n)),
+        format!("{}{}", "{".repeat(n), "}".repeat(n)),
+        format!("{}print 1;", "if (true) ".repeat(n)),
+        format!("{}print 1;", "while (false) ".repeat(n)),
+        format!("var f = {}nil;", "fun () ".repeat(n)),
+        format!("{}{}", "fun f() { ".repeat(n), "}".repeat(n)),
+        format!("print f{};", "()".repeat(n)),
+        // flat, but as deep a tree as the parser will build
+        format!("print {}1;", "1 + ".repeat(990)),
+        format!("print {}1;", "1 - ".repeat(990)),
+        "fun f(n) { return 1 + f(n + 1); } f(0);".to_string(),
+        "fun f() { yield f().done; } print f().done;".to_string(),
+        "var x = \"x\"; while (true) x = x + x;".to_string(),
+        "var x = 2; while (true) x = x * x;".to_string(),
+    ];
+    // either side of the limit, where the parser's count has to agree with the json loader's
+    for k in 985..1000 {
+        cases.push(format!("var x = {}1;", "1 + ".repeat(k)));
+        cases.push(format!("for (;; x = {}1) {{}}", "1 + ".repeat(k)));
+    }
+
+    thread::Builder::new()
+        .stack_size(STACK)
+        .spawn(move || {
+            for src in &cases {
+                let res = panic::catch_unwind(AssertUnwindSafe(|| run(src, true)));
+                assert!(res.is_ok(), "panicked on {:?}", &src[..src.len().min(80)]);
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+// json nested too deep is an error, the same as too deep a script.  a tree that's valid json
+// still has to pass the parser's limit to load
+#[test]
+fn deep_json() {
+    let group = r#"{"expr": "Grouping", "inner": "#;
+    let print = move |n: usize| {
+        format!(
+            r#"{{"stmts": [{{"stmt": "Print", "line": 1, "expr": {}{}{}}}]}}"#,
+            group.repeat(n),
+            r#"{"expr": "Variable", "name": {"type": "Identifier", "lexeme": "x", "line": 1}}"#,
+            "}".repeat(n)
+        )
+    };
+
+    thread::Builder::new()
+        .stack_size(STACK)
+        .spawn(move || {
+            let err = Json::parse(&"[".repeat(200_000)).unwrap_err();
+            assert_eq!(err.msg, "too much nesting");
+            assert_eq!(err.msg, Json::parse(&print(20_000)).unwrap_err().msg);
+
+            let doc = Json::parse(&print(1500)).unwrap();
+            assert_eq!(ast_json::load(&doc).unwrap_err().msg, "too much nesting");
+            let doc = Json::parse(&print(900)).unwrap();
+            assert!(ast_json::load(&doc).is_ok());
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+// long flat chains aren't nesting, however many links they have
+#[test]
+fn long_chains() {
+    let n = 250;
+    let else_ifs: String = (1..=n)
+        .map(|i| format!(" else if (x == {}) print {};", i, i))
+        .collect();
+    let cases = vec![
+        format!("print 1{};", " + 1".repeat(n)),
+        format!("print 1{};", " - 1".repeat(n)),
+        format!("print true{};", " and true".repeat(n)),
+        format!("print false{};", " or false".repeat(n)),
+        format!("print 1{};", " == 1".repeat(n)),
+        format!(
+            "var x = {}; if (x == 0) print 0;{} else print -1;",
+            n, else_ifs
+        ),
+        format!("fun f() {{ return f; }} print f{};", "()".repeat(n)),
+    ];
+
+    thread::Builder::new()
+        .stack_size(STACK)
+        .spawn(move || {
+            for src in &cases {
+                let toks = Scanner::new(src).scan_tokens();
+                let ast = Parser::new(&toks).parse();
+                assert!(ast.is_ok(), "rejected {:?}", &src[..80]);
+                let ast = ast.unwrap();
+                let mut ir = Interpreter::with_output(&ast, Box::new(io::sink()));
+                assert!(ir.run(&ast.root).is_ok(), "failed {:?}", &src[..80]);
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
@@ -0,0 +1,111 @@
+// every tests/golden/NAME.lox gets run and its stdout compared against NAME.out, once through the
+// interpreter and once compiled to C.  the C half is skipped if there's no cc around
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const RLOX: &str = env!("CARGO_BIN_EXE_rlox");
+
+fn scripts() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let mut scripts: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension() == Some("lox".as_ref()))
+        .collect();
+    scripts.sort();
+    assert!(!scripts.is_empty());
+    scripts
+}
+
+fn expected(script: &Path) -> String {
+    fs::read_to_string(script.with_extension("out")).unwrap()
+}
+
+fn stdout(cmd: &mut Command) -> String {
+    let out = cmd.output().unwrap();
+    assert!(out.status.success(), "{:?} failed: {:?}", cmd, out);
+    String::from_utf8(out.stdout).unwrap()
+}
+
+fn have_cc() -> bool {
+    Command::new("cc").arg("--version").output().is_ok()
+}
+
+#[test]
+fn interpreter() {
+    for script in scripts() {
+        let out = stdout(Command::new(RLOX).arg(&script));
+        assert_eq!(out, expected(&script), "{}", script.display());
+    }
+}
+
+// the C file and executable for a script, built in the target's tmp dir
+fn compile(script: &Path) -> PathBuf {
+    let tmp = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let name = script.file_stem().unwrap().to_str().unwrap();
+    let c = tmp.join(format!("{}.c", name));
+    let exe = tmp.join(name);
+    stdout(
+        Command::new(RLOX)
+            .args(["compile", "--target", "c"])
+            .arg(script)
+            .arg("-o")
+            .arg(&c),
+    );
+    // collecting on every allocation flushes out anything the gc can't see
+    stdout(
+        Command::new("cc")
+            .args(["-DRT_GC_STRESS", "-Wall", "-Werror"])
+            .arg(&c)
+            .arg("-o")
+            .arg(&exe)
+            .arg("-lm"),
+    );
+    exe
+}
+
+#[test]
+fn compiled_to_c() {
+    if !have_cc() {
+        eprintln!("no cc, skipping");
+        return;
+    }
+
+    for script in scripts() {
+        let out = stdout(&mut Command::new(compile(&script)));
+        assert_eq!(out, expected(&script), "{}", script.display());
+    }
+}
+
+// recursing too deep is the same uncatchable error either way, with what was printed before it
+// still making it out
+#[test]
+fn call_depth_limit() {
+    if !have_cc() {
+        eprintln!("no cc, skipping");
+        return;
+    }
+
+    let script = Path::new(env!("CARGO_TARGET_TMPDIR")).join("too_deep.lox");
+    fs::write(
+        &script,
+        "print \"before\";\nfun down(n) { return 1 + down(n + 1); }\n\
+         try { down(0); } catch (e) { print \"caught\"; }\n",
+    )
+    .unwrap();
+    let mut interpreted = Command::new(RLOX);
+    interpreted.arg(&script);
+    for mut cmd in [interpreted, Command::new(compile(&script))] {
+        let out = cmd.output().unwrap();
+        assert_eq!(out.status.code(), Some(70), "{:?}", cmd);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "before\n");
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(
+            stderr.starts_with("runtime error: call depth limit of 1000 exceeded\n[line 2]"),
+            "{:?}: {}",
+            cmd,
+            &stderr[..stderr.len().min(200)]
+        );
+    }
+}